@@ -70,3 +70,23 @@ pub fn get_app_mq_bucket() -> Result<String, std::env::VarError> {
     };
     Ok(value)
 }
+
+pub fn get_openai_api_base() -> Result<String, std::env::VarError> {
+    std::env::var("OPENAI_API_BASE")
+}
+
+pub fn get_openai_org_id() -> Result<String, std::env::VarError> {
+    std::env::var("OPENAI_ORG_ID")
+}
+
+pub fn get_app_embedding_model() -> Result<String, std::env::VarError> {
+    std::env::var("APP_EMBEDDING_MODEL")
+}
+
+pub fn get_app_reranker_model() -> Result<String, std::env::VarError> {
+    std::env::var("APP_RERANKER_MODEL")
+}
+
+pub fn get_app_embedding_quantization() -> Result<String, std::env::VarError> {
+    std::env::var("APP_EMBEDDING_QUANTIZATION")
+}
@@ -17,6 +17,19 @@ impl Composer {
             Composer::OpenAIComposer(composer) => composer.compose(context, query).await,
         }
     }
+
+    pub fn model_name(&self) -> String {
+        match self {
+            Composer::OpenAIComposer(composer) => composer.model().to_string(),
+        }
+    }
+
+    /// Tokens reserved for the completion.
+    pub fn max_tokens(&self) -> usize {
+        match self {
+            Composer::OpenAIComposer(composer) => *composer.max_tokens() as usize,
+        }
+    }
 }
 
 impl From<OpenAIComposer> for Composer {
@@ -36,6 +36,14 @@ impl OpenAIComposer {
     pub fn set_max_tokens(&mut self, max_tokens: u16) {
         self.max_tokens = max_tokens;
     }
+
+    pub fn model(&self) -> &ComposerEnum {
+        &self.model
+    }
+
+    pub fn max_tokens(&self) -> &u16 {
+        &self.max_tokens
+    }
 }
 
 impl OpenAIComposer {
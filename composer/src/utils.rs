@@ -1,6 +1,9 @@
-use crate::{Composer, ComposerEnum, OpenAIComposer, Result};
-use indexer::Context;
-use tracing::info;
+use crate::{prompt, Composer, ComposerEnum, OpenAIComposer, Result};
+use indexer::{Context, Provenance, TokenBudget};
+use tracing::{debug, info};
+
+// Role and separator tokens of the chat messages.
+const MESSAGE_OVERHEAD_TOKENS: usize = 8;
 
 pub async fn get_composer(max_output_tokens: usize) -> Result<Composer> {
     let mut composer = OpenAIComposer::default();
@@ -10,31 +13,54 @@ pub async fn get_composer(max_output_tokens: usize) -> Result<Composer> {
     Ok(Composer::OpenAIComposer(composer))
 }
 
+/// Context budget of a prompt of `model` answering `query`: the context window
+/// of the model without the prompt template, the query, the message overhead
+/// and the `completion_tokens` of the answer, capped to `max_input_tokens`.
+pub fn context_budget(
+    model: &str,
+    query: &str,
+    completion_tokens: usize,
+    max_input_tokens: usize,
+) -> Result<TokenBudget> {
+    let mut budget = TokenBudget::for_model(model)?;
+    budget.reserve_text(&prompt::build_prompt_content("", query));
+    budget.reserve(MESSAGE_OVERHEAD_TOKENS);
+    budget.reserve(completion_tokens);
+    budget.limit(max_input_tokens);
+    Ok(budget)
+}
+
+/// Packs the best `nodes` into the prompt of the composer model, after the
+/// prompt template, the query and the completion tokens, up to
+/// `max_input_tokens` of context. The last node that does not fit whole is cut
+/// at a sentence boundary. Returns the message, the context and the provenance
+/// of the nodes packed in it.
 pub async fn compose_message_with_graph(
     composer: &Composer,
-    nodes: Vec<(f32, String)>,
+    nodes: Vec<Context>,
     query: &str,
     max_input_tokens: usize,
-) -> Result<(String, String)> {
+) -> Result<(String, String, Vec<Provenance>)> {
     info!("compose message");
-    let max_num_token = max_input_tokens;
-    let mut contexts = vec![];
-    let mut current_tokens = 0;
+    let budget = context_budget(
+        &composer.model_name(),
+        query,
+        composer.max_tokens(),
+        max_input_tokens,
+    )?;
 
-    for (idx, node) in nodes.iter().enumerate() {
-        current_tokens += node.1.chars().count() / 4;
-        if current_tokens > max_num_token {
-            println!("{} contents found", nodes.len());
-            println!("{} contents used", idx);
-            break;
-        }
-        let context = node.1.as_str();
-        contexts.push(context);
-        println!(
+    let texts = nodes
+        .iter()
+        .map(|node| node.data().as_str())
+        .collect::<Vec<&str>>();
+    let contexts = budget.pack(&texts, true);
+    info!("{} contents found, {} used", nodes.len(), contexts.len());
+    for (idx, (node, context)) in nodes.iter().zip(&contexts).enumerate() {
+        debug!(
             "Chunk: {} Score: {:2} => Content: {:?}",
             idx + 1,
-            node.0,
-            node.1,
+            node.score(),
+            context,
         );
     }
 
@@ -60,7 +86,12 @@ pub async fn compose_message_with_graph(
         }
     };
 
-    Ok((message, context))
+    let sources = nodes
+        .iter()
+        .take(contexts.len())
+        .map(|node| node.provenance().clone())
+        .collect();
+    Ok((message, context, sources))
 }
 
 pub async fn compose_message_with_context(
@@ -18,4 +18,7 @@ pub struct Document {
     creation_time: i64,
     #[builder(default = "active".to_string())]
     status: String,
+    #[serde(default)]
+    #[builder(default)]
+    tags: Vec<String>,
 }
@@ -1,6 +1,8 @@
 mod callback_task;
 mod download_task;
 mod indexing_task;
+mod reindex_scope;
+mod reindex_task;
 mod task;
 mod task_kind;
 mod upload_task;
@@ -8,6 +10,8 @@ mod upload_task;
 pub use callback_task::CallbackTask;
 pub use download_task::DownloadTask;
 pub use indexing_task::IndexingTask;
+pub use reindex_scope::ReindexScope;
+pub use reindex_task::ReindexTask;
 pub use task::Task;
 pub use task_kind::TaskKind;
 pub use upload_task::UploadTask;
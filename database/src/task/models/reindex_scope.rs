@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// Documents re-indexed by a `ReindexTask`, each variant holding an id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReindexScope {
+    Document(String),
+    Collection(String),
+    Group(String),
+}
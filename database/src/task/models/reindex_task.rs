@@ -0,0 +1,23 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::ReindexScope;
+
+/// Rebuilds the graphs of already indexed documents with another embedding
+/// model. Collection and group tasks are split into one task per document.
+#[derive(Debug, Clone, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(rename_all = "snake_case")]
+pub struct ReindexTask {
+    model_id: String,
+    scope: ReindexScope,
+    creator_id: String,
+    #[builder(default = None)]
+    callback_url: Option<String>,
+    /// Position of the document in the split task, from 1.
+    #[builder(default = None)]
+    position: Option<usize>,
+    /// Documents in the split task.
+    #[builder(default = None)]
+    total: Option<usize>,
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{CallbackTask, DownloadTask, IndexingTask, UploadTask};
+use crate::{CallbackTask, DownloadTask, IndexingTask, ReindexTask, UploadTask};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "kind", content = "payload")]
@@ -8,6 +8,7 @@ pub enum TaskKind {
     CallbackTask(CallbackTask),
     DownloadTask(DownloadTask),
     IndexingTask(IndexingTask),
+    ReindexTask(ReindexTask),
     UploadTask(UploadTask),
 }
 
@@ -29,6 +30,12 @@ impl From<IndexingTask> for TaskKind {
     }
 }
 
+impl From<ReindexTask> for TaskKind {
+    fn from(value: ReindexTask) -> Self {
+        TaskKind::ReindexTask(value)
+    }
+}
+
 impl From<UploadTask> for TaskKind {
     fn from(value: UploadTask) -> Self {
         TaskKind::UploadTask(value)
@@ -1,3 +1,5 @@
+use std::ops::Range;
+
 use rayon::prelude::*;
 use tiktoken_rs::{p50k_base, CoreBPE};
 
@@ -48,4 +50,47 @@ impl OverlappedChunker {
             })
             .collect::<Vec<String>>()
     }
+
+    /// Same as `chunks`, but keeps the trailing lines and returns a single
+    /// chunk for content shorter than one window, so short sections are not
+    /// dropped.
+    pub fn chunks_with_remainder(&self, lines: &Vec<String>) -> Vec<String> {
+        self.line_ranges_with_remainder(lines)
+            .into_iter()
+            .map(|range| {
+                lines[range]
+                    .iter()
+                    .map(|line| format!("{}\n", line))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Lines of each chunk of `chunks_with_remainder`, as ranges of `lines`.
+    pub fn line_ranges_with_remainder(&self, lines: &Vec<String>) -> Vec<Range<usize>> {
+        let mut windows: Vec<Range<usize>> = vec![];
+        let mut start = 0;
+        let mut buf = String::new();
+
+        let chunk_token = self.max_token / 2;
+        for (index, line) in lines.iter().enumerate() {
+            let buf_token_count = self.token_counter.encode_with_special_tokens(&buf).len();
+            let line_token_count = self.token_counter.encode_with_special_tokens(&line).len();
+
+            if !buf.is_empty() && (line_token_count + buf_token_count) > chunk_token {
+                windows.push(start..index);
+                start = index;
+                buf = String::new();
+            }
+            buf.push_str(&line);
+            buf.push_str("\n");
+        }
+        if !buf.trim().is_empty() {
+            windows.push(start..lines.len());
+        }
+        if windows.len() < 2 {
+            return windows;
+        }
+        windows.windows(2).map(|w| w[0].start..w[1].end).collect()
+    }
 }
@@ -4,7 +4,7 @@ use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
-use super::{DocumentMeta, Node};
+use super::{DocumentMeta, Node, Section, SectionSplitter};
 
 #[derive(Debug, Clone, TypedBuilder, Getters, Serialize, Deserialize)]
 pub struct Document {
@@ -42,9 +42,22 @@ impl Document {
             .collect::<Vec<String>>();
         lines
     }
+
+    /// Splits the document at every heading. Each section starts with its
+    /// heading line. Headings without content are skipped.
+    pub fn sections(&self) -> Vec<Section> {
+        let mut splitter = SectionSplitter::new();
+        let mut sections = self
+            .nodes
+            .iter()
+            .filter_map(|node| splitter.push(node))
+            .collect::<Vec<Section>>();
+        sections.extend(splitter.finish());
+        sections
+    }
 }
 
-fn extract_node_content(node: &Node) -> String {
+pub(super) fn extract_node_content(node: &Node) -> String {
     let mut buf = String::new();
 
     match node {
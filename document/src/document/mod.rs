@@ -13,6 +13,8 @@ mod node;
 mod paragraph;
 mod point;
 mod position;
+mod section;
+mod section_splitter;
 mod table;
 mod table_cell;
 mod table_row;
@@ -34,6 +36,8 @@ pub use node::Node;
 pub use paragraph::Paragraph;
 pub use point::Point;
 pub use position::Position;
+pub use section::Section;
+pub use section_splitter::SectionSplitter;
 pub use table::Table;
 pub use table_cell::TableCell;
 pub use table_row::TableRow;
@@ -12,6 +12,11 @@ pub struct Point {
     column: usize,
     /// 0-indexed integer representing a character in a source file.
     offset: usize,
+    /// 1-indexed integer representing a page in a source file, `None` for
+    /// formats without pages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[builder(default = None)]
+    page: Option<usize>,
 }
 impl Point {
     pub(crate) fn init() -> Self {
@@ -19,6 +24,7 @@ impl Point {
             line: 1,
             column: 0,
             offset: 0,
+            page: None,
         }
     }
 
@@ -32,6 +38,10 @@ impl Point {
 
 impl fmt::Debug for Point {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{} ({})", self.line, self.column, self.offset)
+        write!(f, "{}:{} ({})", self.line, self.column, self.offset)?;
+        if let Some(page) = self.page {
+            write!(f, " p{}", page)?;
+        }
+        Ok(())
     }
 }
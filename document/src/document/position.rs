@@ -20,6 +20,7 @@ impl Position {
             .line(*self.end.line())
             .offset(*self.end.offset())
             .column(0)
+            .page(*self.end.page())
             .build();
     }
 
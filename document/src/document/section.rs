@@ -0,0 +1,24 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::Position;
+
+/// Lines of a document under one heading.
+#[derive(Debug, Clone, TypedBuilder, Getters, Serialize, Deserialize)]
+pub struct Section {
+    /// Heading text, `None` for the content before the first heading.
+    title: Option<String>,
+    /// Depth of the heading, `0` without heading.
+    depth: u8,
+    /// Titles of the enclosing headings, outermost first, ending with
+    /// `title`.
+    #[builder(default)]
+    #[serde(default)]
+    path: Vec<String>,
+    lines: Vec<String>,
+    /// Position of every line, the one of the node it comes from.
+    #[builder(default)]
+    #[serde(default)]
+    positions: Vec<Option<Position>>,
+}
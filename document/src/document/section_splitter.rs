@@ -0,0 +1,74 @@
+use super::{document::extract_node_content, Node, Position, Section};
+
+/// Splits nodes into sections at every heading, as they are read. Each section
+/// starts with its heading line. Headings without content are skipped.
+#[derive(Debug, Default)]
+pub struct SectionSplitter {
+    headings: Vec<(u8, String)>,
+    lines: Vec<String>,
+    positions: Vec<Option<Position>>,
+}
+
+impl SectionSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `node`, and returns the section it ends when it is a heading.
+    pub fn push(&mut self, node: &Node) -> Option<Section> {
+        let content = extract_node_content(node);
+        let mut section = None;
+        if let Node::Heading(heading) = node {
+            section = build_section(
+                &self.headings,
+                std::mem::take(&mut self.lines),
+                std::mem::take(&mut self.positions),
+            );
+            let depth = *heading.depth();
+            while self.headings.last().is_some_and(|(last, _)| *last >= depth) {
+                self.headings.pop();
+            }
+            self.headings.push((depth, content.trim().to_string()));
+        }
+        self.lines
+            .extend(content.split("\n").map(|s| s.to_string()));
+        self.positions.resize(self.lines.len(), node.position());
+        section
+    }
+
+    /// Last section, once all nodes were added.
+    pub fn finish(self) -> Option<Section> {
+        build_section(&self.headings, self.lines, self.positions)
+    }
+}
+
+fn build_section(
+    headings: &[(u8, String)],
+    lines: Vec<String>,
+    positions: Vec<Option<Position>>,
+) -> Option<Section> {
+    let (depth, title) = match headings.last() {
+        Some((depth, title)) => (*depth, Some(title.to_string())),
+        None => (0, None),
+    };
+    let skip = match title {
+        Some(_) => 1,
+        None => 0,
+    };
+    if lines.iter().skip(skip).all(|line| line.trim().is_empty()) {
+        return None;
+    }
+    let path = headings
+        .iter()
+        .map(|(_, title)| title.to_string())
+        .collect();
+    Some(
+        Section::builder()
+            .title(title)
+            .depth(depth)
+            .path(path)
+            .lines(lines)
+            .positions(positions)
+            .build(),
+    )
+}
@@ -1,9 +1,23 @@
+use std::path::Path;
+
 use pdfium_render::prelude::*;
 
 use super::{extractor, Analyser, Element, TextElement, TextElementGroupKind};
 use crate::{
-    document::{Document, Heading, ListItem, Node, Paragraph, Point, Position, Text, TextGroup},
-    utils, Result,
+    document::{
+        Document,
+        DocumentMeta,
+        Heading,
+        ListItem,
+        Node,
+        Paragraph,
+        Point,
+        Position,
+        Text,
+        TextGroup,
+    },
+    utils,
+    Result,
 };
 
 pub struct PdfDocumentParser {
@@ -34,8 +48,47 @@ impl PdfDocumentParser {
             .flatten()
             .collect::<Vec<Element>>();
 
-        let groups = self.analyzer.analyse(elements);
+        let nodes = self.build_nodes(elements, &mut Position::init());
+        let result = Document::builder().meta(meta).nodes(nodes).build();
+        Ok(result)
+    }
+
+    /// Number of pages of the PDF file at `path`, read without parsing them.
+    pub fn page_count(&self, path: &Path) -> Result<usize> {
+        let pdfium_document = self.pdfium.load_pdf_from_file(path, None)?;
+        Ok(pdfium_document.pages().len() as usize)
+    }
+
+    /// Parses the PDF file at `path` `pages_per_batch` pages at a time, and
+    /// passes the nodes of every batch to `on_nodes`, so only one batch is in
+    /// memory. Headings are told apart with the font statistics of their batch
+    /// instead of the whole document. Returns the document metadata.
+    pub fn parse_file_in_batches(
+        &self,
+        path: &Path,
+        pages_per_batch: usize,
+        mut on_nodes: impl FnMut(Vec<Node>) -> Result<()>,
+    ) -> Result<DocumentMeta> {
+        let pdfium_document = self.pdfium.load_pdf_from_file(path, None)?;
+        let meta = extractor::extract_meta(pdfium_document.metadata());
         let mut last_position = Position::init();
+        let mut elements = vec![];
+        for (page_num, pdf_page) in pdfium_document.pages().iter().enumerate() {
+            elements.extend(extractor::extract_pdf_page(&pdf_page, page_num).unwrap_or(vec![]));
+            if (page_num + 1) % pages_per_batch.max(1) == 0 && !elements.is_empty() {
+                on_nodes(self.build_nodes(elements, &mut last_position))?;
+                elements = vec![];
+            }
+        }
+        if !elements.is_empty() {
+            on_nodes(self.build_nodes(elements, &mut last_position))?;
+        }
+        Ok(meta)
+    }
+
+    // Groups `elements` into document nodes, placed after `last_position`.
+    fn build_nodes(&self, elements: Vec<Element>, last_position: &mut Position) -> Vec<Node> {
+        let groups = self.analyzer.analyse(elements);
         let mut nodes = vec![];
 
         for group in groups {
@@ -50,7 +103,7 @@ impl PdfDocumentParser {
                                 .iter()
                                 .map(|y| {
                                     let text_node = create_text_node(y, last_position.clone());
-                                    last_position = match text_node.position() {
+                                    *last_position = match text_node.position() {
                                         Some(position) => position,
                                         None => last_position.clone(),
                                     };
@@ -83,7 +136,7 @@ impl PdfDocumentParser {
                                 .iter()
                                 .map(|y| {
                                     let text_node = create_text_node(y, last_position.clone());
-                                    last_position = match text_node.position() {
+                                    *last_position = match text_node.position() {
                                         Some(position) => position,
                                         None => last_position.clone(),
                                     };
@@ -130,7 +183,7 @@ impl PdfDocumentParser {
                                 .iter()
                                 .map(|y| {
                                     let text_node = create_text_node(y, last_position.clone());
-                                    last_position = match text_node.position() {
+                                    *last_position = match text_node.position() {
                                         Some(position) => position,
                                         None => last_position.clone(),
                                     };
@@ -166,7 +219,7 @@ impl PdfDocumentParser {
                                     let text_node =
                                         create_text_node(&current, last_position.clone());
 
-                                    last_position = match text_node.position() {
+                                    *last_position = match text_node.position() {
                                         Some(position) => position,
                                         None => last_position.clone(),
                                     };
@@ -205,9 +258,7 @@ impl PdfDocumentParser {
                   // TextElementGroupKind::PageNumber => {}
             }
         }
-        let nodes = utils::grouper::group_list_items(nodes);
-        let result = Document::builder().meta(meta).nodes(nodes).build();
-        Ok(result)
+        utils::grouper::group_list_items(nodes)
     }
 }
 
@@ -217,12 +268,14 @@ fn create_text_node(text_element: &TextElement, last_position: Position) -> Node
     let start_line = *last_position.end().line();
     let start_column = *last_position.end().column();
     let start_offset = *last_position.end().offset();
+    let page = Some(text_element.page().page_num() + 1);
     let position = Position::builder()
         .start(
             Point::builder()
                 .line(start_line)
                 .column(start_column)
                 .offset(start_offset)
+                .page(page)
                 .build(),
         )
         .end(
@@ -230,6 +283,7 @@ fn create_text_node(text_element: &TextElement, last_position: Position) -> Node
                 .line(start_line)
                 .column(start_column + text.len())
                 .offset(start_offset + text.len())
+                .page(page)
                 .build(),
         )
         .build();
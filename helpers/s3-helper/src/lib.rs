@@ -1,4 +1,4 @@
-use std::{path::Path, time::Duration};
+use std::{fs::File, io::Read, path::Path, time::Duration};
 
 use aws_sdk_s3::{
     error::SdkError,
@@ -11,12 +11,23 @@ use aws_sdk_s3::{
     },
     presigning::PresigningConfig,
     primitives::ByteStream,
-    types::{BucketLocationConstraint, CreateBucketConfiguration, Delete, ObjectIdentifier},
+    types::{
+        BucketLocationConstraint,
+        CompletedMultipartUpload,
+        CompletedPart,
+        CreateBucketConfiguration,
+        Delete,
+        ObjectIdentifier,
+    },
     Client,
 };
 mod error;
 use error::Error;
 
+// Size of the parts of multipart uploads. S3 requires at least 5 MiB for
+// every part but the last.
+const PART_SIZE: u64 = 8 * 1024 * 1024;
+
 pub async fn delete_bucket(client: &Client, bucket_name: &str) -> Result<(), Error> {
     client.delete_bucket().bucket(bucket_name).send().await?;
     println!("Bucket deleted");
@@ -164,6 +175,97 @@ pub async fn upload_object_with_content(
         .await
 }
 
+/// Uploads the file at `file_name` in parts, so only one part is held in
+/// memory. The upload is aborted when a part fails.
+pub async fn upload_object_multipart(
+    client: &Client,
+    bucket_name: &str,
+    file_name: &Path,
+    key: &str,
+) -> Result<(), Error> {
+    let upload = client
+        .create_multipart_upload()
+        .bucket(bucket_name)
+        .key(key)
+        .send()
+        .await?;
+    let upload_id = upload
+        .upload_id()
+        .ok_or(Error::unhandled("missing multipart upload id"))?;
+
+    let parts = match upload_parts(client, bucket_name, file_name, key, upload_id).await {
+        Ok(parts) => parts,
+        Err(err) => {
+            client
+                .abort_multipart_upload()
+                .bucket(bucket_name)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await?;
+            return Err(err);
+        }
+    };
+    client
+        .complete_multipart_upload()
+        .bucket(bucket_name)
+        .key(key)
+        .upload_id(upload_id)
+        .multipart_upload(
+            CompletedMultipartUpload::builder()
+                .set_parts(Some(parts))
+                .build(),
+        )
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+async fn upload_parts(
+    client: &Client,
+    bucket_name: &str,
+    file_name: &Path,
+    key: &str,
+    upload_id: &str,
+) -> Result<Vec<CompletedPart>, Error> {
+    let mut file = File::open(file_name).map_err(Error::unhandled)?;
+    let mut parts = vec![];
+    loop {
+        let mut part = vec![];
+        (&mut file)
+            .take(PART_SIZE)
+            .read_to_end(&mut part)
+            .map_err(Error::unhandled)?;
+        // An empty file is uploaded as a single empty part.
+        if part.is_empty() && !parts.is_empty() {
+            break;
+        }
+        let last = (part.len() as u64) < PART_SIZE;
+        let part_number = parts.len() as i32 + 1;
+        let output = client
+            .upload_part()
+            .bucket(bucket_name)
+            .key(key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(part))
+            .send()
+            .await?;
+        parts.push(
+            CompletedPart::builder()
+                .set_e_tag(output.e_tag().map(|e_tag| e_tag.to_string()))
+                .part_number(part_number)
+                .build(),
+        );
+        if last {
+            break;
+        }
+    }
+
+    Ok(parts)
+}
+
 pub async fn create_bucket(
     client: &Client,
     bucket_name: &str,
@@ -0,0 +1,287 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+};
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::{candidate::Candidate, HnswParams};
+use crate::{
+    graph::{Graph, Node, NodeId},
+    math,
+};
+
+const MAX_LEVEL: usize = 16;
+
+/// Hierarchical navigable small world index over the nodes of a `Graph`.
+///
+/// Only the link structure is stored. Embeddings are read from the graph the
+/// index was built from, so both have to be loaded together.
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct HnswIndex {
+    params: HnswParams,
+    node_ids: Vec<NodeId>,
+    /// Neighbours of each node, per layer.
+    links: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    max_level: usize,
+}
+
+impl HnswIndex {
+    pub fn build(graph: &Graph, params: HnswParams) -> Self {
+        let mut node_ids = graph.node_map().keys().cloned().collect::<Vec<NodeId>>();
+        node_ids.sort();
+        let vectors = node_ids
+            .iter()
+            .map(|id| graph.node_map()[id].embeddings().as_slice())
+            .collect::<Vec<&[f32]>>();
+        Self::build_rows(node_ids, &vectors, params)
+    }
+
+    /// Index of the nodes `node_ids`, sorted, with the embeddings `vectors` in
+    /// the same order, for nodes whose embeddings are not in a graph yet.
+    pub fn build_rows(node_ids: Vec<NodeId>, vectors: &[&[f32]], params: HnswParams) -> Self {
+        let mut index = Self {
+            params,
+            node_ids,
+            links: vec![],
+            entry_point: None,
+            max_level: 0,
+        };
+        for position in 0..vectors.len() {
+            index.insert(position, vectors);
+        }
+        index
+    }
+
+    /// Returns up to `k` nodes of `graph`, most similar first. Returns `None`
+    /// when the index was not built from `graph`.
+    pub fn search<'g>(
+        &self,
+        graph: &'g Graph,
+        query: &[f32],
+        k: usize,
+        ef_search: usize,
+    ) -> Option<Vec<(f32, &'g Node)>> {
+        let nodes = self.nodes(graph)?;
+        let vectors = nodes
+            .iter()
+            .map(|node| node.embeddings().as_slice())
+            .collect::<Vec<&[f32]>>();
+
+        let Some(mut entry_point) = self.entry_point else {
+            return Some(vec![]);
+        };
+        for layer in (1..=self.max_level).rev() {
+            entry_point = self.search_layer(query, entry_point, 1, layer, &vectors)[0].index;
+        }
+        let result = self
+            .search_layer(query, entry_point, ef_search.max(k), 0, &vectors)
+            .into_iter()
+            .take(k)
+            .map(|candidate| (candidate.score, nodes[candidate.index]))
+            .collect();
+
+        Some(result)
+    }
+
+    fn nodes<'g>(&self, graph: &'g Graph) -> Option<Vec<&'g Node>> {
+        if self.node_ids.len() != graph.node_count() {
+            return None;
+        }
+        self.node_ids
+            .iter()
+            .map(|id| graph.node_map().get(id))
+            .collect()
+    }
+
+    fn insert(&mut self, position: usize, vectors: &[&[f32]]) {
+        let level = self.random_level(position);
+        self.links.push(vec![vec![]; level + 1]);
+
+        let Some(mut entry_point) = self.entry_point else {
+            self.entry_point = Some(position);
+            self.max_level = level;
+            return;
+        };
+
+        let query = vectors[position];
+        for layer in (level + 1..=self.max_level).rev() {
+            entry_point = self.search_layer(query, entry_point, 1, layer, vectors)[0].index;
+        }
+
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(
+                query,
+                entry_point,
+                *self.params.ef_construction(),
+                layer,
+                vectors,
+            );
+            let neighbours = candidates
+                .iter()
+                .take(*self.params.m())
+                .map(|candidate| candidate.index)
+                .collect::<Vec<usize>>();
+            for &neighbour in &neighbours {
+                self.links[neighbour][layer].push(position);
+                self.prune(neighbour, layer, vectors);
+            }
+            self.links[position][layer] = neighbours;
+            entry_point = candidates[0].index;
+        }
+
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry_point = Some(position);
+        }
+    }
+
+    fn prune(&mut self, position: usize, layer: usize, vectors: &[&[f32]]) {
+        let max_links = match layer {
+            0 => self.params.m() * 2,
+            _ => *self.params.m(),
+        };
+        if self.links[position][layer].len() <= max_links {
+            return;
+        }
+
+        let base = vectors[position];
+        let mut candidates = self.links[position][layer]
+            .iter()
+            .map(|&index| Candidate {
+                score: math::cosine_similarity_slice(base, vectors[index]),
+                index,
+            })
+            .collect::<Vec<Candidate>>();
+        candidates.sort_by(|a, b| b.cmp(a));
+        self.links[position][layer] = candidates
+            .into_iter()
+            .take(max_links)
+            .map(|candidate| candidate.index)
+            .collect();
+    }
+
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_point: usize,
+        ef: usize,
+        layer: usize,
+        vectors: &[&[f32]],
+    ) -> Vec<Candidate> {
+        let ef = ef.max(1);
+        let first = Candidate {
+            score: math::cosine_similarity_slice(query, vectors[entry_point]),
+            index: entry_point,
+        };
+        let mut visited = HashSet::from([entry_point]);
+        let mut candidates = BinaryHeap::from([first]);
+        let mut results = BinaryHeap::from([Reverse(first)]);
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map_or(f32::MIN, |Reverse(c)| c.score);
+            if candidate.score < worst && results.len() >= ef {
+                break;
+            }
+
+            for &neighbour in &self.links[candidate.index][layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let score = math::cosine_similarity_slice(query, vectors[neighbour]);
+                let worst = results.peek().map_or(f32::MIN, |Reverse(c)| c.score);
+                if results.len() < ef || score > worst {
+                    let next = Candidate {
+                        score,
+                        index: neighbour,
+                    };
+                    candidates.push(next);
+                    results.push(Reverse(next));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut results = results
+            .into_iter()
+            .map(|Reverse(candidate)| candidate)
+            .collect::<Vec<Candidate>>();
+        results.sort_by(|a, b| b.cmp(a));
+        results
+    }
+
+    // Deterministic per position, so re-indexing a document yields the same
+    // index.
+    fn random_level(&self, position: usize) -> usize {
+        let mut x = (position as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^= x >> 31;
+        let uniform = ((x >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        let level_mult = 1.0 / ((*self.params.m()).max(2) as f64).ln();
+
+        ((-uniform.ln() * level_mult) as usize).min(MAX_LEVEL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::random_graph;
+
+    fn exact_search<'g>(graph: &'g Graph, query: &[f32], k: usize) -> Vec<&'g NodeId> {
+        let mut scored = graph
+            .node_map()
+            .values()
+            .map(|node| {
+                (
+                    math::cosine_similarity_slice(query, node.embeddings()),
+                    node.id(),
+                )
+            })
+            .collect::<Vec<(f32, &NodeId)>>();
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        scored.into_iter().take(k).map(|(_, id)| id).collect()
+    }
+
+    #[test]
+    fn search_recalls_exact_scan() {
+        let graph = random_graph(500, 16, 42);
+        let index = HnswIndex::build(&graph, HnswParams::default());
+        let queries = random_graph(20, 16, 7);
+
+        let k = 10;
+        let mut found = 0;
+        for query in queries.node_map().values() {
+            let expected = exact_search(&graph, query.embeddings(), k);
+            let result = index.search(&graph, query.embeddings(), k, 64).unwrap();
+            assert_eq!(result.len(), k);
+            assert!(result.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+            found += result
+                .iter()
+                .filter(|(_, node)| expected.contains(&node.id()))
+                .count();
+        }
+        let recall = found as f32 / (k * queries.node_count()) as f32;
+        assert!(recall >= 0.95, "recall@{k}: {recall}");
+    }
+
+    #[test]
+    fn search_is_none_for_another_graph() {
+        let graph = random_graph(50, 8, 42);
+        let index = HnswIndex::build(&graph, HnswParams::default());
+        let other = random_graph(40, 8, 7);
+        assert!(index.search(&other, &[0.0; 8], 5, 64).is_none());
+    }
+
+    #[test]
+    fn search_empty_graph() {
+        let graph = random_graph(0, 8, 42);
+        let index = HnswIndex::build(&graph, HnswParams::default());
+        assert_eq!(index.search(&graph, &[1.0; 8], 5, 64).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,19 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct HnswParams {
+    /// Number of links kept per node on the upper layers; layer 0 keeps `2 * m`.
+    #[builder(default = 16)]
+    m: usize,
+    /// Size of the candidate list used while inserting nodes.
+    #[builder(default = 100)]
+    ef_construction: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
@@ -0,0 +1,8 @@
+mod candidate;
+mod hnsw_index;
+mod hnsw_params;
+mod search_strategy;
+
+pub use hnsw_index::HnswIndex;
+pub use hnsw_params::HnswParams;
+pub use search_strategy::SearchStrategy;
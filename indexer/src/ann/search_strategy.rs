@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_EF_SEARCH: usize = 64;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum SearchStrategy {
+    /// Compare the query against every node of the graph.
+    Exact,
+    /// Walk the graph's HNSW index. Larger `ef_search` trades latency for
+    /// recall. Falls back to an exact scan when the graph has no usable index.
+    Approximate { ef_search: usize },
+}
+
+impl Default for SearchStrategy {
+    fn default() -> Self {
+        SearchStrategy::Approximate {
+            ef_search: DEFAULT_EF_SEARCH,
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Migrates JSON graphs of already indexed documents to the binary format.
+//!
+//! Usage: `convert-graphs <document_id>...`, with the bucket taken from
+//! `APP_ENVIRONMENT`. JSON graphs are kept so older deployments can still read
+//! them.
+use aws_config::BehaviorVersion;
+use indexer::utils::convert_graph_in_s3;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let document_ids = std::env::args().skip(1).collect::<Vec<String>>();
+    if document_ids.is_empty() {
+        anyhow::bail!("usage: convert-graphs <document_id>...");
+    }
+
+    let bucket_name = common::vars::get_app_document_bucket()?;
+    let config = aws_config::load_defaults(BehaviorVersion::v2023_11_09()).await;
+    let s3_client = aws_sdk_s3::Client::new(&config);
+    for document_id in document_ids {
+        match convert_graph_in_s3(&s3_client, &bucket_name, &document_id).await? {
+            true => println!("converted: {}", document_id),
+            false => println!("skipped: {}", document_id),
+        }
+    }
+    Ok(())
+}
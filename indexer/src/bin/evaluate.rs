@@ -0,0 +1,110 @@
+//! Measures retrieval quality on a labeled query set, comparing configurations.
+//!
+//! Usage: `evaluate [--resources <path>] [--k 1,5,10] [--output <report.json>]
+//! <documents_dir> <queries.jsonl> [<config.json>...]`. Every configuration
+//! indexes the PDF and DOCX files of `documents_dir` and answers the queries,
+//! one JSON object per line with the query and its expected `documents` (file
+//! names) and `passages`. The first configuration is the baseline the others
+//! are compared to. Without configuration the default one is evaluated.
+use std::path::{Path, PathBuf};
+
+use indexer::evaluation::{EvalConfig, EvalQuery, EvalReport, Evaluator};
+
+const USAGE: &str = "usage: evaluate [--resources <path>] [--k 1,5,10] [--output <report.json>] \
+                     <documents_dir> <queries.jsonl> [<config.json>...]";
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut resources_path = common::vars::get_app_resources_path()?;
+    let mut ks = vec![1, 5, 10];
+    let mut output = None;
+    let mut paths = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--resources" => resources_path = args.next().ok_or(anyhow::anyhow!(USAGE))?,
+            "--k" => {
+                ks = args
+                    .next()
+                    .ok_or(anyhow::anyhow!(USAGE))?
+                    .split(',')
+                    .map(|k| k.trim().parse())
+                    .collect::<Result<Vec<usize>, _>>()?;
+            }
+            "--output" => output = Some(args.next().ok_or(anyhow::anyhow!(USAGE))?),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.len() < 2 || ks.is_empty() {
+        anyhow::bail!(USAGE);
+    }
+
+    let queries = EvalQuery::from_jsonl(&paths[1])?;
+    let configs = match paths.len() {
+        2 => vec![EvalConfig::default()],
+        _ => paths[2..]
+            .iter()
+            .map(|path| EvalConfig::from_file(path))
+            .collect::<anyhow::Result<Vec<EvalConfig>>>()?,
+    };
+    let evaluator = Evaluator::from_folder(&paths[0], &resources_path)?;
+
+    let mut reports = vec![];
+    for config in &configs {
+        println!("evaluating: {}", config.name());
+        reports.push(evaluator.evaluate(config, &queries, &ks).await?);
+    }
+    print_reports(&reports);
+
+    if let Some(output) = output {
+        std::fs::write(Path::new(&output), serde_json::to_string_pretty(&reports)?)?;
+        println!("report written to {}", output);
+    }
+    Ok(())
+}
+
+// One row per configuration. Metrics of the other configurations are followed
+// by their difference with the baseline.
+fn print_reports(reports: &[EvalReport]) {
+    let Some(baseline) = reports.first() else {
+        return;
+    };
+    let mut header = format!("{:<24}{:>8}", "config", "nodes");
+    for metrics in baseline.metrics() {
+        for name in ["recall", "mrr", "ndcg"] {
+            header.push_str(&format!("{:>16}", format!("{}@{}", name, metrics.k())));
+        }
+    }
+    println!("\n{}", header);
+
+    for report in reports {
+        let mut row = format!("{:<24}{:>8}", report.name(), report.nodes());
+        for (metrics, base) in report.metrics().iter().zip(baseline.metrics()) {
+            let values = [
+                (metrics.recall(), base.recall()),
+                (metrics.mrr(), base.mrr()),
+                (metrics.ndcg(), base.ndcg()),
+            ];
+            for (value, base) in values {
+                let cell = match std::ptr::eq(report, baseline) {
+                    true => format!("{:.3}", value),
+                    false => format!("{:.3} {:+.3}", value, value - base),
+                };
+                row.push_str(&format!("{:>16}", cell));
+            }
+        }
+        println!("{}", row);
+    }
+
+    for report in reports {
+        println!(
+            "\n{}: {} of {} queries without relevant context",
+            report.name(),
+            report.missed().len(),
+            report.queries()
+        );
+        for query in report.missed() {
+            println!("  - {}", query);
+        }
+    }
+}
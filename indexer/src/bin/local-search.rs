@@ -0,0 +1,219 @@
+//! Indexes and searches a local folder of documents, without AWS.
+//!
+//! Usage:
+//! - `local-search index [--resources <path>] [--model <model_id>]
+//!   <documents_dir> <graphs_dir>` parses the PDF and DOCX files of
+//!   `documents_dir` and writes their graphs to `graphs_dir`, laid out like the
+//!   document bucket. Embeddings of unchanged chunks are reused when a document
+//!   is indexed again, and every document is linked to the ones before it.
+//! - `local-search query [--resources <path>] [--k <top_k>] [--max-tokens
+//!   <tokens>] <graphs_dir>` reads queries from the standard input and prints
+//!   the contexts found by `search_context`, with their score and provenance.
+//! - `local-search duplicates <graphs_dir>` lists the duplicate and near
+//!   duplicate documents.
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use indexer::{
+    fingerprint::{self, DocumentFingerprint},
+    graph::{DocumentMetadata, Graph},
+    utils::{self, CHUNK_SIZE},
+    Context,
+    Indexer,
+    IndexingMeta,
+    ModelId,
+    SearchOptions,
+    TokenBudget,
+};
+
+const USAGE: &str = "usage:
+  local-search index [--resources <path>] [--model <model_id>] <documents_dir> <graphs_dir>
+  local-search query [--resources <path>] [--k <top_k>] [--max-tokens <tokens>] <graphs_dir>
+  local-search duplicates <graphs_dir>";
+// Chat model whose encoding counts the budget of the printed contexts.
+const BUDGET_MODEL: &str = "gpt-3.5-turbo";
+
+struct Args {
+    resources_path: String,
+    model_id: ModelId,
+    top_k: usize,
+    max_tokens: usize,
+    paths: Vec<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().ok_or(anyhow::anyhow!(USAGE))?;
+    let args = parse_args(args)?;
+    match (command.as_str(), args.paths.as_slice()) {
+        ("index", [documents_path, graphs_path]) => index(&args, documents_path, graphs_path).await,
+        ("query", [graphs_path]) => query(&args, graphs_path).await,
+        ("duplicates", [graphs_path]) => duplicates(graphs_path),
+        _ => anyhow::bail!(USAGE),
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Args> {
+    let mut parsed = Args {
+        resources_path: common::vars::get_app_resources_path()?,
+        model_id: ModelId::AllMiniLML6V2,
+        top_k: *SearchOptions::default().top_k(),
+        max_tokens: 1024,
+        paths: vec![],
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(anyhow::anyhow!(USAGE));
+        match arg.as_str() {
+            "--resources" => parsed.resources_path = value()?,
+            "--model" => parsed.model_id = ModelId::from_str(&value()?)?,
+            "--k" => parsed.top_k = value()?.parse()?,
+            "--max-tokens" => parsed.max_tokens = value()?.parse()?,
+            _ => parsed.paths.push(PathBuf::from(arg)),
+        }
+    }
+    Ok(parsed)
+}
+
+async fn index(args: &Args, documents_path: &Path, graphs_path: &Path) -> anyhow::Result<()> {
+    let documents = utils::parse_folder(documents_path, &args.resources_path)?;
+    let model = utils::load_embedding_model(&args.resources_path, args.model_id.clone()).await?;
+    let indexer = Indexer::new(model)?;
+    let mut graphs: Vec<Graph> = vec![];
+    for (filename, document) in documents {
+        let previous = utils::load_graph_from_folder(graphs_path, &filename)?;
+        let file_type = filename.rsplit('.').next().map(|ext| ext.to_lowercase());
+        let meta = IndexingMeta::builder()
+            .id(filename.clone())
+            .title(filename.clone())
+            .external_link(documents_path.join(&filename).to_string_lossy().to_string())
+            .metadata(
+                DocumentMetadata::builder()
+                    .creation_time(modification_time(&documents_path.join(&filename)))
+                    .file_type(file_type)
+                    .build(),
+            )
+            .build();
+        let sections = utils::indexing_sections(&document, CHUNK_SIZE);
+        let (mut graph, report) = indexer.reindex(sections, meta, previous.as_ref()).await?;
+        for other in &graphs {
+            indexer.link_document(&mut graph, other);
+        }
+        utils::save_graph_to_folder(graphs_path, &filename, &graph, &document)?;
+        println!(
+            "indexed: {} ({} nodes, added: {}, kept: {}, removed: {})",
+            filename,
+            graph.node_count(),
+            report.added(),
+            report.kept(),
+            report.removed()
+        );
+        graphs.push(graph);
+    }
+    Ok(())
+}
+
+async fn query(args: &Args, graphs_path: &Path) -> anyhow::Result<()> {
+    let graphs = utils::load_graphs_from_folder(graphs_path)?;
+    let Some(index_model) = graphs.iter().find_map(|graph| graph.index_model().clone()) else {
+        anyhow::bail!("no graph in folder: {}", graphs_path.display());
+    };
+    let model =
+        utils::load_embedding_model(&args.resources_path, ModelId::from_str(&index_model)?).await?;
+    let reranker = utils::get_reranker_model(&args.resources_path).await?;
+    let budget = TokenBudget::new(BUDGET_MODEL, args.max_tokens)?;
+    let options = SearchOptions::builder().top_k(args.top_k).build();
+    println!(
+        "loaded {} graphs indexed with {}",
+        graphs.len(),
+        index_model
+    );
+
+    let mut lines = std::io::stdin().lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let query = line?;
+        if query.trim().is_empty() {
+            continue;
+        }
+        let contexts = utils::search_context(
+            &graphs,
+            query.trim(),
+            &model,
+            reranker.as_ref(),
+            &budget,
+            &options,
+        )
+        .await?;
+        for (rank, context) in contexts.iter().enumerate() {
+            println!(
+                "\n#{} [{:.4}] {}",
+                rank + 1,
+                context.score(),
+                provenance(context)
+            );
+            for reference in context.references().iter().skip(1) {
+                println!("   also in {}", reference);
+            }
+            println!("{}", context.raw_data().trim());
+        }
+        println!();
+    }
+    Ok(())
+}
+
+// Modification time of the file in seconds, standing for its upload time.
+fn modification_time(path: &Path) -> Option<i64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let elapsed = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(elapsed.as_secs() as i64)
+}
+
+fn duplicates(graphs_path: &Path) -> anyhow::Result<()> {
+    let fingerprints = utils::load_graphs_from_folder(graphs_path)?
+        .iter()
+        .filter_map(|graph| {
+            let key = graph.reference().as_ref()?;
+            Some(DocumentFingerprint::from_graph(key, graph))
+        })
+        .collect::<Vec<DocumentFingerprint>>();
+    for duplicate in fingerprint::find_duplicates(&fingerprints) {
+        println!(
+            "{:?} [{:.2}] {} - {}",
+            duplicate.kind(),
+            duplicate.similarity(),
+            duplicate.first(),
+            duplicate.second()
+        );
+    }
+    Ok(())
+}
+
+// Document of the context, followed by the heading path and pages it was
+// taken from.
+fn provenance(context: &Context) -> String {
+    let provenance = context.provenance();
+    let mut parts = vec![provenance.document_id().as_deref().unwrap_or_default()];
+    parts.extend(
+        provenance
+            .heading_path()
+            .iter()
+            .map(|heading| heading.as_str()),
+    );
+    let pages = provenance
+        .span()
+        .as_ref()
+        .map_or(&[][..], |span| span.pages().as_slice());
+    match pages {
+        [] => parts.join(" > "),
+        [page] => format!("{} (p. {})", parts.join(" > "), page),
+        [first, .., last] => format!("{} (p. {}-{})", parts.join(" > "), first, last),
+    }
+}
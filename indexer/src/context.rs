@@ -2,10 +2,85 @@ use derive_getters::Getters;
 use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
+use crate::Provenance;
+
 #[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
 pub struct Context {
     score: f32,
     raw_data: String,
     data: String,
     reference: Option<String>,
+    /// References of every document holding the text, `reference` first, when
+    /// near duplicate contexts of several documents were collapsed into this
+    /// one.
+    #[serde(default)]
+    #[builder(default)]
+    references: Vec<String>,
+    /// Document, headings, pages and offsets the text comes from.
+    #[serde(default)]
+    #[builder(default)]
+    provenance: Provenance,
+}
+
+impl Context {
+    pub fn set_score(&mut self, score: f32) {
+        self.score = score;
+    }
+
+    /// Lists `reference` among the documents holding the text.
+    pub(crate) fn add_reference(&mut self, reference: String) {
+        if self.references.is_empty() {
+            self.references.extend(self.reference.clone());
+        }
+        if !self.references.contains(&reference) {
+            self.references.push(reference);
+        }
+    }
+
+    /// Cuts `data` to its first `len` characters, and `raw_data`, which ends
+    /// it, and the span of its provenance to match.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        let raw_chars = self.raw_data.chars().count();
+        let raw_len = len.saturating_sub(self.data.chars().count() - raw_chars);
+        self.raw_data.truncate(byte_offset(&self.raw_data, raw_len));
+        self.data.truncate(byte_offset(&self.data, len));
+        self.provenance.truncate(raw_len.min(raw_chars));
+    }
+}
+
+// Byte offset of the character `chars` of `text`, or its length when it has
+// fewer characters.
+fn byte_offset(text: &str, chars: usize) -> usize {
+    text.char_indices()
+        .nth(chars)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::SourceSpan;
+
+    #[test]
+    fn truncate_counts_characters() {
+        let span = SourceSpan::builder().start(100).end(106).build();
+        let mut context = Context::builder()
+            .score(1.0)
+            .raw_data("Größe.".to_string())
+            .data("Maße: Größe.".to_string())
+            .reference(None)
+            .provenance(Provenance::builder().span(Some(span)).build())
+            .build();
+
+        context.truncate(10);
+        assert_eq!(context.data(), "Maße: Größ");
+        assert_eq!(context.raw_data(), "Größ");
+        let span = context.provenance().span().clone().unwrap();
+        assert_eq!((*span.start(), *span.end()), (100, 104));
+
+        context.truncate(3);
+        assert_eq!(context.data(), "Maß");
+        assert!(context.raw_data().is_empty());
+        assert_eq!(*context.provenance().span().as_ref().unwrap().end(), 100);
+    }
 }
@@ -0,0 +1,28 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Removes redundant contexts and reorders the rest with maximal marginal
+/// relevance.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct DiversityOptions {
+    /// Weight of relevance against novelty, between 0 and 1. `1.0` keeps the
+    /// relevance order.
+    #[builder(default = 0.7)]
+    lambda: f32,
+    /// Contexts picked by maximal marginal relevance. The others follow in
+    /// relevance order.
+    #[builder(default = 20)]
+    top_n: usize,
+    /// A context is dropped when this share of its lines already appears in a
+    /// better ranked one.
+    #[builder(default = 0.5)]
+    max_overlap: f32,
+}
+
+impl Default for DiversityOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
@@ -0,0 +1,55 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Inputs of a local model longer than its max sequence length.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct EmbeddingStats {
+    /// Inputs embedded.
+    inputs: usize,
+    /// Inputs longer than the max sequence length, embedded in overlapping
+    /// windows pooled into one vector.
+    overflowing: usize,
+    /// Windows embedded for the overflowing inputs.
+    windows: usize,
+    /// Tokens past the last window of inputs with too many windows, left out
+    /// of their embedding.
+    truncated_tokens: usize,
+}
+
+impl EmbeddingStats {
+    /// Counts since `earlier`, a snapshot of the same model.
+    pub fn since(&self, earlier: &EmbeddingStats) -> EmbeddingStats {
+        EmbeddingStats {
+            inputs: self.inputs.saturating_sub(earlier.inputs),
+            overflowing: self.overflowing.saturating_sub(earlier.overflowing),
+            windows: self.windows.saturating_sub(earlier.windows),
+            truncated_tokens: self
+                .truncated_tokens
+                .saturating_sub(earlier.truncated_tokens),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_since_a_snapshot() {
+        let stats = |inputs, overflowing, windows, truncated_tokens| {
+            EmbeddingStats::builder()
+                .inputs(inputs)
+                .overflowing(overflowing)
+                .windows(windows)
+                .truncated_tokens(truncated_tokens)
+                .build()
+        };
+        let earlier = stats(10, 1, 3, 0);
+        assert_eq!(stats(25, 3, 9, 40).since(&earlier), stats(15, 2, 6, 40));
+        assert_eq!(
+            earlier.since(&stats(25, 3, 9, 40)),
+            EmbeddingStats::default()
+        );
+    }
+}
@@ -0,0 +1,23 @@
+#[derive(thiserror::Error, Debug)]
+pub enum EmbeddingError {
+    #[error("unknown embedding model: {0}")]
+    UnknownModel(String),
+    #[error("embedding model {0} is not registered")]
+    UnregisteredModel(String),
+    #[error("embedding model {0} needs a model file")]
+    MissingModelFile(String),
+    #[error("graph {0} has no index model")]
+    MissingIndexModel(String),
+    #[error("graph {graph} was indexed with {index_model}, not with query model {query_model}")]
+    IncompatibleModel {
+        graph: String,
+        index_model: String,
+        query_model: String,
+    },
+    #[error("graph {graph} has {actual} dimensional embeddings, query model has {expected}")]
+    IncompatibleDimension {
+        graph: String,
+        expected: usize,
+        actual: usize,
+    },
+}
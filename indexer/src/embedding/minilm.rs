@@ -1,23 +1,53 @@
-use std::path::{Path, PathBuf};
+use std::{
+    iter,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use async_trait::async_trait;
 use ndarray::{ArrayBase, IxDynImpl, OwnedRepr};
-use tokenizers::Tokenizer;
+use tokenizers::{Encoding, Tokenizer, TruncationParams};
 use tract_onnx::prelude::*;
 
-use super::ModelId;
+use super::{EmbeddingError, EmbeddingProvider, EmbeddingStats, ModelId};
 use crate::{math, Result};
 
+const DEFAULT_MAX_SEQUENCE_LENGTH: usize = 256;
+// Tokens shared by consecutive windows of an overflowing input.
+const WINDOW_STRIDE: usize = 32;
+// Windows embedded per input. Tokens past the last one are dropped.
+const MAX_WINDOWS: usize = 16;
+
 #[derive(Debug)]
 pub struct MiniLMEmbeddingModel {
     tokenizer: Tokenizer,
     model_id: ModelId,
+    dimension: usize,
     model: SimplePlan<TypedFact, Box<dyn TypedOp>, Graph<TypedFact, Box<dyn TypedOp>>>,
+    inputs: AtomicUsize,
+    overflowing: AtomicUsize,
+    windows: AtomicUsize,
+    truncated_tokens: AtomicUsize,
 }
 
 impl MiniLMEmbeddingModel {
     pub fn from_file(model_id: ModelId, model_path: &str) -> Result<Self> {
+        let dimension = model_id
+            .dimension()
+            .ok_or(EmbeddingError::UnknownModel(model_id.to_string()))?;
+        let max_sequence_length = model_id
+            .spec()
+            .max_sequence_length()
+            .unwrap_or(DEFAULT_MAX_SEQUENCE_LENGTH);
         let model_dir = PathBuf::from(model_path);
-        let tokenizer = Tokenizer::from_file(Path::join(&model_dir, "tokenizer.json")).unwrap();
+        let mut tokenizer = Tokenizer::from_file(Path::join(&model_dir, "tokenizer.json")).unwrap();
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: max_sequence_length,
+                stride: WINDOW_STRIDE,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
         let model = tract_onnx::onnx()
             .model_for_path(Path::join(&model_dir, "model.onnx"))?
             .into_optimized()?
@@ -27,6 +57,11 @@ impl MiniLMEmbeddingModel {
             tokenizer,
             model,
             model_id,
+            dimension,
+            inputs: AtomicUsize::new(0),
+            overflowing: AtomicUsize::new(0),
+            windows: AtomicUsize::new(0),
+            truncated_tokens: AtomicUsize::new(0),
         })
     }
 
@@ -35,37 +70,120 @@ impl MiniLMEmbeddingModel {
     }
 
     pub fn run(&self, text: &str) -> Result<Vec<f32>> {
-        let result = self.sentence_embeddings(&text)?;
-        Ok(result.into_raw_vec())
+        let mut embeddings = self.run_batch(&[text])?;
+        embeddings
+            .pop()
+            .ok_or(anyhow::anyhow!("no embedding returned"))
+    }
+
+    /// Inputs embedded since the model was loaded, and how many overflowed.
+    pub fn stats(&self) -> EmbeddingStats {
+        EmbeddingStats::builder()
+            .inputs(self.inputs.load(Ordering::Relaxed))
+            .overflowing(self.overflowing.load(Ordering::Relaxed))
+            .windows(self.windows.load(Ordering::Relaxed))
+            .truncated_tokens(self.truncated_tokens.load(Ordering::Relaxed))
+            .build()
+    }
+
+    pub fn run_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let result = self.batch_sentence_embeddings(texts)?;
+        let dim = result.len() / texts.len();
+        let embeddings = result
+            .into_raw_vec()
+            .chunks(dim)
+            .map(|row| row.to_vec())
+            .collect();
+        Ok(embeddings)
     }
 }
+#[async_trait]
+impl EmbeddingProvider for MiniLMEmbeddingModel {
+    async fn embed(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.run_batch(data)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_id(&self) -> String {
+        self.model_id.to_string()
+    }
+}
+
 impl MiniLMEmbeddingModel {
-    fn sentence_embeddings(
+    // Splits every input into windows of at most the max sequence length, the
+    // encoding and its overflowing encodings, and pads them to the longest one
+    // so the whole batch runs as a single `(windows, length)` tensor. Padded
+    // positions are masked out of the mean pooling. The windows of an input
+    // are averaged, weighted by their token counts.
+    fn batch_sentence_embeddings(
         &self,
-        sentence: &str,
+        sentences: &[&str],
     ) -> Result<ArrayBase<OwnedRepr<f32>, ndarray::Dim<IxDynImpl>>> {
         let model = &self.model;
         let tokenizer = &self.tokenizer;
-        let encoded_input = tokenizer.encode(sentence, true).unwrap();
-        let input_ids = encoded_input.get_ids();
-        let attention_mask = encoded_input.get_attention_mask();
-        let token_type_ids = encoded_input.get_type_ids();
-        let length = encoded_input.len();
-        let input_ids: Tensor = tract_ndarray::Array2::from_shape_vec(
-            (1, length),
-            input_ids.iter().map(|&x| x as i64).collect(),
-        )?
-        .into();
+        let encoded_inputs = tokenizer
+            .encode_batch(sentences.to_vec(), true)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+        let mut windows: Vec<&Encoding> = vec![];
+        let mut owners = vec![];
+        for (index, encoded_input) in encoded_inputs.iter().enumerate() {
+            let overflowing = encoded_input.get_overflowing();
+            let kept = overflowing.len().min(MAX_WINDOWS - 1);
+            windows.push(encoded_input);
+            windows.extend(&overflowing[..kept]);
+            owners.extend(iter::repeat(index).take(kept + 1));
+            if !overflowing.is_empty() {
+                let truncated_tokens = overflowing[kept..]
+                    .iter()
+                    .map(|window| {
+                        let special_tokens = window.get_special_tokens_mask().iter().sum::<u32>();
+                        window
+                            .len()
+                            .saturating_sub(special_tokens as usize + WINDOW_STRIDE)
+                    })
+                    .sum::<usize>();
+                self.overflowing.fetch_add(1, Ordering::Relaxed);
+                self.windows.fetch_add(kept + 1, Ordering::Relaxed);
+                self.truncated_tokens
+                    .fetch_add(truncated_tokens, Ordering::Relaxed);
+            }
+        }
+        self.inputs.fetch_add(sentences.len(), Ordering::Relaxed);
+
+        let batch_size = windows.len();
+        let length = windows.iter().map(|x| x.len()).max().unwrap_or(0);
+        let mut input_ids = vec![0i64; batch_size * length];
+        let mut attention_mask = vec![0u32; batch_size * length];
+        let mut token_type_ids = vec![0i64; batch_size * length];
+        for (row, encoded_input) in windows.iter().enumerate() {
+            let offset = row * length;
+            for (column, &id) in encoded_input.get_ids().iter().enumerate() {
+                input_ids[offset + column] = id as i64;
+            }
+            for (column, &mask) in encoded_input.get_attention_mask().iter().enumerate() {
+                attention_mask[offset + column] = mask;
+            }
+            for (column, &type_id) in encoded_input.get_type_ids().iter().enumerate() {
+                token_type_ids[offset + column] = type_id as i64;
+            }
+        }
+
+        let input_ids: Tensor =
+            tract_ndarray::Array2::from_shape_vec((batch_size, length), input_ids)?.into();
         let input_attention_mask: Tensor = tract_ndarray::Array2::from_shape_vec(
-            (1, length),
+            (batch_size, length),
             attention_mask.iter().map(|&x| x as i64).collect(),
         )?
         .into();
-        let input_token_type_ids: Tensor = tract_ndarray::Array2::from_shape_vec(
-            (1, length),
-            token_type_ids.iter().map(|&x| x as i64).collect(),
-        )?
-        .into();
+        let input_token_type_ids: Tensor =
+            tract_ndarray::Array2::from_shape_vec((batch_size, length), token_type_ids)?.into();
 
         let outputs = model.run(tvec!(
             input_ids.into(),
@@ -73,7 +191,13 @@ impl MiniLMEmbeddingModel {
             input_token_type_ids.into()
         ))?;
 
-        let sentence_embeddings = math::mean_pooling(outputs, attention_mask).unwrap();
+        let window_embeddings = math::mean_pooling(outputs, &attention_mask).unwrap();
+        let tokens = windows
+            .iter()
+            .map(|window| window.get_attention_mask().iter().sum::<u32>() as f32)
+            .collect::<Vec<f32>>();
+        let sentence_embeddings =
+            math::pool_windows(&window_embeddings, &owners, &tokens, sentences.len());
         let sentence_embeddings = math::normalize(&sentence_embeddings);
         Ok(sentence_embeddings)
     }
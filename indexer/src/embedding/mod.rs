@@ -1,9 +1,27 @@
+mod embedding_stats;
+mod error;
 mod minilm;
 mod model;
 mod model_id;
+mod model_spec;
 mod openai;
+mod openai_config;
+mod pooling_mode;
+mod provider;
+mod registry;
+mod transformer;
+mod transformer_config;
 
+pub use embedding_stats::EmbeddingStats;
+pub use error::EmbeddingError;
 pub use minilm::MiniLMEmbeddingModel;
 pub use model::EmbeddingModel;
 pub use model_id::ModelId;
-pub use openai::OpenAIAdaV2EmbeddingModel;
+pub use model_spec::ModelSpec;
+pub use openai::OpenAIEmbeddingModel;
+pub use openai_config::{OpenAIEmbeddingConfig, OpenAIProvider};
+pub use pooling_mode::PoolingMode;
+pub use provider::EmbeddingProvider;
+pub use registry::EmbeddingRegistry;
+pub use transformer::TransformerEmbeddingModel;
+pub use transformer_config::TransformerConfig;
@@ -1,64 +1,334 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
-use super::{MiniLMEmbeddingModel, ModelId};
-use crate::Result;
+use async_trait::async_trait;
+use rayon::prelude::*;
+
+use super::{
+    EmbeddingError,
+    EmbeddingProvider,
+    EmbeddingStats,
+    MiniLMEmbeddingModel,
+    ModelId,
+    OpenAIEmbeddingModel,
+    TransformerEmbeddingModel,
+};
+use crate::{graph::Graph, Result};
+
+/// Inputs sent to a local model in one inference call.
+const LOCAL_BATCH_SIZE: usize = 16;
+/// Inputs sent to a remote model in one request.
+const REMOTE_BATCH_SIZE: usize = 256;
 
 #[derive(Debug)]
 pub enum EmbeddingModel {
     MiniLMEmbeddingModel(MiniLMEmbeddingModel),
-    // OpenAIAdaV2EmbeddingModel(OpenAIAdaV2EmbeddingModel),
+    OpenAIEmbeddingModel(OpenAIEmbeddingModel),
+    TransformerEmbeddingModel(TransformerEmbeddingModel),
+    Custom(Arc<dyn EmbeddingProvider>),
 }
 
 impl EmbeddingModel {
-    pub fn run(&self, data: &str) -> Result<Vec<f32>> {
+    pub async fn run(&self, data: &str) -> Result<Vec<f32>> {
         let result = match self {
             EmbeddingModel::MiniLMEmbeddingModel(model) => model.run(data)?,
-            // EmbeddingModel::OpenAIAdaV2EmbeddingModel(model) => model.run(data.to_owned()).await?,
+            _ => self
+                .run_batch(&[data])
+                .await?
+                .pop()
+                .ok_or(anyhow::anyhow!("no embedding returned"))?,
         };
 
         Ok(result)
     }
 
-    pub fn get_model_id(&self) -> ModelId {
+    /// Embeds a search query, with the query instruction of models that have
+    /// one.
+    pub async fn run_query(&self, query: &str) -> Result<Vec<f32>> {
+        match self {
+            EmbeddingModel::TransformerEmbeddingModel(model) => model.run_query(query),
+            EmbeddingModel::Custom(provider) => provider.embed_query(query).await,
+            _ => self.run(query).await,
+        }
+    }
+
+    pub async fn run_batch(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
         let result = match self {
-            EmbeddingModel::MiniLMEmbeddingModel(model) => model.get_model_id(),
-            // EmbeddingModel::OpenAIAdaV2EmbeddingModel(model) => model.get_model_id(),
+            EmbeddingModel::MiniLMEmbeddingModel(model) => model.run_batch(data)?,
+            EmbeddingModel::OpenAIEmbeddingModel(model) => model.run_batch(data).await?,
+            EmbeddingModel::TransformerEmbeddingModel(model) => model.run_batch(data)?,
+            EmbeddingModel::Custom(provider) => provider.embed(data).await?,
         };
 
-        result
+        Ok(result)
+    }
+
+    /// Embeds any number of inputs, split into batches sized for the model.
+    /// Local batches run in parallel. Embeddings are returned in input order.
+    pub async fn run_batches(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let result = match self {
+            EmbeddingModel::MiniLMEmbeddingModel(model) => {
+                run_in_batches(data, LOCAL_BATCH_SIZE, |batch| model.run_batch(batch))?
+            }
+            EmbeddingModel::TransformerEmbeddingModel(model) => {
+                run_in_batches(data, LOCAL_BATCH_SIZE, |batch| model.run_batch(batch))?
+            }
+            EmbeddingModel::OpenAIEmbeddingModel(model) => {
+                let mut embeddings = vec![];
+                for batch in data.chunks(REMOTE_BATCH_SIZE) {
+                    embeddings.extend(model.run_batch(batch).await?);
+                }
+                embeddings
+            }
+            // Custom providers batch on their own.
+            EmbeddingModel::Custom(provider) => provider.embed(data).await?,
+        };
+
+        Ok(result)
+    }
+
+    pub fn model_id(&self) -> String {
+        match self {
+            EmbeddingModel::MiniLMEmbeddingModel(model) => model.get_model_id().to_string(),
+            EmbeddingModel::OpenAIEmbeddingModel(model) => model.get_model_id().to_string(),
+            EmbeddingModel::TransformerEmbeddingModel(model) => model.get_model_id().to_string(),
+            EmbeddingModel::Custom(provider) => provider.model_id(),
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        match self {
+            EmbeddingModel::MiniLMEmbeddingModel(model) => EmbeddingProvider::dimension(model),
+            EmbeddingModel::OpenAIEmbeddingModel(model) => EmbeddingProvider::dimension(model),
+            EmbeddingModel::TransformerEmbeddingModel(model) => EmbeddingProvider::dimension(model),
+            EmbeddingModel::Custom(provider) => provider.dimension(),
+        }
+    }
+
+    /// Overflowing input counts of models that embed long inputs in windows.
+    pub fn stats(&self) -> Option<EmbeddingStats> {
+        match self {
+            EmbeddingModel::MiniLMEmbeddingModel(model) => Some(model.stats()),
+            _ => None,
+        }
+    }
+
+    /// Checks that query embeddings of this model can be compared with the
+    /// embeddings of `graph`. Graphs without index model, indexed by older
+    /// versions, only need the same dimension.
+    pub fn check_compatible(&self, graph: &Graph) -> std::result::Result<(), EmbeddingError> {
+        let model_id = self.model_id();
+        match graph.index_model() {
+            Some(index_model) if index_model != &model_id => {
+                Err(EmbeddingError::IncompatibleModel {
+                    graph: graph.id().to_string(),
+                    index_model: index_model.to_string(),
+                    query_model: model_id,
+                })
+            }
+            Some(_) => Ok(()),
+            None => match graph.dimension() {
+                Some(dimension) if dimension != self.dimension() => {
+                    Err(EmbeddingError::IncompatibleDimension {
+                        graph: graph.id().to_string(),
+                        expected: self.dimension(),
+                        actual: dimension,
+                    })
+                }
+                _ => Ok(()),
+            },
+        }
     }
 
     pub fn load_model(model_id: &str, model_file: Option<&str>) -> Result<EmbeddingModel> {
         let model_id = ModelId::from_str(&model_id)?;
+        let local_file =
+            || model_file.ok_or_else(|| EmbeddingError::MissingModelFile(model_id.to_string()));
 
         let result = match model_id {
             ModelId::AllMiniLML12V2 => {
                 let model =
-                    MiniLMEmbeddingModel::from_file(ModelId::AllMiniLML12V2, &model_file.unwrap())?;
+                    MiniLMEmbeddingModel::from_file(ModelId::AllMiniLML12V2, local_file()?)?;
                 EmbeddingModel::MiniLMEmbeddingModel(model)
             }
             ModelId::AllMiniLML6V2 => {
-                let model =
-                    MiniLMEmbeddingModel::from_file(ModelId::AllMiniLML6V2, &model_file.unwrap())?;
+                let model = MiniLMEmbeddingModel::from_file(ModelId::AllMiniLML6V2, local_file()?)?;
                 EmbeddingModel::MiniLMEmbeddingModel(model)
-            } // ModelId::OpenAITextEmbeddingAdaV2 => {
-              //     let model = OpenAIAdaV2EmbeddingModel::new(ModelId::OpenAITextEmbeddingAdaV2)?;
-              //     EmbeddingModel::OpenAIAdaV2EmbeddingModel(model)
-              // }
+            }
+            ModelId::Onnx(_) => {
+                let model_file = local_file()?;
+                let model = TransformerEmbeddingModel::from_file(model_id, model_file)?;
+                EmbeddingModel::TransformerEmbeddingModel(model)
+            }
+            ModelId::OpenAITextEmbeddingAdaV2
+            | ModelId::OpenAITextEmbedding3Small
+            | ModelId::OpenAITextEmbedding3Large
+            | ModelId::OpenAICompatible(_) => {
+                let model = OpenAIEmbeddingModel::new(model_id)?;
+                EmbeddingModel::OpenAIEmbeddingModel(model)
+            }
         };
 
         Ok(result)
     }
 }
 
-// impl From<OpenAIAdaV2EmbeddingModel> for EmbeddingModel {
-//     fn from(model: OpenAIAdaV2EmbeddingModel) -> Self {
-//         EmbeddingModel::OpenAIAdaV2EmbeddingModel(model)
-//     }
-// }
+#[async_trait]
+impl EmbeddingProvider for EmbeddingModel {
+    async fn embed(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.run_batches(data).await
+    }
+
+    fn dimension(&self) -> usize {
+        EmbeddingModel::dimension(self)
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        self.run_query(query).await
+    }
+
+    fn model_id(&self) -> String {
+        EmbeddingModel::model_id(self)
+    }
+}
+
+// Runs `run` over batches of `batch_size` inputs in parallel and flattens the
+// results back into input order.
+fn run_in_batches<F>(data: &[&str], batch_size: usize, run: F) -> Result<Vec<Vec<f32>>>
+where
+    F: Fn(&[&str]) -> Result<Vec<Vec<f32>>> + Send + Sync,
+{
+    let batches = data
+        .par_chunks(batch_size.max(1))
+        .map(run)
+        .collect::<Result<Vec<Vec<Vec<f32>>>>>()?;
+
+    Ok(batches.into_iter().flatten().collect())
+}
+
+impl From<OpenAIEmbeddingModel> for EmbeddingModel {
+    fn from(model: OpenAIEmbeddingModel) -> Self {
+        EmbeddingModel::OpenAIEmbeddingModel(model)
+    }
+}
 
 impl From<MiniLMEmbeddingModel> for EmbeddingModel {
     fn from(model: MiniLMEmbeddingModel) -> Self {
         EmbeddingModel::MiniLMEmbeddingModel(model)
     }
 }
+
+impl From<TransformerEmbeddingModel> for EmbeddingModel {
+    fn from(model: TransformerEmbeddingModel) -> Self {
+        EmbeddingModel::TransformerEmbeddingModel(model)
+    }
+}
+
+impl From<Arc<dyn EmbeddingProvider>> for EmbeddingModel {
+    fn from(provider: Arc<dyn EmbeddingProvider>) -> Self {
+        EmbeddingModel::Custom(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::HashProvider;
+
+    // Embeds every input as its length and the size of its batch.
+    fn embed_lengths(batch: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(batch
+            .iter()
+            .map(|text| vec![text.len() as f32, batch.len() as f32])
+            .collect())
+    }
+
+    #[test]
+    fn run_in_batches_keeps_input_order() {
+        let texts = (0..50).map(|index| "x".repeat(index)).collect::<Vec<_>>();
+        let data = texts.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let embeddings = run_in_batches(&data, 16, embed_lengths).unwrap();
+        assert_eq!(embeddings.len(), 50);
+        for (index, embedding) in embeddings.iter().enumerate() {
+            assert_eq!(embedding[0], index as f32);
+            assert!(embedding[1] <= 16.0);
+        }
+    }
+
+    #[test]
+    fn run_in_batches_of_empty_input() {
+        let embeddings = run_in_batches(&[], 16, |_| panic!("no batch to run")).unwrap();
+        assert!(embeddings.is_empty());
+    }
+
+    #[test]
+    fn run_in_batches_fails_with_a_batch() {
+        let result = run_in_batches(&["a", "b", "c"], 1, |batch| match batch {
+            ["b"] => Err(anyhow::anyhow!("inference failed")),
+            _ => embed_lengths(batch),
+        });
+        assert!(result.is_err());
+    }
+    #[tokio::test]
+    async fn custom_provider_embeds_all_inputs() {
+        let provider: Arc<dyn EmbeddingProvider> = Arc::new(HashProvider::new("fake::a", 4));
+        let model = EmbeddingModel::from(provider);
+        assert_eq!(model.model_id(), "fake::a");
+        assert_eq!(model.dimension(), 4);
+
+        let embeddings = model.run_batches(&["a", "b", "a"]).await.unwrap();
+        assert_eq!(embeddings.len(), 3);
+        assert_eq!(embeddings[0], embeddings[2]);
+        assert_eq!(model.run("b").await.unwrap(), embeddings[1]);
+    }
+
+    #[test]
+    fn local_models_need_a_model_file() {
+        for model_id in ["onnx::multilingual-e5-small", "MiniLM::all-MiniLM-L6-v2"] {
+            let model_id = ModelId::from_str(model_id).unwrap().to_string();
+            let err = EmbeddingModel::load_model(&model_id, None).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref(),
+                Some(EmbeddingError::MissingModelFile(id)) if *id == model_id
+            ));
+        }
+    }
+
+    fn graph(index_model: Option<&str>, dimension: usize) -> Graph {
+        let node = crate::graph::Node::builder()
+            .id("node".to_string())
+            .data(String::new())
+            .rank_id(String::new())
+            .hash(String::new())
+            .embeddings(vec![0.5; dimension])
+            .build();
+        Graph::builder()
+            .id("graph".to_string())
+            .title("graph".to_string())
+            .node_map([(node.id().to_string(), node)].into())
+            .index_model(index_model.map(str::to_string))
+            .build()
+    }
+
+    #[test]
+    fn check_compatible_compares_index_model_then_dimension() {
+        let provider: Arc<dyn EmbeddingProvider> = Arc::new(HashProvider::new("fake::a", 4));
+        let model = EmbeddingModel::from(provider);
+
+        assert!(model.check_compatible(&graph(Some("fake::a"), 4)).is_ok());
+        assert!(matches!(
+            model.check_compatible(&graph(Some("fake::b"), 4)),
+            Err(EmbeddingError::IncompatibleModel { index_model, .. }) if index_model == "fake::b"
+        ));
+        // Graphs of older versions have no index model.
+        assert!(model.check_compatible(&graph(None, 4)).is_ok());
+        assert!(matches!(
+            model.check_compatible(&graph(None, 8)),
+            Err(EmbeddingError::IncompatibleDimension {
+                expected: 4,
+                actual: 8,
+                ..
+            })
+        ));
+    }
+}
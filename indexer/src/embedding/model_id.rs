@@ -3,12 +3,78 @@ use std::str::FromStr;
 
 use serde::{Deserialize, Deserializer, Serialize};
 
+use super::{EmbeddingError, ModelSpec, PoolingMode};
+
+const OPENAI_PREFIX: &str = "openai::";
+const ONNX_PREFIX: &str = "onnx::";
+
 #[derive(Debug, Clone)]
 pub enum ModelId {
     AllMiniLML12V2,
     AllMiniLML6V2,
-    // OpenAITextEmbeddingAdaV2,
+    OpenAITextEmbeddingAdaV2,
+    OpenAITextEmbedding3Small,
+    OpenAITextEmbedding3Large,
+    /// Any other model served through an OpenAI-compatible embeddings API,
+    /// e.g. a llama.cpp or vLLM server. Holds the model name sent to the server.
+    OpenAICompatible(String),
+    /// Local ONNX sentence-transformer described by its `embedding.json`,
+    /// e.g. e5, bge or gte. Holds the name of its folder under `models`.
+    Onnx(String),
+}
+
+impl ModelId {
+    pub fn is_openai(&self) -> bool {
+        !matches!(
+            self,
+            ModelId::AllMiniLML12V2 | ModelId::AllMiniLML6V2 | ModelId::Onnx(_)
+        )
+    }
+
+    /// Models with a known spec, i.e. every model but `OpenAICompatible` and
+    /// `Onnx`.
+    pub fn catalog() -> Vec<ModelId> {
+        vec![
+            ModelId::AllMiniLML12V2,
+            ModelId::AllMiniLML6V2,
+            ModelId::OpenAITextEmbeddingAdaV2,
+            ModelId::OpenAITextEmbedding3Small,
+            ModelId::OpenAITextEmbedding3Large,
+        ]
+    }
+
+    pub fn spec(&self) -> ModelSpec {
+        let (dimension, max_sequence_length, pooling) = match self {
+            ModelId::AllMiniLML12V2 => (Some(384), Some(128), PoolingMode::Mean),
+            ModelId::AllMiniLML6V2 => (Some(384), Some(256), PoolingMode::Mean),
+            ModelId::OpenAITextEmbeddingAdaV2 | ModelId::OpenAITextEmbedding3Small => {
+                (Some(1536), Some(8191), PoolingMode::Remote)
+            }
+            ModelId::OpenAITextEmbedding3Large => (Some(3072), Some(8191), PoolingMode::Remote),
+            ModelId::OpenAICompatible(_) => (None, None, PoolingMode::Remote),
+            // Described by `embedding.json`, these are its defaults.
+            ModelId::Onnx(_) => (None, Some(512), PoolingMode::Mean),
+        };
+        ModelSpec::builder()
+            .dimension(dimension)
+            .max_sequence_length(max_sequence_length)
+            .pooling(pooling)
+            .normalized(!matches!(self, ModelId::OpenAICompatible(_)))
+            .build()
+    }
+
+    /// Embedding size of the model, when it is fixed by the model itself.
+    pub fn dimension(&self) -> Option<usize> {
+        *self.spec().dimension()
+    }
+
+    /// The model name without its provider prefix.
+    pub fn model_name(&self) -> String {
+        let model_id = self.to_string();
+        model_id.split("::").last().unwrap_or("").to_string()
+    }
 }
+
 impl FromStr for ModelId {
     type Err = anyhow::Error;
 
@@ -16,8 +82,14 @@ impl FromStr for ModelId {
         let result = match s {
             "MiniLM::all-MiniLM-L12-v2" => ModelId::AllMiniLML12V2,
             "MiniLM::all-MiniLM-L6-v2" => ModelId::AllMiniLML6V2,
-            // "openai::text-embedding-ada-002" => ModelId::OpenAITextEmbeddingAdaV2,
-            _ => todo!(),
+            "openai::text-embedding-ada-002" => ModelId::OpenAITextEmbeddingAdaV2,
+            "openai::text-embedding-3-small" => ModelId::OpenAITextEmbedding3Small,
+            "openai::text-embedding-3-large" => ModelId::OpenAITextEmbedding3Large,
+            s if s.starts_with(OPENAI_PREFIX) => {
+                ModelId::OpenAICompatible(s[OPENAI_PREFIX.len()..].to_string())
+            }
+            s if s.starts_with(ONNX_PREFIX) => ModelId::Onnx(s[ONNX_PREFIX.len()..].to_string()),
+            _ => return Err(EmbeddingError::UnknownModel(s.to_string()).into()),
         };
 
         Ok(result)
@@ -28,7 +100,11 @@ impl fmt::Display for ModelId {
         match self {
             ModelId::AllMiniLML12V2 => write!(f, "MiniLM::all-MiniLM-L12-v2"),
             ModelId::AllMiniLML6V2 => write!(f, "MiniLM::all-MiniLM-L6-v2"),
-            // ModelId::OpenAITextEmbeddingAdaV2 => write!(f, "openai::text-embedding-ada-002"),
+            ModelId::OpenAITextEmbeddingAdaV2 => write!(f, "openai::text-embedding-ada-002"),
+            ModelId::OpenAITextEmbedding3Small => write!(f, "openai::text-embedding-3-small"),
+            ModelId::OpenAITextEmbedding3Large => write!(f, "openai::text-embedding-3-large"),
+            ModelId::OpenAICompatible(model) => write!(f, "{}{}", OPENAI_PREFIX, model),
+            ModelId::Onnx(model) => write!(f, "{}{}", ONNX_PREFIX, model),
         }
     }
 }
@@ -48,6 +124,64 @@ impl<'de> Deserialize<'de> for ModelId {
         D: Deserializer<'de>,
     {
         let s = String::deserialize(deserializer)?;
-        Ok(Self::from_str(&s).unwrap())
+        Self::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn openai_model_ids_round_trip() {
+        for model_id in [
+            "openai::text-embedding-ada-002",
+            "openai::text-embedding-3-small",
+            "openai::text-embedding-3-large",
+            "openai::nomic-embed-text",
+        ] {
+            let parsed = ModelId::from_str(model_id).unwrap();
+            assert!(parsed.is_openai());
+            assert_eq!(parsed.to_string(), model_id);
+        }
+    }
+
+    #[test]
+    fn openai_compatible_model_name() {
+        let model_id = ModelId::from_str("openai::nomic-embed-text").unwrap();
+        assert!(
+            matches!(&model_id, ModelId::OpenAICompatible(model) if model == "nomic-embed-text")
+        );
+        assert_eq!(model_id.model_name(), "nomic-embed-text");
+        assert_eq!(ModelId::AllMiniLML6V2.model_name(), "all-MiniLM-L6-v2");
+        assert!(!ModelId::AllMiniLML6V2.is_openai());
+    }
+    #[test]
+    fn onnx_model_ids_round_trip() {
+        let model_id = ModelId::from_str("onnx::multilingual-e5-small").unwrap();
+        assert!(matches!(&model_id, ModelId::Onnx(model) if model == "multilingual-e5-small"));
+        assert_eq!(model_id.to_string(), "onnx::multilingual-e5-small");
+        assert!(!model_id.is_openai());
+        assert!(model_id.dimension().is_none());
+    }
+
+    #[test]
+    fn unknown_model_id() {
+        assert!(ModelId::from_str("MiniLM::all-MiniLM-L24-v2").is_err());
+    }
+    #[test]
+    fn catalog_models_round_trip_with_their_spec() {
+        for model_id in ModelId::catalog() {
+            let parsed = ModelId::from_str(&model_id.to_string()).unwrap();
+            assert_eq!(parsed.to_string(), model_id.to_string());
+            assert!(parsed.dimension().is_some());
+            assert!(*parsed.spec().normalized());
+        }
+        let model_id = ModelId::OpenAICompatible("nomic-embed-text".to_string());
+        assert!(model_id.dimension().is_none());
+        assert_eq!(
+            *ModelId::AllMiniLML6V2.spec().max_sequence_length(),
+            Some(256)
+        );
     }
 }
@@ -0,0 +1,17 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::PoolingMode;
+
+/// Catalog entry of an embedding model.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct ModelSpec {
+    /// Embedding size, `None` when it depends on the served model.
+    dimension: Option<usize>,
+    /// Longest input in tokens, `None` when unknown.
+    max_sequence_length: Option<usize>,
+    pooling: PoolingMode,
+    /// Whether embeddings are scaled to unit length.
+    normalized: bool,
+}
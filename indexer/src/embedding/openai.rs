@@ -1,27 +1,83 @@
-use std::error::Error;
+use async_openai::{
+    config::{AzureConfig, OpenAIConfig},
+    types::{CreateEmbeddingRequestArgs, CreateEmbeddingResponse},
+    Client,
+};
+use async_trait::async_trait;
 
-use async_openai::{config::OpenAIConfig, types::CreateEmbeddingRequestArgs, Client};
+use super::{EmbeddingProvider, ModelId, OpenAIEmbeddingConfig, OpenAIProvider};
+use crate::Result;
 
-use super::ModelId;
+#[derive(Debug)]
+enum OpenAIClient {
+    OpenAI(Client<OpenAIConfig>),
+    Azure(Client<AzureConfig>),
+}
 
 #[derive(Debug)]
-pub struct OpenAIAdaV2EmbeddingModel {
-    client: Client<OpenAIConfig>,
+pub struct OpenAIEmbeddingModel {
+    client: OpenAIClient,
     model: String,
     model_id: ModelId,
+    dimensions: Option<u32>,
+    dimension: usize,
 }
 
-impl OpenAIAdaV2EmbeddingModel {
-    pub fn new(model_id: ModelId) -> Result<Self, Box<dyn Error>> {
-        let model = model_id.to_string();
-        let model = model.split("::").last().unwrap();
-        let org_id = std::env::var("OPENAI_ORG_ID")?;
-        let client = Client::with_config(OpenAIConfig::new().with_org_id(org_id));
+impl OpenAIEmbeddingModel {
+    pub fn new(model_id: ModelId) -> Result<Self> {
+        Self::with_config(model_id, OpenAIEmbeddingConfig::from_env())
+    }
+
+    pub fn with_config(model_id: ModelId, config: OpenAIEmbeddingConfig) -> Result<Self> {
+        if !model_id.is_openai() {
+            return Err(anyhow::anyhow!("{} is not an OpenAI model", model_id));
+        }
+        let model = model_id.model_name();
+        let dimension = config
+            .dimensions()
+            .map(|dimensions| dimensions as usize)
+            .or(model_id.dimension())
+            .ok_or(anyhow::anyhow!(
+                "dimension of {} is unknown, set it in the config",
+                model_id
+            ))?;
+
+        let client = match config.provider() {
+            OpenAIProvider::OpenAI => {
+                let mut openai_config = OpenAIConfig::new();
+                if let Some(api_base) = config.api_base() {
+                    openai_config = openai_config.with_api_base(api_base);
+                }
+                if let Some(api_key) = config.api_key() {
+                    openai_config = openai_config.with_api_key(api_key);
+                }
+                if let Some(org_id) = config.org_id() {
+                    openai_config = openai_config.with_org_id(org_id);
+                }
+                OpenAIClient::OpenAI(Client::with_config(openai_config))
+            }
+            OpenAIProvider::Azure { api_version } => {
+                let api_base = config
+                    .api_base()
+                    .as_ref()
+                    .ok_or(anyhow::anyhow!("Azure OpenAI requires an api base"))?;
+                let mut azure_config = AzureConfig::new()
+                    .with_api_version(api_version)
+                    .with_deployment_id(&model)
+                    .with_api_base(api_base);
+                if let Some(api_key) = config.api_key() {
+                    azure_config = azure_config.with_api_key(api_key);
+                }
+                OpenAIClient::Azure(Client::with_config(azure_config))
+            }
+        };
 
         Ok(Self {
             client,
             model_id,
-            model: model.to_string(),
+            model,
+            dimensions: config.dimensions().to_owned(),
+            dimension,
         })
     }
 
@@ -29,22 +85,106 @@ impl OpenAIAdaV2EmbeddingModel {
         self.model_id.clone()
     }
 
-    pub async fn run(&self, data: Vec<&str>) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
-        let text = data.join("\n");
-        let tokens = text.split_whitespace();
-        println!("Token used: {}", tokens.count());
-
-        let client = self.client.to_owned();
-        let request = CreateEmbeddingRequestArgs::default()
-            .model(self.model.to_owned())
-            .input(data)
-            .build()?;
+    pub async fn run_batch(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if data.is_empty() {
+            return Ok(vec![]);
+        }
 
-        let mut embeddings = vec![];
-        let response = client.embeddings().create(request).await?;
-        for embedding in response.data {
-            embeddings.push(embedding.embedding.to_vec())
+        let mut request = CreateEmbeddingRequestArgs::default();
+        request.model(self.model.to_owned()).input(data.to_vec());
+        if let Some(dimensions) = self.dimensions {
+            request.dimensions(dimensions);
         }
+        let request = request.build()?;
+
+        let response: CreateEmbeddingResponse = match &self.client {
+            OpenAIClient::OpenAI(client) => client.embeddings().create(request).await?,
+            OpenAIClient::Azure(client) => client.embeddings().create(request).await?,
+        };
+
+        let mut data = response.data;
+        data.sort_by_key(|embedding| embedding.index);
+        let embeddings = data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect();
         Ok(embeddings)
     }
 }
+
+#[async_trait]
+impl EmbeddingProvider for OpenAIEmbeddingModel {
+    async fn embed(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.run_batch(data).await
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_id(&self) -> String {
+        self.model_id.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_config_rejects_local_models() {
+        let config = OpenAIEmbeddingConfig::default();
+        assert!(OpenAIEmbeddingModel::with_config(ModelId::AllMiniLML6V2, config).is_err());
+    }
+
+    #[test]
+    fn azure_requires_an_api_base() {
+        let provider: OpenAIProvider =
+            serde_json::from_str(r#"{"kind": "azure", "api_version": "2024-02-01"}"#).unwrap();
+        let config = OpenAIEmbeddingConfig::builder().provider(provider).build();
+        let result = OpenAIEmbeddingModel::with_config(ModelId::OpenAITextEmbedding3Small, config);
+        assert!(result.is_err());
+
+        let config = OpenAIEmbeddingConfig::builder()
+            .provider(OpenAIProvider::Azure {
+                api_version: "2024-02-01".to_string(),
+            })
+            .api_base(Some("https://example.openai.azure.com".to_string()))
+            .build();
+        let model =
+            OpenAIEmbeddingModel::with_config(ModelId::OpenAITextEmbedding3Small, config).unwrap();
+        assert_eq!(model.model, "text-embedding-3-small");
+    }
+
+    #[tokio::test]
+    async fn run_batch_of_empty_input() {
+        let config = OpenAIEmbeddingConfig::builder()
+            .api_base(Some("http://localhost:0/v1".to_string()))
+            .api_key(Some("key".to_string()))
+            .dimensions(Some(768))
+            .build();
+        let model = OpenAIEmbeddingModel::with_config(
+            ModelId::OpenAICompatible("nomic-embed-text".to_string()),
+            config,
+        )
+        .unwrap();
+        assert!(model.run_batch(&[]).await.unwrap().is_empty());
+    }
+    #[test]
+    fn dimension_of_openai_compatible_models_is_configured() {
+        let model_id = ModelId::OpenAICompatible("nomic-embed-text".to_string());
+        let config = OpenAIEmbeddingConfig::default();
+        assert!(OpenAIEmbeddingModel::with_config(model_id.clone(), config).is_err());
+
+        let config = OpenAIEmbeddingConfig::builder()
+            .dimensions(Some(768))
+            .build();
+        let model = OpenAIEmbeddingModel::with_config(model_id, config).unwrap();
+        assert_eq!(EmbeddingProvider::dimension(&model), 768);
+
+        let config = OpenAIEmbeddingConfig::default();
+        let model =
+            OpenAIEmbeddingModel::with_config(ModelId::OpenAITextEmbedding3Large, config).unwrap();
+        assert_eq!(EmbeddingProvider::dimension(&model), 3072);
+    }
+}
@@ -0,0 +1,40 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum OpenAIProvider {
+    /// OpenAI, or any server implementing the OpenAI embeddings API.
+    #[default]
+    OpenAI,
+    /// Azure OpenAI. The model name is used as the deployment id.
+    Azure { api_version: String },
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct OpenAIEmbeddingConfig {
+    #[builder(default)]
+    provider: OpenAIProvider,
+    /// Overrides the provider's endpoint, e.g. `http://localhost:8080/v1`.
+    #[builder(default = None)]
+    api_base: Option<String>,
+    /// Falls back to the `OPENAI_API_KEY` environment variable.
+    #[builder(default = None)]
+    api_key: Option<String>,
+    #[builder(default = None)]
+    org_id: Option<String>,
+    /// Output dimension, sent with every request. Required for models whose
+    /// dimension is not known in advance.
+    #[builder(default = None)]
+    dimensions: Option<u32>,
+}
+
+impl OpenAIEmbeddingConfig {
+    pub fn from_env() -> Self {
+        Self::builder()
+            .api_base(common::vars::get_openai_api_base().ok())
+            .org_id(common::vars::get_openai_org_id().ok())
+            .build()
+    }
+}
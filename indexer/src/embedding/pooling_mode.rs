@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// How token embeddings are combined into a sentence embedding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolingMode {
+    /// Mean of the token embeddings, weighted by the attention mask.
+    Mean,
+    /// Embedding of the first token.
+    Cls,
+    /// Embedding of the last token that is not padding, for decoder models.
+    LastToken,
+    /// Pooled by the remote API before the embedding is returned.
+    Remote,
+}
@@ -0,0 +1,30 @@
+use core::fmt;
+
+use async_trait::async_trait;
+
+use crate::Result;
+
+/// An embedding model that can be registered in an `EmbeddingRegistry`.
+///
+/// Implement this to plug a model into indexing and search without adding a
+/// variant to `EmbeddingModel`; wrap it with `EmbeddingModel::Custom`.
+#[async_trait]
+pub trait EmbeddingProvider: fmt::Debug + Send + Sync {
+    /// Embeds every input, returning one vector per input in input order.
+    async fn embed(&self, data: &[&str]) -> Result<Vec<Vec<f32>>>;
+
+    /// Embeds a search query. Models embedding queries and passages with
+    /// different instructions override this.
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        self.embed(&[query])
+            .await?
+            .pop()
+            .ok_or(anyhow::anyhow!("no embedding returned"))
+    }
+
+    /// Length of the vectors returned by `embed`.
+    fn dimension(&self) -> usize;
+
+    /// Registry key of the model, stored in `Graph.index_model`.
+    fn model_id(&self) -> String;
+}
@@ -0,0 +1,143 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{EmbeddingError, EmbeddingProvider};
+use crate::graph::Graph;
+
+#[derive(Debug, Default, Clone)]
+pub struct EmbeddingRegistry {
+    providers: HashMap<String, Arc<dyn EmbeddingProvider>>,
+}
+
+impl EmbeddingRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `provider` under its model id. Returns the provider it
+    /// replaced, if any.
+    pub fn register(
+        &mut self,
+        provider: Arc<dyn EmbeddingProvider>,
+    ) -> Option<Arc<dyn EmbeddingProvider>> {
+        self.providers.insert(provider.model_id(), provider)
+    }
+
+    pub fn contains(&self, model_id: &str) -> bool {
+        self.providers.contains_key(model_id)
+    }
+
+    pub fn model_ids(&self) -> Vec<String> {
+        self.providers.keys().cloned().collect()
+    }
+
+    pub fn get(&self, model_id: &str) -> Result<Arc<dyn EmbeddingProvider>, EmbeddingError> {
+        self.providers
+            .get(model_id)
+            .cloned()
+            .ok_or(EmbeddingError::UnregisteredModel(model_id.to_string()))
+    }
+
+    /// Returns the provider of the model `graph` was indexed with.
+    pub fn resolve(&self, graph: &Graph) -> Result<Arc<dyn EmbeddingProvider>, EmbeddingError> {
+        let model_id = graph
+            .index_model()
+            .as_ref()
+            .ok_or(EmbeddingError::MissingIndexModel(graph.id().to_string()))?;
+        self.get(model_id)
+    }
+
+    /// Returns the provider to embed queries searching `graphs`, the model
+    /// of the first graph whose index model is registered. Graphs indexed
+    /// without index model by older versions use the only registered model.
+    pub fn resolve_query(
+        &self,
+        graphs: &[Graph],
+    ) -> Result<Arc<dyn EmbeddingProvider>, EmbeddingError> {
+        let mut error = None;
+        for graph in graphs.iter().filter(|graph| graph.index_model().is_some()) {
+            match self.resolve(graph) {
+                Ok(provider) => return Ok(provider),
+                Err(err) => error = error.or(Some(err)),
+            }
+        }
+        if let Some(err) = error {
+            return Err(err);
+        }
+        match self.providers.values().collect::<Vec<_>>().as_slice() {
+            [provider] => Ok(Arc::clone(provider)),
+            _ => Err(EmbeddingError::MissingIndexModel(
+                graphs.first().map_or("", |graph| graph.id()).to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::HashProvider;
+
+    fn graph(index_model: Option<&str>) -> Graph {
+        Graph::builder()
+            .id("graph".to_string())
+            .title("graph".to_string())
+            .node_map(HashMap::new())
+            .index_model(index_model.map(str::to_string))
+            .build()
+    }
+
+    #[test]
+    fn register_replaces_a_provider_of_the_same_model() {
+        let mut registry = EmbeddingRegistry::new();
+        assert!(registry
+            .register(Arc::new(HashProvider::new("fake::a", 4)))
+            .is_none());
+        let replaced = registry.register(Arc::new(HashProvider::new("fake::a", 8)));
+        assert_eq!(replaced.unwrap().dimension(), 4);
+        assert_eq!(registry.model_ids(), vec!["fake::a".to_string()]);
+        assert_eq!(registry.get("fake::a").unwrap().dimension(), 8);
+    }
+
+    #[test]
+    fn resolve_the_index_model_of_a_graph() {
+        let mut registry = EmbeddingRegistry::new();
+        registry.register(Arc::new(HashProvider::new("fake::a", 4)));
+
+        let provider = registry.resolve(&graph(Some("fake::a"))).unwrap();
+        assert_eq!(provider.model_id(), "fake::a");
+        assert!(matches!(
+            registry.resolve(&graph(Some("fake::b"))),
+            Err(EmbeddingError::UnregisteredModel(model_id)) if model_id == "fake::b"
+        ));
+        assert!(matches!(
+            registry.resolve(&graph(None)),
+            Err(EmbeddingError::MissingIndexModel(_))
+        ));
+    }
+
+    #[test]
+    fn resolve_query_uses_the_first_registered_index_model() {
+        let mut registry = EmbeddingRegistry::new();
+        registry.register(Arc::new(HashProvider::new("fake::a", 4)));
+        let graphs = [graph(None), graph(Some("fake::b")), graph(Some("fake::a"))];
+        assert_eq!(
+            registry.resolve_query(&graphs).unwrap().model_id(),
+            "fake::a"
+        );
+
+        assert!(matches!(
+            registry.resolve_query(&[graph(Some("fake::b"))]),
+            Err(EmbeddingError::UnregisteredModel(model_id)) if model_id == "fake::b"
+        ));
+        // Graphs of older versions use the only registered model.
+        assert_eq!(
+            registry.resolve_query(&[graph(None)]).unwrap().model_id(),
+            "fake::a"
+        );
+        registry.register(Arc::new(HashProvider::new("fake::b", 4)));
+        assert!(matches!(
+            registry.resolve_query(&[graph(None)]),
+            Err(EmbeddingError::MissingIndexModel(_))
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use ndarray::{ArrayBase, IxDynImpl, OwnedRepr};
+use tokenizers::{Tokenizer, TruncationParams};
+use tract_onnx::prelude::*;
+
+use super::{EmbeddingProvider, ModelId, PoolingMode, TransformerConfig};
+use crate::{math, Result};
+
+const CONFIG_FILENAME: &str = "embedding.json";
+// Text embedded once at load time to read the embedding size from the model.
+const PROBE_TEXT: &str = "dimension";
+
+/// Any ONNX sentence-transformer, e.g. e5, bge or gte, described by the
+/// `embedding.json` file of its model folder. See `TransformerConfig`.
+#[derive(Debug)]
+pub struct TransformerEmbeddingModel {
+    tokenizer: Tokenizer,
+    model_id: ModelId,
+    config: TransformerConfig,
+    dimension: usize,
+    // Names of the model inputs, in the order they are fed.
+    input_names: Vec<String>,
+    model: SimplePlan<TypedFact, Box<dyn TypedOp>, Graph<TypedFact, Box<dyn TypedOp>>>,
+}
+
+impl TransformerEmbeddingModel {
+    /// Loads `model.onnx`, `tokenizer.json` and `embedding.json` from
+    /// `model_path`. A missing `embedding.json` describes a BERT-like model
+    /// with mean pooling.
+    pub fn from_file(model_id: ModelId, model_path: &str) -> Result<Self> {
+        let model_dir = PathBuf::from(model_path);
+        let config_path = Path::join(&model_dir, CONFIG_FILENAME);
+        let config = match config_path.exists() {
+            true => TransformerConfig::from_file(&config_path)?,
+            false => TransformerConfig::default(),
+        };
+
+        let mut tokenizer = Tokenizer::from_file(Path::join(&model_dir, "tokenizer.json"))
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: *config.max_sequence_length(),
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+        let model = tract_onnx::onnx().model_for_path(Path::join(&model_dir, "model.onnx"))?;
+        let input_names = model
+            .input_outlets()?
+            .iter()
+            .map(|outlet| model.node(outlet.node).name.clone())
+            .collect::<Vec<String>>();
+        for name in &input_names {
+            let known = name == config.input_ids()
+                || name == config.attention_mask()
+                || config.token_type_ids().as_ref() == Some(name);
+            if !known {
+                anyhow::bail!(
+                    "input {} of model {} is not described in {}",
+                    name,
+                    model_id,
+                    CONFIG_FILENAME
+                );
+            }
+        }
+        let model = model.into_optimized()?.into_runnable()?;
+
+        let mut transformer = Self {
+            tokenizer,
+            model_id,
+            dimension: config.dimension().unwrap_or_default(),
+            config,
+            input_names,
+            model,
+        };
+        if transformer.config.dimension().is_none() {
+            transformer.dimension = transformer.run_batch(&[PROBE_TEXT])?[0].len();
+        }
+        Ok(transformer)
+    }
+
+    pub fn get_model_id(&self) -> ModelId {
+        self.model_id.clone()
+    }
+
+    /// Embeds indexed texts, prefixed with the passage instruction.
+    pub fn run_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let prefix = self.config.passage_prefix();
+        self.embed_with_prefix(prefix, texts)
+    }
+
+    /// Embeds a search query, prefixed with the query instruction.
+    pub fn run_query(&self, query: &str) -> Result<Vec<f32>> {
+        let prefix = self.config.query_prefix();
+        let mut embeddings = self.embed_with_prefix(prefix, &[query])?;
+        embeddings
+            .pop()
+            .ok_or(anyhow::anyhow!("no embedding returned"))
+    }
+
+    fn embed_with_prefix(&self, prefix: &str, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let texts = texts
+            .iter()
+            .map(|text| format!("{}{}", prefix, text))
+            .collect::<Vec<String>>();
+        let result = self.batch_sentence_embeddings(&texts)?;
+        let dim = result.len() / texts.len();
+        let embeddings = result
+            .into_raw_vec()
+            .chunks(dim)
+            .map(|row| row.to_vec())
+            .collect();
+        Ok(embeddings)
+    }
+
+    // Same padding scheme as `MiniLMEmbeddingModel`, feeding only the inputs
+    // the model declares, in its own order.
+    fn batch_sentence_embeddings(
+        &self,
+        sentences: &[String],
+    ) -> Result<ArrayBase<OwnedRepr<f32>, ndarray::Dim<IxDynImpl>>> {
+        let encoded_inputs = self
+            .tokenizer
+            .encode_batch(sentences.to_vec(), true)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let batch_size = encoded_inputs.len();
+        let length = encoded_inputs.iter().map(|x| x.len()).max().unwrap_or(0);
+
+        let mut input_ids = vec![0i64; batch_size * length];
+        let mut attention_mask = vec![0u32; batch_size * length];
+        let mut token_type_ids = vec![0i64; batch_size * length];
+        for (row, encoded_input) in encoded_inputs.iter().enumerate() {
+            let offset = row * length;
+            for (column, &id) in encoded_input.get_ids().iter().enumerate() {
+                input_ids[offset + column] = id as i64;
+            }
+            for (column, &mask) in encoded_input.get_attention_mask().iter().enumerate() {
+                attention_mask[offset + column] = mask;
+            }
+            for (column, &type_id) in encoded_input.get_type_ids().iter().enumerate() {
+                token_type_ids[offset + column] = type_id as i64;
+            }
+        }
+
+        let mask = attention_mask
+            .iter()
+            .map(|&x| x as i64)
+            .collect::<Vec<i64>>();
+        let inputs = self
+            .input_names
+            .iter()
+            .map(|name| {
+                let values = if name == self.config.input_ids() {
+                    input_ids.clone()
+                } else if name == self.config.attention_mask() {
+                    mask.clone()
+                } else {
+                    token_type_ids.clone()
+                };
+                let tensor: Tensor =
+                    tract_ndarray::Array2::from_shape_vec((batch_size, length), values)?.into();
+                Ok(tensor.into())
+            })
+            .collect::<Result<TVec<TValue>>>()?;
+        let outputs = self.model.run(inputs)?;
+
+        let sentence_embeddings = match self.config.pooling() {
+            PoolingMode::Cls => math::cls_pooling(outputs),
+            PoolingMode::LastToken => math::last_token_pooling(outputs, &attention_mask),
+            PoolingMode::Mean | PoolingMode::Remote => math::mean_pooling(outputs, &attention_mask),
+        }
+        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        if !self.config.normalize() {
+            return Ok(sentence_embeddings);
+        }
+        Ok(math::normalize(&sentence_embeddings))
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for TransformerEmbeddingModel {
+    async fn embed(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.run_batch(data)
+    }
+
+    async fn embed_query(&self, query: &str) -> Result<Vec<f32>> {
+        self.run_query(query)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_id(&self) -> String {
+        self.model_id.to_string()
+    }
+}
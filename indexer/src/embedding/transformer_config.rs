@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::Context;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::PoolingMode;
+use crate::Result;
+
+/// Description of an ONNX sentence-transformer, read from the `embedding.json`
+/// file next to its `model.onnx` and `tokenizer.json`, e.g. for e5:
+///
+/// ```json
+/// {
+///   "pooling": "mean",
+///   "query_prefix": "query: ",
+///   "passage_prefix": "passage: "
+/// }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct TransformerConfig {
+    /// Embedding size, read from the model output when not set.
+    #[builder(default = None)]
+    dimension: Option<usize>,
+    /// Longest input in tokens. Longer inputs are truncated.
+    #[builder(default = 512)]
+    max_sequence_length: usize,
+    /// Names of the model inputs.
+    #[builder(default = "input_ids".to_string())]
+    input_ids: String,
+    #[builder(default = "attention_mask".to_string())]
+    attention_mask: String,
+    /// `None` for models without token type input, e.g. most decoder models.
+    #[builder(default = Some("token_type_ids".to_string()))]
+    token_type_ids: Option<String>,
+    #[builder(default = PoolingMode::Mean)]
+    pooling: PoolingMode,
+    /// Whether embeddings are scaled to unit length.
+    #[builder(default = true)]
+    normalize: bool,
+    /// Instructions prepended to queries and to indexed texts, e.g. `query: `
+    /// and `passage: ` for e5 models.
+    #[builder(default)]
+    query_prefix: String,
+    #[builder(default)]
+    passage_prefix: String,
+}
+
+impl TransformerConfig {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read model config: {}", path.display()))?;
+        let config = serde_json::from_str(&content)
+            .with_context(|| format!("invalid model config: {}", path.display()))?;
+        Ok(config)
+    }
+}
+
+impl Default for TransformerConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_take_their_default() {
+        let config: TransformerConfig = serde_json::from_str(
+            r#"{"pooling": "cls", "token_type_ids": null, "query_prefix": "query: "}"#,
+        )
+        .unwrap();
+        assert_eq!(*config.pooling(), PoolingMode::Cls);
+        assert!(config.token_type_ids().is_none());
+        assert_eq!(config.query_prefix(), "query: ");
+        assert_eq!(config.passage_prefix(), "");
+        assert_eq!(*config.max_sequence_length(), 512);
+        assert_eq!(config.input_ids(), "input_ids");
+        assert!(*config.normalize());
+    }
+}
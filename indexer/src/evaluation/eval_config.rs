@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use anyhow::Context;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::{quantization::Quantization, utils::CHUNK_SIZE, ModelId, Result, SearchOptions};
+
+/// Indexing and search configuration compared by an evaluation.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct EvalConfig {
+    /// Name of the configuration in reports. Configurations read from a file
+    /// default to the file name.
+    #[builder(default = "default".to_string())]
+    name: String,
+    #[builder(default = ModelId::AllMiniLML6V2)]
+    model_id: ModelId,
+    /// Token size of the chunks.
+    #[builder(default = CHUNK_SIZE)]
+    chunk_size: usize,
+    #[builder(default = None)]
+    quantization: Option<Quantization>,
+    /// Cross-encoder of the resources folder, used when `options` rerank.
+    #[builder(default = None)]
+    reranker: Option<String>,
+    /// Token budget of the contexts returned for each query.
+    #[builder(default = 1024)]
+    max_tokens: usize,
+    #[builder(default)]
+    options: SearchOptions,
+}
+
+impl EvalConfig {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config: {}", path.display()))?;
+        let value: serde_json::Value = serde_json::from_str(&content)
+            .with_context(|| format!("invalid config: {}", path.display()))?;
+        let named = value.get("name").is_some();
+        let mut config: Self = serde_json::from_value(value)
+            .with_context(|| format!("invalid config: {}", path.display()))?;
+        if !named {
+            if let Some(stem) = path.file_stem() {
+                config.name = stem.to_string_lossy().to_string();
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
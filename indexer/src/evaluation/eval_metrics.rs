@@ -0,0 +1,12 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Metrics at rank `k`, averaged over the queries of an evaluation set.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct EvalMetrics {
+    k: usize,
+    recall: f32,
+    mrr: f32,
+    ndcg: f32,
+}
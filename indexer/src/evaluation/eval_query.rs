@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::{Context, Result};
+
+/// Labeled query of an evaluation set, one JSON object per line of a JSONL
+/// file. A query may expect whole documents, passages, or both.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct EvalQuery {
+    query: String,
+    /// File names of the documents answering the query. A context is
+    /// relevant to an expected document when it comes from that document.
+    #[builder(default)]
+    #[serde(default)]
+    documents: Vec<String>,
+    /// Texts answering the query. A context is relevant to an expected
+    /// passage when one contains the other, ignoring case and whitespace.
+    #[builder(default)]
+    #[serde(default)]
+    passages: Vec<String>,
+}
+
+impl EvalQuery {
+    pub fn from_jsonl(path: &Path) -> Result<Vec<Self>> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read queries: {}", path.display()))?;
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("invalid query at line {}", index + 1))
+            })
+            .collect()
+    }
+
+    /// Number of expected documents and passages.
+    pub fn expected(&self) -> usize {
+        self.documents.len() + self.passages.len()
+    }
+
+    /// Indices of the expected documents, then passages, that `context` is
+    /// relevant to.
+    pub fn matches(&self, context: &Context) -> Vec<usize> {
+        let reference = context.reference().as_deref();
+        let documents = self
+            .documents
+            .iter()
+            .map(|document| reference == Some(document.as_str()));
+        let data = normalize(context.raw_data());
+        let passages = self.passages.iter().map(|passage| {
+            let passage = normalize(passage);
+            !data.is_empty() && (data.contains(&passage) || passage.contains(&data))
+        });
+        documents
+            .chain(passages)
+            .enumerate()
+            .filter(|(_, matched)| *matched)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
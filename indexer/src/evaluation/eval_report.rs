@@ -0,0 +1,19 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::EvalMetrics;
+
+/// Results of one configuration on an evaluation set.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct EvalReport {
+    /// Name of the evaluated configuration.
+    name: String,
+    queries: usize,
+    /// Nodes indexed over all documents, sub-chunks excluded.
+    nodes: usize,
+    /// One entry per evaluated `k`, in increasing order.
+    metrics: Vec<EvalMetrics>,
+    /// Queries without any relevant context within the largest `k`.
+    missed: Vec<String>,
+}
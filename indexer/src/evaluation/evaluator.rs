@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use document::document::Document;
+use tracing::info;
+
+use super::{metrics, EvalConfig, EvalMetrics, EvalQuery, EvalReport};
+use crate::{
+    graph::Graph,
+    utils::{self, indexing_sections},
+    Indexer,
+    IndexingMeta,
+    Result,
+    TokenBudget,
+};
+
+// Chat model whose encoding counts the budget of the returned contexts.
+const BUDGET_MODEL: &str = "gpt-3.5-turbo";
+
+/// Runs a labeled query set against a folder of documents, going through the
+/// parsing, indexing and `search_context` steps of the deployed pipeline
+/// without S3.
+pub struct Evaluator {
+    resources_path: String,
+    documents: Vec<(IndexingMeta, Document)>,
+}
+
+impl Evaluator {
+    /// Parses the PDF and DOCX files of `documents_path`. Documents are
+    /// identified by their file name, which expected documents refer to.
+    /// Files that fail to parse are skipped.
+    pub fn from_folder(documents_path: &Path, resources_path: &str) -> Result<Self> {
+        let documents = utils::parse_folder(documents_path, resources_path)
+            .with_context(|| format!("failed to read folder: {}", documents_path.display()))?
+            .into_iter()
+            .map(|(filename, document)| {
+                let meta = IndexingMeta::builder()
+                    .id(filename.clone())
+                    .title(filename.clone())
+                    .external_link(documents_path.join(&filename).to_string_lossy().to_string())
+                    .build();
+                (meta, document)
+            })
+            .collect::<Vec<(IndexingMeta, Document)>>();
+        if documents.is_empty() {
+            anyhow::bail!("no document in folder: {}", documents_path.display());
+        }
+        info!("parsed {} documents", documents.len());
+
+        Ok(Self {
+            resources_path: resources_path.to_string(),
+            documents,
+        })
+    }
+
+    /// Indexes the documents with `config` and reports its metrics at each of
+    /// `ks`.
+    pub async fn evaluate(
+        &self,
+        config: &EvalConfig,
+        queries: &[EvalQuery],
+        ks: &[usize],
+    ) -> Result<EvalReport> {
+        let model =
+            utils::load_embedding_model(&self.resources_path, config.model_id().clone()).await?;
+        let reranker = match config.reranker() {
+            Some(model_name) => {
+                Some(utils::load_reranker_model(&self.resources_path, model_name).await?)
+            }
+            None => None,
+        };
+        let mut indexer = Indexer::new(model)?;
+        indexer.set_quantization(*config.quantization());
+        let graphs = self.index(&indexer, config).await?;
+        let nodes = graphs.iter().map(|graph| graph.node_count()).sum();
+        let budget = TokenBudget::new(BUDGET_MODEL, *config.max_tokens())?;
+
+        let mut ks = ks.to_vec();
+        ks.sort();
+        ks.dedup();
+        let max_k = ks.last().copied().unwrap_or_default();
+        let mut totals = vec![(0.0, 0.0, 0.0); ks.len()];
+        let mut missed = vec![];
+        for query in queries {
+            let contexts = utils::search_context(
+                &graphs,
+                query.query(),
+                indexer.model(),
+                reranker.as_ref(),
+                &budget,
+                config.options(),
+            )
+            .await?;
+            let matches = contexts
+                .iter()
+                .map(|context| query.matches(context))
+                .collect::<Vec<Vec<usize>>>();
+            let expected = query.expected();
+            for (total, k) in totals.iter_mut().zip(&ks) {
+                total.0 += metrics::recall_at_k(&matches, expected, *k);
+                total.1 += metrics::reciprocal_rank_at_k(&matches, *k);
+                total.2 += metrics::ndcg_at_k(&matches, expected, *k);
+            }
+            if metrics::reciprocal_rank_at_k(&matches, max_k) == 0.0 {
+                missed.push(query.query().to_string());
+            }
+        }
+
+        let count = queries.len().max(1) as f32;
+        let metrics = ks
+            .iter()
+            .zip(totals)
+            .map(|(k, (recall, mrr, ndcg))| {
+                EvalMetrics::builder()
+                    .k(*k)
+                    .recall(recall / count)
+                    .mrr(mrr / count)
+                    .ndcg(ndcg / count)
+                    .build()
+            })
+            .collect();
+        Ok(EvalReport::builder()
+            .name(config.name().to_string())
+            .queries(queries.len())
+            .nodes(nodes)
+            .metrics(metrics)
+            .missed(missed)
+            .build())
+    }
+
+    async fn index(&self, indexer: &Indexer, config: &EvalConfig) -> Result<Vec<Graph>> {
+        let mut graphs = vec![];
+        for (meta, document) in &self.documents {
+            let sections = indexing_sections(document, *config.chunk_size());
+            let (mut graph, _) = indexer.reindex(sections, meta.clone(), None).await?;
+            for other in &graphs {
+                indexer.link_document(&mut graph, other);
+            }
+            graphs.push(graph);
+        }
+        info!(
+            "indexed {} documents with config: {}",
+            graphs.len(),
+            config.name()
+        );
+        Ok(graphs)
+    }
+}
@@ -0,0 +1,95 @@
+//! Ranking metrics of a query. `matches` holds, for each returned context in
+//! rank order, the indices of the expected items it is relevant to, and
+//! `expected` is the number of expected items.
+
+use std::collections::HashSet;
+
+/// Share of the expected items found in the first `k` contexts.
+pub fn recall_at_k(matches: &[Vec<usize>], expected: usize, k: usize) -> f32 {
+    if expected == 0 {
+        return 0.0;
+    }
+    let found = matches
+        .iter()
+        .take(k)
+        .flatten()
+        .collect::<HashSet<&usize>>();
+    found.len() as f32 / expected as f32
+}
+
+/// Inverse rank of the first relevant context, or zero when none of the first
+/// `k` contexts is relevant.
+pub fn reciprocal_rank_at_k(matches: &[Vec<usize>], k: usize) -> f32 {
+    matches
+        .iter()
+        .take(k)
+        .position(|items| !items.is_empty())
+        .map_or(0.0, |rank| 1.0 / (rank + 1) as f32)
+}
+
+/// Normalized discounted cumulative gain of the first `k` contexts, with a
+/// gain of one for contexts finding an expected item not found at a better
+/// rank, so that repeated contexts do not count twice.
+pub fn ndcg_at_k(matches: &[Vec<usize>], expected: usize, k: usize) -> f32 {
+    let ideal = (0..expected.min(k)).map(discount).sum::<f32>();
+    if ideal == 0.0 {
+        return 0.0;
+    }
+    let mut found = HashSet::new();
+    let gain = matches
+        .iter()
+        .take(k)
+        .enumerate()
+        .filter(|(_, items)| items.iter().filter(|item| found.insert(**item)).count() > 0)
+        .map(|(rank, _)| discount(rank))
+        .sum::<f32>();
+    gain / ideal
+}
+
+fn discount(rank: usize) -> f32 {
+    1.0 / (rank as f32 + 2.0).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    fn matches() -> Vec<Vec<usize>> {
+        vec![vec![], vec![0], vec![0, 1], vec![2]]
+    }
+
+    #[test]
+    fn recall_counts_distinct_items() {
+        let matches = matches();
+        assert_close(recall_at_k(&matches, 3, 1), 0.0);
+        assert_close(recall_at_k(&matches, 3, 2), 1.0 / 3.0);
+        assert_close(recall_at_k(&matches, 3, 3), 2.0 / 3.0);
+        assert_close(recall_at_k(&matches, 3, 10), 1.0);
+        assert_close(recall_at_k(&matches, 0, 10), 0.0);
+    }
+
+    #[test]
+    fn reciprocal_rank_of_first_relevant_context() {
+        let matches = matches();
+        assert_close(reciprocal_rank_at_k(&matches, 1), 0.0);
+        assert_close(reciprocal_rank_at_k(&matches, 2), 0.5);
+        assert_close(reciprocal_rank_at_k(&[vec![1]], 5), 1.0);
+        assert_close(reciprocal_rank_at_k(&[], 5), 0.0);
+    }
+
+    #[test]
+    fn ndcg_discounts_ranks_and_repeats() {
+        assert_close(ndcg_at_k(&[vec![0], vec![1]], 2, 10), 1.0);
+        assert_close(ndcg_at_k(&[vec![], vec![0]], 1, 10), 1.0 / 3f32.log2());
+        // A context finding an item already found gains nothing.
+        let ideal = 1.0 + 1.0 / 3f32.log2();
+        assert_close(ndcg_at_k(&[vec![0], vec![0]], 2, 10), 1.0 / ideal);
+        // The ideal ranking is cut at `k`.
+        assert_close(ndcg_at_k(&[vec![0], vec![1]], 5, 2), 1.0);
+        assert_close(ndcg_at_k(&[vec![0]], 0, 10), 0.0);
+    }
+}
@@ -0,0 +1,13 @@
+mod eval_config;
+mod eval_metrics;
+mod eval_query;
+mod eval_report;
+mod evaluator;
+mod metrics;
+
+pub use eval_config::EvalConfig;
+pub use eval_metrics::EvalMetrics;
+pub use eval_query::EvalQuery;
+pub use eval_report::EvalReport;
+pub use evaluator::Evaluator;
+pub use metrics::{ndcg_at_k, recall_at_k, reciprocal_rank_at_k};
@@ -0,0 +1,28 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::graph::EdgeKind;
+
+/// Adds the nodes linked to the best retrieved nodes by graph edges, to gather
+/// the context around them.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct ExpansionOptions {
+    /// Kinds of edges followed.
+    #[builder(default = vec![EdgeKind::Next, EdgeKind::Previous])]
+    kinds: Vec<EdgeKind>,
+    /// Best nodes of each graph whose edges are followed.
+    #[builder(default = 3)]
+    hits: usize,
+    /// Nodes added per hit. Sequential and section edges come first, then
+    /// semantic and cross-document edges by decreasing similarity.
+    #[builder(default = 2)]
+    edges_per_hit: usize,
+}
+
+impl Default for ExpansionOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
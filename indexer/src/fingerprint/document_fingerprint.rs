@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::{min_hash_similarity, DuplicateDocuments, DuplicateKind};
+use crate::graph::Graph;
+
+// Estimated Jaccard similarity from which documents are near duplicates.
+const NEAR_DUPLICATE_SIMILARITY: f32 = 0.7;
+
+/// What duplicate detection keeps of a graph, so that the graphs of a group
+/// need not be held in memory at once. Stored next to the graph, so that
+/// duplicates are found without downloading graphs.
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct DocumentFingerprint {
+    #[serde(skip)] // the key the fingerprint is stored under
+    key: String,
+    min_hash: Vec<u32>,
+    chunk_hashes: HashSet<String>,
+}
+
+impl DocumentFingerprint {
+    pub fn new(key: &str, min_hash: Vec<u32>, chunk_hashes: HashSet<String>) -> Self {
+        Self {
+            key: key.to_string(),
+            min_hash,
+            chunk_hashes,
+        }
+    }
+
+    pub fn from_graph(key: &str, graph: &Graph) -> Self {
+        let chunk_hashes = graph
+            .node_map()
+            .values()
+            .map(|node| node.hash().to_string())
+            .collect();
+        Self::new(key, graph.min_hash().clone(), chunk_hashes)
+    }
+
+    pub fn set_key(&mut self, key: &str) {
+        self.key = key.to_string();
+    }
+
+    /// Duplicate when both documents have the same chunks, near duplicate
+    /// when their texts are similar enough. Graphs indexed without MinHash
+    /// signature are only found as duplicates.
+    pub fn compare(&self, other: &DocumentFingerprint) -> Option<DuplicateDocuments> {
+        let (kind, similarity) =
+            if !self.chunk_hashes.is_empty() && self.chunk_hashes == other.chunk_hashes {
+                (DuplicateKind::Duplicate, 1.0)
+            } else {
+                let similarity = min_hash_similarity(&self.min_hash, &other.min_hash);
+                if similarity < NEAR_DUPLICATE_SIMILARITY {
+                    return None;
+                }
+                (DuplicateKind::NearDuplicate, similarity)
+            };
+        Some(
+            DuplicateDocuments::builder()
+                .kind(kind)
+                .first(self.key.clone())
+                .second(other.key.clone())
+                .similarity(similarity)
+                .build(),
+        )
+    }
+}
+
+/// Duplicate and near duplicate pairs among `fingerprints`, most similar
+/// first.
+pub fn find_duplicates(fingerprints: &[DocumentFingerprint]) -> Vec<DuplicateDocuments> {
+    let mut duplicates = fingerprints
+        .iter()
+        .enumerate()
+        .flat_map(|(index, fingerprint)| {
+            fingerprints[index + 1..]
+                .iter()
+                .filter_map(|other| fingerprint.compare(other))
+        })
+        .collect::<Vec<DuplicateDocuments>>();
+    duplicates.sort_by(|a, b| b.similarity().total_cmp(a.similarity()));
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fingerprint::min_hash;
+
+    fn fingerprint(key: &str, text: &str, chunks: &[&str]) -> DocumentFingerprint {
+        let chunk_hashes = chunks.iter().map(|chunk| chunk.to_string()).collect();
+        DocumentFingerprint::new(key, min_hash([text]), chunk_hashes)
+    }
+
+    fn text(words: usize, prefix: &str) -> String {
+        (0..words)
+            .map(|index| format!("{prefix}{index}"))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn same_chunks_are_duplicates() {
+        let first = fingerprint("first", "some text", &["a", "b"]);
+        let second = fingerprint("second", "other words entirely", &["b", "a"]);
+
+        let duplicate = first.compare(&second).unwrap();
+        assert_eq!(*duplicate.kind(), DuplicateKind::Duplicate);
+        assert_eq!(*duplicate.similarity(), 1.0);
+        assert_eq!(duplicate.first(), "first");
+        assert_eq!(duplicate.second(), "second");
+    }
+
+    #[test]
+    fn similar_texts_are_near_duplicates() {
+        let original = text(300, "term");
+        let revision = original.replace("term150 ", "other ");
+        let first = fingerprint("first", &original, &["a"]);
+        let second = fingerprint("second", &revision, &["b"]);
+        let unrelated = fingerprint("third", &text(300, "word"), &["c"]);
+
+        let duplicate = first.compare(&second).unwrap();
+        assert_eq!(*duplicate.kind(), DuplicateKind::NearDuplicate);
+        assert!(*duplicate.similarity() >= NEAR_DUPLICATE_SIMILARITY);
+        assert!(first.compare(&unrelated).is_none());
+        // Graphs without chunks or signature are never duplicates.
+        let empty = fingerprint("empty", "", &[]);
+        assert!(empty.compare(&empty).is_none());
+    }
+
+    #[test]
+    fn duplicates_are_sorted_by_similarity() {
+        let original = text(300, "term");
+        let revision = original.replace("term150 ", "other ");
+        let fingerprints = [
+            fingerprint("a", &original, &["1"]),
+            fingerprint("b", &revision, &["2"]),
+            fingerprint("c", &text(300, "word"), &["1"]),
+        ];
+
+        let duplicates = find_duplicates(&fingerprints);
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(*duplicates[0].kind(), DuplicateKind::Duplicate);
+        assert_eq!(duplicates[0].second(), "c");
+        assert_eq!(*duplicates[1].kind(), DuplicateKind::NearDuplicate);
+        assert_eq!(duplicates[1].second(), "b");
+    }
+}
@@ -0,0 +1,15 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::DuplicateKind;
+
+/// Pair of documents with the same or mostly the same content.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct DuplicateDocuments {
+    kind: DuplicateKind,
+    first: String,
+    second: String,
+    /// Estimated Jaccard similarity of their texts.
+    similarity: f32,
+}
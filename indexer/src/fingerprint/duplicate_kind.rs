@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateKind {
+    /// Same chunks, e.g. the same file uploaded twice.
+    Duplicate,
+    /// Mostly the same text, e.g. two revisions of a document.
+    NearDuplicate,
+}
@@ -0,0 +1,96 @@
+use super::MinHasher;
+
+/// Values of a MinHash signature.
+pub const MIN_HASH_SIZE: usize = 128;
+// Terms per shingle.
+pub(super) const SHINGLE_SIZE: usize = 5;
+// Mersenne prime modulus of the permutations.
+pub(super) const PRIME: u64 = (1 << 61) - 1;
+
+/// MinHash signature of the term shingles of `texts`. The share of equal
+/// values of two signatures estimates the Jaccard similarity of their
+/// shingle sets. Empty without any term.
+pub fn min_hash<'t>(texts: impl IntoIterator<Item = &'t str>) -> Vec<u32> {
+    let mut hasher = MinHasher::new();
+    for text in texts {
+        hasher.add(text);
+    }
+    hasher.finish()
+}
+
+/// Estimated Jaccard similarity of the texts of two signatures. Zero when a
+/// signature is empty or they were computed with different sizes.
+pub fn min_hash_similarity(a: &[u32], b: &[u32]) -> f32 {
+    if a.is_empty() || a.len() != b.len() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b).filter(|(a, b)| a == b).count();
+    equal as f32 / a.len() as f32
+}
+
+// Coefficients of the `MIN_HASH_SIZE` permutations `(a * x + b) mod PRIME`,
+// drawn with splitmix64 from a fixed seed.
+pub(super) fn permutations() -> impl Iterator<Item = (u64, u64)> {
+    let mut state: u64 = 0x9e3779b97f4a7c15;
+    let mut next = move || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        (z ^ (z >> 31)) % PRIME
+    };
+    (0..MIN_HASH_SIZE).map(move |_| (next().max(1), next()))
+}
+
+pub(super) fn permute(x: u64, a: u64, b: u64) -> u64 {
+    ((a as u128 * x as u128 + b as u128) % PRIME as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(words: usize, prefix: &str) -> String {
+        (0..words)
+            .map(|index| format!("{prefix}{index}"))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn similarity_estimates_shared_shingles() {
+        let original = text(300, "term");
+        let revised = original
+            .replace("term100 ", "other ")
+            .replace("term200 ", "other ");
+        let unrelated = text(300, "word");
+
+        let signature = min_hash([original.as_str()]);
+        assert_eq!(signature.len(), MIN_HASH_SIZE);
+        assert_eq!(min_hash_similarity(&signature, &signature), 1.0);
+        let revised = min_hash_similarity(&signature, &min_hash([revised.as_str()]));
+        assert!((0.8..1.0).contains(&revised), "similarity: {revised}");
+        let unrelated = min_hash_similarity(&signature, &min_hash([unrelated.as_str()]));
+        assert!(unrelated < 0.1, "similarity: {unrelated}");
+    }
+
+    #[test]
+    fn texts_are_shingled_across_boundaries() {
+        let words = text(40, "term");
+        let (first, second) = words.split_at(words.find("term13").unwrap());
+        assert_eq!(min_hash([first, second]), min_hash([words.as_str()]));
+
+        let mut hasher = MinHasher::new();
+        hasher.add(first);
+        hasher.add(second);
+        assert_eq!(hasher.finish(), min_hash([words.as_str()]));
+    }
+
+    #[test]
+    fn short_and_empty_texts() {
+        assert_eq!(min_hash(["two words"]).len(), MIN_HASH_SIZE);
+        assert!(min_hash(["", " ,. "]).is_empty());
+        assert_eq!(min_hash_similarity(&[], &[]), 0.0);
+        assert_eq!(min_hash_similarity(&[1, 2], &[1]), 0.0);
+    }
+}
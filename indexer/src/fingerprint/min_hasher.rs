@@ -0,0 +1,74 @@
+use std::collections::VecDeque;
+
+use super::{
+    min_hash::{permutations, permute, PRIME, SHINGLE_SIZE},
+    simhash::stable_hash,
+};
+use crate::lexical::tokenize;
+
+/// MinHash signature of texts added one at a time, equal to `min_hash` of all
+/// of them. Only the last terms are kept between texts.
+#[derive(Debug)]
+pub struct MinHasher {
+    permutations: Vec<(u64, u64)>,
+    mins: Vec<u64>,
+    // Last terms, the start of the next shingle.
+    window: VecDeque<String>,
+    terms: usize,
+}
+
+impl MinHasher {
+    pub fn new() -> Self {
+        let permutations = permutations().collect::<Vec<(u64, u64)>>();
+        Self {
+            mins: vec![u64::MAX; permutations.len()],
+            permutations,
+            window: VecDeque::with_capacity(SHINGLE_SIZE),
+            terms: 0,
+        }
+    }
+
+    pub fn add(&mut self, text: &str) {
+        for term in tokenize(text) {
+            if self.window.len() == SHINGLE_SIZE {
+                self.window.pop_front();
+            }
+            self.window.push_back(term);
+            self.terms += 1;
+            if self.window.len() == SHINGLE_SIZE {
+                self.add_shingle();
+            }
+        }
+    }
+
+    /// Signature of the added texts. Texts shorter than a shingle make a
+    /// single shingle.
+    pub fn finish(mut self) -> Vec<u32> {
+        if self.terms == 0 {
+            return vec![];
+        }
+        if self.terms < SHINGLE_SIZE {
+            self.add_shingle();
+        }
+        self.mins.iter().map(|min| *min as u32).collect()
+    }
+
+    fn add_shingle(&mut self) {
+        let shingle = self
+            .window
+            .iter()
+            .map(|term| term.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let shingle = stable_hash(shingle.as_bytes()) % PRIME;
+        for ((a, b), min) in self.permutations.iter().zip(self.mins.iter_mut()) {
+            *min = (*min).min(permute(shingle, *a, *b));
+        }
+    }
+}
+
+impl Default for MinHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
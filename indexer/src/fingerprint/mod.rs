@@ -0,0 +1,13 @@
+mod document_fingerprint;
+mod duplicate_documents;
+mod duplicate_kind;
+mod min_hash;
+mod min_hasher;
+mod simhash;
+
+pub use document_fingerprint::{find_duplicates, DocumentFingerprint};
+pub use duplicate_documents::DuplicateDocuments;
+pub use duplicate_kind::DuplicateKind;
+pub use min_hash::{min_hash, min_hash_similarity, MIN_HASH_SIZE};
+pub use min_hasher::MinHasher;
+pub use simhash::{is_near_duplicate, simhash};
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::lexical::tokenize;
+
+// Differing bits up to which two texts are near duplicates.
+const NEAR_DUPLICATE_BITS: u32 = 4;
+
+/// SimHash of the terms of `text`, weighted by their count. Close texts get
+/// fingerprints differing in few bits.
+pub fn simhash(text: &str) -> u64 {
+    let mut counts: HashMap<String, i32> = HashMap::new();
+    for token in tokenize(text) {
+        *counts.entry(token).or_default() += 1;
+    }
+    let mut weights = [0i32; 64];
+    for (token, count) in counts {
+        let hash = stable_hash(token.as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            match hash >> bit & 1 {
+                1 => *weight += count,
+                _ => *weight -= count,
+            }
+        }
+    }
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |fingerprint, (bit, _)| fingerprint | 1 << bit)
+}
+
+pub fn is_near_duplicate(a: u64, b: u64) -> bool {
+    (a ^ b).count_ones() <= NEAR_DUPLICATE_BITS
+}
+
+// FNV-1a, a hash that stays the same across builds, as fingerprints are stored.
+pub(super) fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "The pump must be inspected every month. Check the pressure of the \
+                        pump and the seals of the valves. Replace the filter of the pump \
+                        when the pressure drops below the limit, and record the inspection \
+                        in the maintenance log of the pump.";
+
+    #[test]
+    fn near_duplicates_differ_in_few_bits() {
+        let fingerprint = simhash(TEXT);
+        assert_eq!(fingerprint, simhash(&TEXT.to_uppercase()));
+        assert_eq!(fingerprint, simhash(&TEXT.replace(". ", ".\n")));
+        for revision in [
+            TEXT.replace("every month", "every week"),
+            TEXT.replace("record", "write"),
+            format!("{TEXT} Done."),
+        ] {
+            assert!(is_near_duplicate(fingerprint, simhash(&revision)));
+        }
+
+        let unrelated = "Quarterly revenue grew by ten percent thanks to strong sales in \
+                         the northern region and lower costs.";
+        assert!(!is_near_duplicate(fingerprint, simhash(unrelated)));
+    }
+
+    #[test]
+    fn near_duplicate_threshold() {
+        assert!(is_near_duplicate(0, 0b1111));
+        assert!(is_near_duplicate(u64::MAX, u64::MAX << 4));
+        assert!(!is_near_duplicate(0, 0b11111));
+    }
+
+    #[test]
+    fn stable_across_builds() {
+        assert_eq!(stable_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(stable_hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(simhash(""), 0);
+    }
+}
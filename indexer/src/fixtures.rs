@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    graph::{Graph, Node, NodeId},
+    EmbeddingProvider,
+    Result,
+};
+
+/// Graph of `count` nodes with pseudo-random embeddings of `dimension`, in
+/// `[-0.5, 0.5)` and reproducible for a `seed`.
+pub(crate) fn random_graph(count: usize, dimension: usize, seed: u64) -> Graph {
+    let mut state = seed;
+    let node_map = (0..count)
+        .map(|index| {
+            let node = Node::builder()
+                .id(format!("node-{index:04}"))
+                .data(String::new())
+                .rank_id(index.to_string())
+                .hash(String::new())
+                .embeddings(random_vector(&mut state, dimension))
+                .build();
+            (node.id().to_string(), node)
+        })
+        .collect::<HashMap<NodeId, Node>>();
+    Graph::builder()
+        .id("graph".to_string())
+        .title("graph".to_string())
+        .node_map(node_map)
+        .index_model(None)
+        .build()
+}
+
+/// Embedding provider mapping every input to a pseudo-random vector seeded by
+/// its text, so equal inputs get equal embeddings. Counts the inputs it
+/// embedded.
+#[derive(Debug)]
+pub(crate) struct HashProvider {
+    model_id: String,
+    dimension: usize,
+    embedded: AtomicUsize,
+}
+
+impl HashProvider {
+    pub(crate) fn new(model_id: &str, dimension: usize) -> Self {
+        Self {
+            model_id: model_id.to_string(),
+            dimension,
+            embedded: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of inputs embedded so far.
+    pub(crate) fn embedded(&self) -> usize {
+        self.embedded.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashProvider {
+    async fn embed(&self, data: &[&str]) -> Result<Vec<Vec<f32>>> {
+        self.embedded.fetch_add(data.len(), Ordering::SeqCst);
+        Ok(data
+            .iter()
+            .map(|text| {
+                let mut state = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+                    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+                });
+                random_vector(&mut state, self.dimension)
+            })
+            .collect())
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn model_id(&self) -> String {
+        self.model_id.clone()
+    }
+}
+
+// Advances the LCG `state` once per component.
+fn random_vector(state: &mut u64, dimension: usize) -> Vec<f32> {
+    (0..dimension)
+        .map(|_| {
+            *state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (*state >> 40) as f32 / (1u64 << 24) as f32 - 0.5
+        })
+        .collect()
+}
@@ -0,0 +1,21 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Attributes of the indexed document that searches can filter on.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct DocumentMetadata {
+    #[builder(default = None)]
+    creator: Option<String>,
+    /// Unix timestamp in seconds of when the document was added: its upload
+    /// time, or the modification time of files indexed from a local folder.
+    /// Not the creation date written in the file, which many files lack.
+    #[builder(default = None)]
+    creation_time: Option<i64>,
+    /// Lowercase file extension, e.g. `pdf`.
+    #[builder(default = None)]
+    file_type: Option<String>,
+    #[builder(default)]
+    tags: Vec<String>,
+}
@@ -0,0 +1,20 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::{EdgeKind, NodeId};
+
+/// Edge from a node of the graph to `target`.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct Edge {
+    kind: EdgeKind,
+    target: NodeId,
+    /// Reference of the document holding `target`, for cross-document edges.
+    #[serde(default)]
+    #[builder(default = None)]
+    document: Option<String>,
+    /// Cosine similarity of the nodes for semantic and cross-document edges,
+    /// `1.0` for the others.
+    #[builder(default = 1.0)]
+    weight: f32,
+}
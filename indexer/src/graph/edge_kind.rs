@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// Next chunk in text order.
+    Next,
+    /// Previous chunk in text order.
+    Previous,
+    /// Another chunk of the same section.
+    Section,
+    /// Chunk of the same document with a close embedding.
+    Semantic,
+    /// Chunk of another document with a close embedding.
+    CrossDocument,
+}
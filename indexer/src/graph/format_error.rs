@@ -0,0 +1,21 @@
+#[derive(thiserror::Error, Debug)]
+pub enum GraphFormatError {
+    #[error("not a binary graph")]
+    InvalidMagic,
+    #[error("unsupported binary graph version: {0}")]
+    UnsupportedVersion(u16),
+    #[error("unknown binary graph encoding: {0}")]
+    UnknownEncoding(u16),
+    #[error("binary graph truncated: expected {expected} bytes, got {actual}")]
+    Truncated { expected: usize, actual: usize },
+    #[error("node {node} has {actual} dimensions, expected {expected}")]
+    InconsistentDimension {
+        node: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("invalid binary graph metadata: {0}")]
+    Metadata(#[from] serde_json::Error),
+    #[error("failed to write binary graph: {0}")]
+    Io(#[from] std::io::Error),
+}
@@ -1,28 +1,88 @@
-use std::{collections::HashMap, error::Error, str::FromStr};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    error::Error,
+    str::FromStr,
+};
 
 use derive_getters::Getters;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 use typed_builder::TypedBuilder;
 
-use super::{node::NodeId, Node};
-use crate::embedding::ModelId;
+use super::{
+    graph_view::GraphMetadata,
+    node::NodeId,
+    DocumentMetadata,
+    Edge,
+    GraphFormatError,
+    GraphHeader,
+    GraphView,
+    Node,
+};
+use crate::{
+    ann::{HnswIndex, SearchStrategy},
+    lexical::Bm25Index,
+    math,
+    quantization::{QuantizedEmbeddings, DEFAULT_RESCORE_FACTOR},
+    ExpansionOptions,
+    SearchFilter,
+};
 
 pub type GraphId = String;
 
-#[derive(Serialize, Deserialize, Debug, TypedBuilder, Getters)]
+#[derive(Clone, Serialize, Deserialize, Debug, TypedBuilder, Getters)]
 pub struct Graph {
     id: GraphId,
     title: String,
+    #[serde(default)]
+    #[builder(default)]
+    metadata: DocumentMetadata,
     node_map: HashMap<NodeId, Node>,
-    index_model: Option<ModelId>,
+    /// Section nodes, parents of the nodes in `node_map`. Not searched
+    /// directly.
+    #[serde(default)]
+    #[builder(default)]
+    section_map: HashMap<NodeId, Node>,
+    /// Sub-chunks of the nodes in `node_map`, scored in their place once the
+    /// nodes are retrieved.
+    #[serde(default)]
+    #[builder(default)]
+    sub_chunk_map: HashMap<NodeId, Node>,
+    /// Edges leaving the nodes in `node_map`, by source node id.
+    #[serde(default)]
+    #[builder(default)]
+    edge_map: HashMap<NodeId, Vec<Edge>>,
+    index_model: Option<String>, // key in `EmbeddingRegistry`
+    /// Centroid of the node embeddings, used to route queries to documents.
     #[builder(default = None)]
     embeddings: Option<Vec<f32>>,
+    /// MinHash signature of the document text, to find near duplicate
+    /// documents.
+    #[serde(default)]
+    #[builder(default)]
+    min_hash: Vec<u32>,
     #[builder(default = None)]
     reference: Option<String>,
     #[builder(default = None)] // reference document
     reference_link: Option<String>, // reference document
     #[builder(default = None)]
     hash: Option<String>,
+    #[serde(default)]
+    #[builder(default = None)]
+    lexical_index: Option<Bm25Index>,
+    #[serde(skip)] // stored next to the graph
+    #[builder(default = None)]
+    ann_index: Option<HnswIndex>,
+    #[serde(default)]
+    #[builder(default = None)]
+    quantized: Option<QuantizedEmbeddings>,
+    /// Key of the document the graph was loaded for, `None` for graphs not
+    /// loaded from storage.
+    #[serde(skip)] // set when loaded
+    #[builder(default = None)]
+    document_key: Option<String>,
 }
 
 impl Graph {
@@ -33,6 +93,364 @@ impl Graph {
     pub fn get_all_nodes(&self) -> Vec<Node> {
         self.node_map.clone().into_values().collect()
     }
+
+    /// Length of the node embeddings, `None` for graphs without embeddings.
+    pub fn dimension(&self) -> Option<usize> {
+        if let Some(quantized) = &self.quantized {
+            return Some(*quantized.dimension());
+        }
+        self.node_map
+            .values()
+            .map(|node| node.embeddings().len())
+            .find(|dimension| *dimension > 0)
+    }
+
+    pub(super) fn node_map_mut(&mut self) -> &mut HashMap<NodeId, Node> {
+        &mut self.node_map
+    }
+
+    pub(super) fn sub_chunk_map_mut(&mut self) -> &mut HashMap<NodeId, Node> {
+        &mut self.sub_chunk_map
+    }
+
+    pub(super) fn section_map_mut(&mut self) -> &mut HashMap<NodeId, Node> {
+        &mut self.section_map
+    }
+
+    pub(crate) fn edge_map_mut(&mut self) -> &mut HashMap<NodeId, Vec<Edge>> {
+        &mut self.edge_map
+    }
+
+    pub(super) fn quantized_mut(&mut self) -> Option<&mut QuantizedEmbeddings> {
+        self.quantized.as_mut()
+    }
+
+    /// Embeddings of `node`, a node, sub-chunk or section of the graph. They
+    /// are dequantized for graphs loaded with quantized embeddings, and empty
+    /// when the graph has none.
+    pub fn embedding<'n>(&self, node: &'n Node) -> Cow<'n, [f32]> {
+        if !node.embeddings().is_empty() {
+            return Cow::Borrowed(node.embeddings());
+        }
+        self.quantized
+            .as_ref()
+            .and_then(|quantized| quantized.embedding(node.id()))
+            .map_or(Cow::Borrowed(&[]), Cow::Owned)
+    }
+
+    /// Encodes the graph in the binary format described in `GraphHeader`.
+    /// Quantized graphs are stored without their full precision embeddings,
+    /// and their sub-chunk and section embeddings are quantized the same way,
+    /// or keep their codes when the graph was loaded quantized.
+    /// Rows of the embedding matrix are ordered by node id, then by sub-chunk
+    /// id, then by section id.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GraphFormatError> {
+        let mut node_ids = self.node_map.keys().cloned().collect::<Vec<NodeId>>();
+        node_ids.sort();
+        let mut sub_chunk_ids = self.sub_chunk_map.keys().cloned().collect::<Vec<NodeId>>();
+        sub_chunk_ids.sort();
+        let mut section_ids = self.section_map.keys().cloned().collect::<Vec<NodeId>>();
+        section_ids.sort();
+        let child_rows = sub_chunk_ids
+            .iter()
+            .map(|id| &self.sub_chunk_map[id])
+            .chain(section_ids.iter().map(|id| &self.section_map[id]))
+            .collect::<Vec<&Node>>();
+        let quantized = self
+            .quantized
+            .as_ref()
+            .filter(|quantized| quantized.node_ids() == &node_ids);
+
+        let (dimension, matrix) = match quantized {
+            Some(quantized) => {
+                let mut codes = quantized.codes().to_vec();
+                for node in &child_rows {
+                    let code = quantized
+                        .child_code(node.id())
+                        .filter(|_| node.embeddings().is_empty());
+                    match code {
+                        Some(code) => codes.extend_from_slice(code),
+                        None => {
+                            check_dimension(node, *quantized.dimension())?;
+                            quantized
+                                .calibration()
+                                .encode(node.embeddings(), &mut codes);
+                        }
+                    }
+                }
+                (*quantized.dimension(), codes)
+            }
+            None => {
+                let rows = node_ids
+                    .iter()
+                    .map(|id| &self.node_map[id])
+                    .chain(child_rows.iter().copied())
+                    .collect::<Vec<&Node>>();
+                let dimension = rows.first().map_or(0, |node| node.embeddings().len());
+                let mut embeddings = Vec::with_capacity(rows.len() * dimension * 4);
+                for node in rows {
+                    check_dimension(node, dimension)?;
+                    for value in node.embeddings() {
+                        embeddings.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+                (dimension, embeddings)
+            }
+        };
+
+        let graph = Graph {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            metadata: self.metadata.clone(),
+            node_map: strip_embeddings(&self.node_map),
+            section_map: strip_embeddings(&self.section_map),
+            sub_chunk_map: strip_embeddings(&self.sub_chunk_map),
+            edge_map: self.edge_map.clone(),
+            index_model: self.index_model.clone(),
+            embeddings: self.embeddings.clone(),
+            min_hash: self.min_hash.clone(),
+            reference: self.reference.clone(),
+            reference_link: self.reference_link.clone(),
+            hash: self.hash.clone(),
+            lexical_index: self.lexical_index.clone(),
+            ann_index: None,
+            quantized: quantized.map(|quantized| quantized.without_codes()),
+            document_key: None,
+        };
+        let row_count = node_ids.len() + sub_chunk_ids.len() + section_ids.len();
+        node_ids.extend(sub_chunk_ids);
+        node_ids.extend(section_ids);
+        let metadata = serde_json::to_vec(&GraphMetadata { graph, node_ids })?;
+
+        let header = GraphHeader::new(
+            quantized.map(|quantized| quantized.quantization()),
+            dimension as u32,
+            row_count as u32,
+            metadata.len() as u64,
+        );
+        let mut bytes = Vec::with_capacity(header.len());
+        header.write(&mut bytes);
+        bytes.extend_from_slice(&matrix);
+        bytes.extend_from_slice(&metadata);
+
+        Ok(bytes)
+    }
+
+    /// Decodes a binary graph, or a JSON graph written by older versions.
+    pub fn from_slice(bytes: &[u8]) -> crate::Result<Self> {
+        if GraphHeader::is_binary(bytes) {
+            return Ok(GraphView::parse(bytes)?.into_graph());
+        }
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    pub fn set_ann_index(&mut self, ann_index: Option<HnswIndex>) {
+        self.ann_index = ann_index;
+    }
+
+    pub fn set_document_key(&mut self, document_key: Option<String>) {
+        self.document_key = document_key;
+    }
+
+    pub fn set_quantized(&mut self, quantized: Option<QuantizedEmbeddings>) {
+        self.quantized = quantized;
+    }
+
+    pub fn set_lexical_index(&mut self, lexical_index: Option<Bm25Index>) {
+        self.lexical_index = lexical_index;
+    }
+
+    /// BM25 search over node data. Empty when the graph has no usable
+    /// lexical index.
+    pub fn search_nodes_lexical(
+        &self,
+        query: &str,
+        k: usize,
+        filter: Option<&SearchFilter>,
+    ) -> Vec<(f32, &Node)> {
+        let filter = |node: &Node| filter.is_none_or(|filter| filter.matches_node(node));
+        match &self.lexical_index {
+            Some(lexical_index) => match lexical_index.search(self, query, k, filter) {
+                Some(result) => result,
+                None => {
+                    warn!("lexical index of graph {} is out of date", self.id);
+                    vec![]
+                }
+            },
+            None => vec![],
+        }
+    }
+
+    /// BM25 score of any text against `query` using this graph's term
+    /// statistics. Zero when the graph has no lexical index.
+    pub fn lexical_score(&self, query: &str, text: &str) -> f32 {
+        self.lexical_index
+            .as_ref()
+            .map_or(0.0, |lexical_index| lexical_index.score_text(query, text))
+    }
+
+    /// Similarity of `query_embedding` to the document-level embedding. `None`
+    /// for graphs indexed without one.
+    pub fn document_score(&self, query_embedding: &[f32]) -> Option<f32> {
+        self.embeddings
+            .as_ref()
+            .filter(|embeddings| embeddings.len() == query_embedding.len())
+            .map(|embeddings| math::cosine_similarity_slice(query_embedding, embeddings))
+    }
+
+    /// Replaces the nodes of `results` sharing a parent section by that section
+    /// when there are at least `min_siblings` of them. The section takes the
+    /// best score and rank of its nodes.
+    pub fn expand_to_sections<'g>(
+        &'g self,
+        results: Vec<(f32, &'g Node)>,
+        min_siblings: usize,
+    ) -> Vec<(f32, &'g Node)> {
+        let mut siblings: HashMap<&str, usize> = HashMap::new();
+        for (_, node) in &results {
+            if let Some(parent_id) = node.parent_id() {
+                *siblings.entry(parent_id.as_str()).or_default() += 1;
+            }
+        }
+
+        let mut expanded = HashSet::new();
+        let mut result = vec![];
+        for (score, node) in results {
+            let section = node
+                .parent_id()
+                .as_ref()
+                .filter(|parent_id| siblings[parent_id.as_str()] >= min_siblings.max(1))
+                .and_then(|parent_id| self.section_map.get(parent_id));
+            match section {
+                Some(section) => {
+                    if expanded.insert(section.id()) {
+                        result.push((score, section));
+                    }
+                }
+                None => result.push((score, node)),
+            }
+        }
+        result
+    }
+
+    /// Edges leaving `node`. Empty for graphs indexed without edges.
+    pub fn edges(&self, node: &Node) -> &[Edge] {
+        self.edge_map
+            .get(node.id())
+            .map_or(&[], |edges| edges.as_slice())
+    }
+
+    /// Adds the nodes of this graph reached from the best `hits` nodes of
+    /// `results` along edges of the expanded kinds, right after the node they
+    /// are reached from. Reached nodes take the score of that node scaled by
+    /// the edge weight. Cross-document edges are left to the caller.
+    pub fn expand_along_edges<'g>(
+        &'g self,
+        results: Vec<(f32, &'g Node)>,
+        expansion: &ExpansionOptions,
+    ) -> Vec<(f32, &'g Node)> {
+        let mut seen = results
+            .iter()
+            .map(|(_, node)| node.id().as_str())
+            .collect::<HashSet<&str>>();
+        let mut expanded = vec![];
+        for (rank, (score, node)) in results.into_iter().enumerate() {
+            expanded.push((score, node));
+            if rank >= *expansion.hits() {
+                continue;
+            }
+            let targets = self
+                .edges(node)
+                .iter()
+                .filter(|edge| edge.document().is_none())
+                .filter(|edge| expansion.kinds().contains(edge.kind()))
+                .filter_map(|edge| {
+                    self.node_map
+                        .get(edge.target())
+                        .map(|target| (score * edge.weight(), target))
+                })
+                .filter(|(_, target)| seen.insert(target.id().as_str()))
+                .take(*expansion.edges_per_hit())
+                .collect::<Vec<(f32, &Node)>>();
+            expanded.extend(targets);
+        }
+        expanded
+    }
+
+    /// Sub-chunks of `node` in text order, or of every chunk of a section
+    /// node. Empty for graphs indexed without sub-chunks.
+    pub fn sub_chunks(&self, node: &Node) -> Vec<&Node> {
+        node.sub_chunk_ids()
+            .iter()
+            .filter_map(|id| self.sub_chunk_map.get(id))
+            .collect()
+    }
+
+    /// Top `k` nodes by cosine similarity among the nodes matching `filter`.
+    /// Graphs with quantized embeddings are always searched through them,
+    /// whatever the `strategy`. Filtering nodes falls back to an exact search
+    /// of graphs without quantized embeddings.
+    pub fn search_nodes(
+        &self,
+        query_embedding: &[f32],
+        k: usize,
+        strategy: &SearchStrategy,
+        filter: Option<&SearchFilter>,
+    ) -> Vec<(f32, &Node)> {
+        let filter = filter.filter(|filter| filter.filters_nodes());
+        let matches = |node: &Node| filter.is_none_or(|filter| filter.matches_node(node));
+        if let Some(quantized) = &self.quantized {
+            match quantized.search(self, query_embedding, k, DEFAULT_RESCORE_FACTOR, matches) {
+                Some(result) => return result,
+                None => warn!("quantized embeddings of graph {} are out of date", self.id),
+            }
+        }
+        if let (SearchStrategy::Approximate { ef_search }, Some(ann_index), None) =
+            (strategy, &self.ann_index, filter)
+        {
+            match ann_index.search(self, query_embedding, k, *ef_search) {
+                Some(result) => return result,
+                None => warn!("ann index of graph {} is out of date", self.id),
+            }
+        }
+
+        let mut result = self
+            .node_map
+            .par_iter()
+            .filter(|(_, node)| matches(node))
+            .map(|(_, node)| {
+                let similarity = math::cosine_similarity_slice(query_embedding, node.embeddings());
+                (similarity, node)
+            })
+            .collect::<Vec<(f32, &Node)>>();
+        result.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        result.truncate(k);
+        result
+    }
+}
+
+fn strip_embeddings(node_map: &HashMap<NodeId, Node>) -> HashMap<NodeId, Node> {
+    node_map
+        .iter()
+        .map(|(id, node)| {
+            let node = Node {
+                embeddings: vec![],
+                ..node.clone()
+            };
+            (id.to_string(), node)
+        })
+        .collect()
+}
+
+fn check_dimension(node: &Node, dimension: usize) -> Result<(), GraphFormatError> {
+    if node.embeddings().len() != dimension {
+        return Err(GraphFormatError::InconsistentDimension {
+            node: node.id().to_string(),
+            expected: dimension,
+            actual: node.embeddings().len(),
+        });
+    }
+    Ok(())
 }
 
 impl Default for Graph {
@@ -40,12 +458,21 @@ impl Default for Graph {
         Self {
             id: common::generate_id(),
             node_map: HashMap::new(),
+            section_map: HashMap::new(),
+            sub_chunk_map: HashMap::new(),
+            edge_map: HashMap::new(),
             title: "No name".to_string(),
+            metadata: DocumentMetadata::default(),
             hash: None,
             index_model: None,
             embeddings: None,
+            min_hash: vec![],
             reference: None,
             reference_link: None,
+            lexical_index: None,
+            ann_index: None,
+            quantized: None,
+            document_key: None,
         }
     }
 }
@@ -65,3 +492,302 @@ impl ToString for Graph {
         serde_json::to_string(self).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graph::{EdgeKind, GRAPH_VERSION},
+        quantization::Quantization,
+    };
+
+    fn node(id: &str, embeddings: Vec<f32>) -> Node {
+        Node::builder()
+            .id(id.to_string())
+            .data(format!("text of {id}"))
+            .rank_id(id.to_string())
+            .hash(id.to_string())
+            .embeddings(embeddings)
+            .build()
+    }
+
+    fn nodes(nodes: Vec<Node>) -> HashMap<NodeId, Node> {
+        nodes
+            .into_iter()
+            .map(|node| (node.id().to_string(), node))
+            .collect()
+    }
+
+    // Graph with two chunks of a section, and a chunk without section.
+    fn graph() -> Graph {
+        let mut a = node("a", vec![1.0, 0.0, -1.0]);
+        a.parent_id = Some("s".to_string());
+        let mut b = node("b", vec![0.5, -0.5, 1.0]);
+        b.parent_id = Some("s".to_string());
+        Graph::builder()
+            .id("graph".to_string())
+            .title("title".to_string())
+            .node_map(nodes(vec![b, a, node("c", vec![0.0, 1.0, 0.0])]))
+            .section_map(nodes(vec![node("s", vec![0.75, -0.25, 0.0])]))
+            .index_model(Some("model".to_string()))
+            .build()
+    }
+
+    #[test]
+    fn binary_graph_round_trip() {
+        let graph = graph();
+        let bytes = graph.to_bytes().unwrap();
+
+        let view = GraphView::parse(&bytes).unwrap();
+        assert_eq!(*view.header().version(), GRAPH_VERSION);
+        assert_eq!(*view.header().dimension(), 3);
+        assert_eq!(*view.header().node_count(), 4);
+        assert_eq!(view.node_ids(), ["a", "b", "c", "s"]);
+        assert_eq!(view.embedding(1), [0.5, -0.5, 1.0]);
+
+        let loaded = Graph::from_slice(&bytes).unwrap();
+        assert_eq!(loaded.title(), "title");
+        assert_eq!(loaded.index_model().as_deref(), Some("model"));
+        for node in graph.node_map().values() {
+            let loaded_node = &loaded.node_map()[node.id()];
+            assert_eq!(loaded_node.embeddings(), node.embeddings());
+            assert_eq!(loaded_node.data(), node.data());
+        }
+        assert_eq!(loaded.section_map()["s"].embeddings(), &[0.75, -0.25, 0.0]);
+        let saved = loaded.to_bytes().unwrap();
+        assert_eq!(
+            GraphView::parse(&saved).unwrap().embeddings(),
+            view.embeddings()
+        );
+    }
+
+    #[test]
+    fn quantized_graph_round_trip() {
+        let mut graph = graph();
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Int8);
+        graph.set_quantized(Some(quantized));
+        let bytes = graph.to_bytes().unwrap();
+
+        let header = GraphHeader::read(&bytes).unwrap();
+        assert_eq!(*header.quantization(), Some(Quantization::Int8));
+        assert_eq!(header.embeddings_range().len(), 4 * 3);
+
+        let loaded = Graph::from_slice(&bytes).unwrap();
+        let nodes = graph
+            .node_map()
+            .values()
+            .chain(graph.section_map().values());
+        for node in nodes {
+            let loaded_node = loaded
+                .node_map()
+                .get(node.id())
+                .or(loaded.section_map().get(node.id()))
+                .unwrap();
+            assert!(loaded_node.embeddings().is_empty());
+            let embeddings = loaded.embedding(loaded_node);
+            assert_eq!(embeddings.len(), 3);
+            for (a, b) in embeddings.iter().zip(node.embeddings()) {
+                assert!((a - b).abs() < 0.01);
+            }
+        }
+        // Loaded quantized graphs keep their codes when saved again.
+        let saved = loaded.to_bytes().unwrap();
+        let view = GraphView::parse(&saved).unwrap();
+        assert_eq!(view.header(), &header);
+        assert_eq!(view.matrix(), &bytes[header.embeddings_range()]);
+    }
+
+    // `graph` in the version 3 layout, without section rows and with the
+    // section embeddings in the metadata.
+    fn version_3_bytes(graph: &Graph) -> Vec<u8> {
+        let bytes = graph.to_bytes().unwrap();
+        let view = GraphView::parse(&bytes).unwrap();
+        let rows = view.node_ids().len() - graph.section_map().len();
+        let mut stored = view.graph().clone();
+        stored.section_map = graph.section_map().clone();
+        let metadata = serde_json::to_vec(&GraphMetadata {
+            graph: stored,
+            node_ids: view.node_ids()[..rows].to_vec(),
+        })
+        .unwrap();
+        let header = view.header();
+        let mut v3 = vec![];
+        GraphHeader::new(
+            *header.quantization(),
+            *header.dimension(),
+            rows as u32,
+            metadata.len() as u64,
+        )
+        .write(&mut v3);
+        v3[4..6].copy_from_slice(&3u16.to_le_bytes());
+        v3.extend_from_slice(&view.matrix()[..rows * header.row_len()]);
+        v3.extend_from_slice(&metadata);
+        v3
+    }
+
+    #[test]
+    fn version_3_graphs_are_loaded_and_saved_with_section_rows() {
+        let mut quantized = graph_with_sub_chunks();
+        let embeddings = QuantizedEmbeddings::build(&quantized, Quantization::Int8);
+        quantized.set_quantized(Some(embeddings));
+
+        for graph in [graph_with_sub_chunks(), quantized] {
+            let bytes = version_3_bytes(&graph);
+            assert_eq!(*GraphHeader::read(&bytes).unwrap().version(), 3);
+
+            let loaded = Graph::from_slice(&bytes).unwrap();
+            let section = &loaded.section_map()["s"];
+            assert_eq!(loaded.embedding(section).as_ref(), [0.75, -0.25, 0.0]);
+            let sub_chunk = &loaded.sub_chunk_map()["a::1"];
+            assert_eq!(loaded.embedding(sub_chunk).len(), 3);
+
+            // Saved again in the current layout, with the same rows as a
+            // graph written by this version.
+            let saved = loaded.to_bytes().unwrap();
+            let view = GraphView::parse(&saved).unwrap();
+            assert_eq!(*view.header().version(), GRAPH_VERSION);
+            assert_eq!(view.node_ids(), ["a", "b", "c", "a::0", "a::1", "s"]);
+            let current = graph.to_bytes().unwrap();
+            assert_eq!(view.matrix(), GraphView::parse(&current).unwrap().matrix());
+        }
+    }
+
+    // Graph whose chunk "a" is split into two sub-chunks.
+    fn graph_with_sub_chunks() -> Graph {
+        let mut graph = graph();
+        let sub_chunks = vec![
+            node("a::0", vec![1.0, 0.0, 0.0]),
+            node("a::1", vec![0.0, 0.0, -1.0]),
+        ];
+        graph.node_map_mut().get_mut("a").unwrap().sub_chunk_ids =
+            vec!["a::0".to_string(), "a::1".to_string()];
+        *graph.sub_chunk_map_mut() = nodes(sub_chunks);
+        graph
+    }
+
+    #[test]
+    fn sub_chunks_round_trip() {
+        let graph = graph_with_sub_chunks();
+        let bytes = graph.to_bytes().unwrap();
+
+        let view = GraphView::parse(&bytes).unwrap();
+        assert_eq!(*view.header().node_count(), 6);
+        assert_eq!(view.node_ids(), ["a", "b", "c", "a::0", "a::1", "s"]);
+
+        let loaded = Graph::from_slice(&bytes).unwrap();
+        let sub_chunks = loaded.sub_chunks(&loaded.node_map()["a"]);
+        let ids = sub_chunks
+            .iter()
+            .map(|node| node.id().as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, ["a::0", "a::1"]);
+        assert_eq!(sub_chunks[1].embeddings(), &[0.0, 0.0, -1.0]);
+        assert!(loaded.sub_chunks(&loaded.node_map()["c"]).is_empty());
+    }
+
+    #[test]
+    fn quantized_sub_chunks_are_dequantized() {
+        let mut graph = graph_with_sub_chunks();
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Int8);
+        graph.set_quantized(Some(quantized));
+        let bytes = graph.to_bytes().unwrap();
+        assert_eq!(*GraphHeader::read(&bytes).unwrap().node_count(), 6);
+
+        let loaded = Graph::from_slice(&bytes).unwrap();
+        assert_eq!(loaded.quantized().as_ref().unwrap().node_ids().len(), 3);
+        for (id, sub_chunk) in graph.sub_chunk_map() {
+            let loaded_sub_chunk = &loaded.sub_chunk_map()[id];
+            assert!(loaded_sub_chunk.embeddings().is_empty());
+            let embeddings = loaded.embedding(loaded_sub_chunk);
+            assert_eq!(embeddings.len(), 3);
+            for (a, b) in embeddings.iter().zip(sub_chunk.embeddings()) {
+                assert!((a - b).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn from_slice_reads_json_graphs() {
+        let graph = graph();
+        let loaded = Graph::from_slice(graph.to_string().as_bytes()).unwrap();
+        assert_eq!(loaded.node_map()["a"].embeddings(), &[1.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn to_bytes_rejects_inconsistent_dimensions() {
+        let mut graph = graph();
+        graph
+            .node_map_mut()
+            .insert("d".to_string(), node("d", vec![1.0]));
+        assert!(matches!(
+            graph.to_bytes(),
+            Err(GraphFormatError::InconsistentDimension { .. })
+        ));
+    }
+    #[test]
+    fn expand_to_sections_replaces_sibling_chunks() {
+        let graph = graph();
+        let results = vec![
+            (0.9, &graph.node_map()["a"]),
+            (0.8, &graph.node_map()["c"]),
+            (0.7, &graph.node_map()["b"]),
+        ];
+
+        let expanded = graph.expand_to_sections(results.clone(), 2);
+        let ids = expanded
+            .iter()
+            .map(|(score, node)| (*score, node.id().as_str()))
+            .collect::<Vec<(f32, &str)>>();
+        assert_eq!(ids, [(0.9, "s"), (0.8, "c")]);
+
+        let expanded = graph.expand_to_sections(results, 3);
+        assert_eq!(expanded.len(), 3);
+    }
+    #[test]
+    fn document_score_needs_a_matching_embedding() {
+        let mut graph = graph();
+        assert!(graph.document_score(&[1.0, 0.0, 0.0]).is_none());
+
+        graph.embeddings = Some(vec![1.0, 0.0, 0.0]);
+        assert_eq!(graph.document_score(&[2.0, 0.0, 0.0]), Some(1.0));
+        assert!(graph.document_score(&[1.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn expand_along_edges_follows_expanded_kinds() {
+        let mut graph = graph();
+        let edge = |kind, target: &str| {
+            Edge::builder()
+                .kind(kind)
+                .target(target.to_string())
+                .build()
+        };
+        graph.edge_map_mut().insert(
+            "a".to_string(),
+            vec![
+                edge(EdgeKind::Semantic, "c"),
+                edge(EdgeKind::Next, "b"),
+                Edge::builder()
+                    .kind(EdgeKind::CrossDocument)
+                    .target("x".to_string())
+                    .document(Some("other".to_string()))
+                    .build(),
+            ],
+        );
+        graph
+            .edge_map_mut()
+            .insert("c".to_string(), vec![edge(EdgeKind::Previous, "b")]);
+        let results = vec![(0.9, &graph.node_map()["a"]), (0.5, &graph.node_map()["c"])];
+
+        let expansion = ExpansionOptions::default();
+        let expanded = graph.expand_along_edges(results.clone(), &expansion);
+        let ids = expanded
+            .iter()
+            .map(|(_, node)| node.id().as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, ["a", "b", "c"]);
+
+        let expansion = ExpansionOptions::builder().hits(0).build();
+        assert_eq!(graph.expand_along_edges(results, &expansion).len(), 2);
+    }
+}
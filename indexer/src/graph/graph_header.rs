@@ -0,0 +1,197 @@
+use std::ops::Range;
+
+use derive_getters::Getters;
+
+use super::GraphFormatError;
+use crate::quantization::Quantization;
+
+pub const GRAPH_MAGIC: &[u8; 4] = b"IDXG";
+pub const GRAPH_VERSION: u16 = 4;
+pub const GRAPH_HEADER_LEN: usize = 24;
+
+/// Fixed size header of a binary graph.
+///
+/// All integers are little-endian:
+///
+/// | offset | size | field                         |
+/// |--------|------|-------------------------------|
+/// | 0      | 4    | magic `IDXG`                  |
+/// | 4      | 2    | version                       |
+/// | 6      | 2    | encoding                      |
+/// | 8      | 4    | dimension                     |
+/// | 12     | 4    | row count                     |
+/// | 16     | 8    | metadata length               |
+///
+/// The header is followed by the embedding matrix, one row per node,
+/// sub-chunk and section in the order of the metadata node ids, then by the
+/// JSON metadata. Rows are `dimension` f32 values for encoding 0, or int8 (1)
+/// and binary (2) quantized vectors. Quantized graphs store node rows before
+/// sub-chunk and section rows. Version 1 graphs are always f32, versions
+/// before 3 have no sub-chunk rows, and versions before 4 have no section
+/// rows and keep full precision section embeddings in the metadata, which
+/// are loaded from there and written as rows when the graph is saved again.
+#[derive(Clone, Debug, PartialEq, Eq, Getters)]
+pub struct GraphHeader {
+    version: u16,
+    quantization: Option<Quantization>,
+    dimension: u32,
+    /// Rows of the embedding matrix, sub-chunks and sections included.
+    node_count: u32,
+    metadata_len: u64,
+}
+
+impl GraphHeader {
+    pub fn new(
+        quantization: Option<Quantization>,
+        dimension: u32,
+        node_count: u32,
+        metadata_len: u64,
+    ) -> Self {
+        Self {
+            version: GRAPH_VERSION,
+            quantization,
+            dimension,
+            node_count,
+            metadata_len,
+        }
+    }
+
+    pub fn is_binary(bytes: &[u8]) -> bool {
+        bytes.starts_with(GRAPH_MAGIC)
+    }
+
+    pub fn read(bytes: &[u8]) -> Result<Self, GraphFormatError> {
+        let header = Self::read_prefix(bytes)?;
+        if bytes.len() < header.len() {
+            return Err(GraphFormatError::Truncated {
+                expected: header.len(),
+                actual: bytes.len(),
+            });
+        }
+        Ok(header)
+    }
+
+    /// Header at the start of `bytes`, without checking that the rest of the
+    /// graph follows, e.g. when a graph file is read in parts.
+    pub fn read_prefix(bytes: &[u8]) -> Result<Self, GraphFormatError> {
+        if !Self::is_binary(bytes) {
+            return Err(GraphFormatError::InvalidMagic);
+        }
+        if bytes.len() < GRAPH_HEADER_LEN {
+            return Err(GraphFormatError::Truncated {
+                expected: GRAPH_HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if !(1..=GRAPH_VERSION).contains(&version) {
+            return Err(GraphFormatError::UnsupportedVersion(version));
+        }
+        let quantization = match u16::from_le_bytes([bytes[6], bytes[7]]) {
+            0 => None,
+            1 => Some(Quantization::Int8),
+            2 => Some(Quantization::Binary),
+            encoding => return Err(GraphFormatError::UnknownEncoding(encoding)),
+        };
+        Ok(Self {
+            version,
+            quantization,
+            dimension: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            node_count: u32::from_le_bytes(bytes[12..16].try_into().unwrap()),
+            metadata_len: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        })
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(GRAPH_MAGIC);
+        out.extend_from_slice(&self.version.to_le_bytes());
+        let encoding: u16 = match self.quantization {
+            None => 0,
+            Some(Quantization::Int8) => 1,
+            Some(Quantization::Binary) => 2,
+        };
+        out.extend_from_slice(&encoding.to_le_bytes());
+        out.extend_from_slice(&self.dimension.to_le_bytes());
+        out.extend_from_slice(&self.node_count.to_le_bytes());
+        out.extend_from_slice(&self.metadata_len.to_le_bytes());
+    }
+
+    /// Bytes used by one row of the embedding matrix.
+    pub fn row_len(&self) -> usize {
+        let dimension = self.dimension as usize;
+        match self.quantization {
+            Some(quantization) => quantization.row_len(dimension),
+            None => dimension * 4,
+        }
+    }
+
+    /// Byte range of the embedding matrix.
+    pub fn embeddings_range(&self) -> Range<usize> {
+        let len = self.node_count as usize * self.row_len();
+        GRAPH_HEADER_LEN..GRAPH_HEADER_LEN + len
+    }
+
+    /// Byte range of the JSON metadata.
+    pub fn metadata_range(&self) -> Range<usize> {
+        let start = self.embeddings_range().end;
+        start..start + self.metadata_len as usize
+    }
+
+    /// Total length of the binary graph.
+    pub fn len(&self) -> usize {
+        self.metadata_range().end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trip() {
+        let header = GraphHeader::new(Some(Quantization::Binary), 384, 12, 1024);
+        let mut bytes = vec![];
+        header.write(&mut bytes);
+        assert_eq!(bytes.len(), GRAPH_HEADER_LEN);
+        assert_eq!(GraphHeader::read_prefix(&bytes).unwrap(), header);
+
+        bytes.resize(header.len(), 0);
+        assert_eq!(GraphHeader::read(&bytes).unwrap(), header);
+        assert_eq!(*header.version(), GRAPH_VERSION);
+    }
+
+    #[test]
+    fn read_rejects_invalid_headers() {
+        let header = GraphHeader::new(None, 4, 2, 10);
+        let mut bytes = vec![];
+        header.write(&mut bytes);
+
+        assert!(matches!(
+            GraphHeader::read(b"{\"id\":1}"),
+            Err(GraphFormatError::InvalidMagic)
+        ));
+        assert!(matches!(
+            GraphHeader::read(&bytes[..10]),
+            Err(GraphFormatError::Truncated { .. })
+        ));
+        assert!(matches!(
+            GraphHeader::read(&bytes),
+            Err(GraphFormatError::Truncated { .. })
+        ));
+
+        bytes.resize(header.len(), 0);
+        bytes[4..6].copy_from_slice(&(GRAPH_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            GraphHeader::read(&bytes),
+            Err(GraphFormatError::UnsupportedVersion(_))
+        ));
+        let mut encoding = bytes.clone();
+        encoding[4..6].copy_from_slice(&GRAPH_VERSION.to_le_bytes());
+        encoding[6..8].copy_from_slice(&3u16.to_le_bytes());
+        assert!(matches!(
+            GraphHeader::read(&encoding),
+            Err(GraphFormatError::UnknownEncoding(3))
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Graph, GraphFormatError, GraphHeader, NodeId};
+
+/// Metadata section of a binary graph: the graph without node embeddings,
+/// and the node, sub-chunk or section id of every row of the embedding
+/// matrix. Written from any `G` serialized like a `Graph`.
+#[derive(Serialize, Deserialize)]
+pub(super) struct GraphMetadata<G = Graph> {
+    pub(super) graph: G,
+    pub(super) node_ids: Vec<NodeId>,
+}
+
+/// Read-only view of a binary graph, to inspect its header, rows and metadata
+/// without building a `Graph`.
+///
+/// The f32 embedding matrix borrows `bytes` on little-endian targets when they
+/// are 4-byte aligned, and is copied otherwise. Searches run on the `Graph`
+/// returned by `into_graph`, which copies every row into its nodes.
+pub struct GraphView<'a> {
+    header: GraphHeader,
+    matrix: &'a [u8],
+    embeddings: Cow<'a, [f32]>,
+    metadata: GraphMetadata,
+}
+
+impl<'a> GraphView<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, GraphFormatError> {
+        let header = GraphHeader::read(bytes)?;
+        let matrix = &bytes[header.embeddings_range()];
+        let embeddings = match header.quantization() {
+            Some(_) => Cow::Borrowed(&[][..]),
+            None => read_embeddings(matrix),
+        };
+        let metadata: GraphMetadata = serde_json::from_slice(&bytes[header.metadata_range()])?;
+
+        Ok(Self {
+            header,
+            matrix,
+            embeddings,
+            metadata,
+        })
+    }
+
+    pub fn header(&self) -> &GraphHeader {
+        &self.header
+    }
+
+    /// Graph without node embeddings.
+    pub fn graph(&self) -> &Graph {
+        &self.metadata.graph
+    }
+
+    pub fn node_ids(&self) -> &[NodeId] {
+        &self.metadata.node_ids
+    }
+
+    /// Raw embedding matrix, one `row_len` row per node, sub-chunk and
+    /// section.
+    pub fn matrix(&self) -> &[u8] {
+        self.matrix
+    }
+
+    /// Row-major `node_count * dimension` embedding matrix, `node_count`
+    /// counting sub-chunks and sections. Empty for quantized graphs.
+    pub fn embeddings(&self) -> &[f32] {
+        &self.embeddings
+    }
+
+    pub fn embedding(&self, row: usize) -> &[f32] {
+        let dimension = *self.header.dimension() as usize;
+        &self.embeddings[row * dimension..(row + 1) * dimension]
+    }
+
+    /// Owned graph, with the rows copied into its nodes, sub-chunks and
+    /// sections. Those of quantized graphs have no embeddings, their codes
+    /// are copied instead and dequantized a row at a time by
+    /// `Graph::embedding`.
+    pub fn into_graph(self) -> Graph {
+        let dimension = *self.header.dimension() as usize;
+        let row_len = self.header.row_len();
+        let GraphMetadata {
+            mut graph,
+            node_ids,
+        } = self.metadata;
+        if let Some(quantized) = graph.quantized_mut() {
+            let node_rows = quantized.node_ids().len();
+            quantized.set_codes(self.matrix[..node_rows * row_len].to_vec());
+            quantized.set_child_codes(
+                &node_ids[node_rows..],
+                self.matrix[node_rows * row_len..].to_vec(),
+            );
+            return graph;
+        }
+        for (row, id) in node_ids.iter().enumerate() {
+            let embeddings = &self.embeddings[row * dimension..(row + 1) * dimension];
+            if let Some(node) = graph.node_map_mut().get_mut(id) {
+                node.embeddings = embeddings.to_vec();
+            } else if let Some(sub_chunk) = graph.sub_chunk_map_mut().get_mut(id) {
+                sub_chunk.embeddings = embeddings.to_vec();
+            } else if let Some(section) = graph.section_map_mut().get_mut(id) {
+                section.embeddings = embeddings.to_vec();
+            }
+        }
+        graph
+    }
+}
+
+fn read_embeddings(bytes: &[u8]) -> Cow<'_, [f32]> {
+    if cfg!(target_endian = "little") {
+        if let Ok(embeddings) = bytemuck::try_cast_slice::<u8, f32>(bytes) {
+            return Cow::Borrowed(embeddings);
+        }
+    }
+    let embeddings = bytes
+        .chunks_exact(4)
+        .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+        .collect();
+    Cow::Owned(embeddings)
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+};
+
+use serde::Serialize;
+
+use super::{graph_view::GraphMetadata, GraphFormatError, GraphHeader, Node, NodeId};
+use crate::quantization::QuantizedEmbeddings;
+
+/// Writes a binary graph whose nodes are added one at a time. Embeddings are
+/// spooled to a file as they are added, and the metadata is written to another
+/// one, so neither is ever all in memory. Rows follow the order the nodes were
+/// added in.
+pub struct GraphWriter {
+    matrix: BufWriter<File>,
+    metadata: File,
+    dimension: Option<usize>,
+    node_ids: Vec<NodeId>,
+}
+
+impl GraphWriter {
+    /// Writer spooling the embedding matrix to `matrix` and the metadata to
+    /// `metadata`, files opened for reading and writing.
+    pub fn new(matrix: File, metadata: File) -> Self {
+        Self {
+            matrix: BufWriter::new(matrix),
+            metadata,
+            dimension: None,
+            node_ids: vec![],
+        }
+    }
+
+    /// Dimension of the embeddings, once a node was added.
+    pub fn dimension(&self) -> Option<usize> {
+        self.dimension
+    }
+
+    /// Ids of the added nodes, by row.
+    pub fn node_ids(&self) -> &[NodeId] {
+        &self.node_ids
+    }
+
+    /// Appends the embeddings of `node`, a node, sub-chunk or section, as the
+    /// next row of the matrix, and removes them from `node`.
+    pub fn push(&mut self, node: &mut Node) -> Result<(), GraphFormatError> {
+        let dimension = *self.dimension.get_or_insert(node.embeddings().len());
+        if node.embeddings().len() != dimension {
+            return Err(GraphFormatError::InconsistentDimension {
+                node: node.id().to_string(),
+                expected: dimension,
+                actual: node.embeddings().len(),
+            });
+        }
+        for value in node.embeddings() {
+            self.matrix.write_all(&value.to_le_bytes())?;
+        }
+        node.embeddings = vec![];
+        self.node_ids.push(node.id().to_string());
+        Ok(())
+    }
+
+    /// Reads the spooled rows back in order, calling `f` with the node id and
+    /// embeddings of each. Rows can still be added afterwards.
+    pub fn scan_rows(
+        &mut self,
+        mut f: impl FnMut(&NodeId, Vec<f32>) -> Result<(), GraphFormatError>,
+    ) -> Result<(), GraphFormatError> {
+        let dimension = self.dimension.unwrap_or_default();
+        let node_ids = &self.node_ids;
+        scan_rows(
+            &mut self.matrix,
+            dimension,
+            node_ids.len(),
+            |row, embeddings| f(&node_ids[row], embeddings),
+        )
+    }
+
+    /// Writes the header, the spooled matrix and `graph` as metadata to `out`.
+    /// `graph` is serialized like a `Graph` holding the added nodes, without
+    /// their embeddings.
+    ///
+    /// With `quantized`, built from the rows of the nodes of the graph, rows
+    /// are stored quantized: its node rows first, then the other rows encoded
+    /// with its calibration in the order they were added. `graph` then holds
+    /// `quantized` without its codes.
+    pub fn finish(
+        mut self,
+        graph: &impl Serialize,
+        quantized: Option<&QuantizedEmbeddings>,
+        mut out: impl Write,
+    ) -> Result<(), GraphFormatError> {
+        let node_ids = match quantized {
+            Some(quantized) => {
+                let node_rows = quantized.node_ids().iter().collect::<HashSet<&NodeId>>();
+                quantized
+                    .node_ids()
+                    .iter()
+                    .chain(self.node_ids.iter().filter(|id| !node_rows.contains(id)))
+                    .cloned()
+                    .collect()
+            }
+            None => std::mem::take(&mut self.node_ids),
+        };
+        let row_count = node_ids.len();
+        self.metadata.seek(SeekFrom::Start(0))?;
+        let mut metadata = BufWriter::new(&self.metadata);
+        serde_json::to_writer(&mut metadata, &GraphMetadata { graph, node_ids })?;
+        metadata.flush()?;
+        drop(metadata);
+        let metadata_len = self.metadata.stream_position()?;
+
+        let header = GraphHeader::new(
+            quantized.map(|quantized| quantized.quantization()),
+            self.dimension.unwrap_or_default() as u32,
+            row_count as u32,
+            metadata_len,
+        );
+        let mut bytes = vec![];
+        header.write(&mut bytes);
+        out.write_all(&bytes)?;
+        match quantized {
+            Some(quantized) => {
+                out.write_all(quantized.codes())?;
+                let node_rows = quantized.node_ids().iter().collect::<HashSet<&NodeId>>();
+                let dimension = self.dimension.unwrap_or_default();
+                let mut codes = vec![];
+                let rows = self.node_ids.len();
+                scan_rows(&mut self.matrix, dimension, rows, |row, embeddings| {
+                    if !node_rows.contains(&self.node_ids[row]) {
+                        codes.clear();
+                        quantized.calibration().encode(&embeddings, &mut codes);
+                        out.write_all(&codes)?;
+                    }
+                    Ok(())
+                })?;
+            }
+            None => {
+                let mut matrix = self.matrix.into_inner().map_err(|err| err.into_error())?;
+                matrix.seek(SeekFrom::Start(0))?;
+                io::copy(&mut matrix, &mut out)?;
+            }
+        }
+        self.metadata.seek(SeekFrom::Start(0))?;
+        io::copy(&mut (&self.metadata).take(metadata_len), &mut out)?;
+        out.flush()?;
+
+        Ok(())
+    }
+}
+
+// Reads the first `rows` rows of `dimension` f32 values of `matrix` in order,
+// then moves back to its end for further writes.
+fn scan_rows(
+    matrix: &mut BufWriter<File>,
+    dimension: usize,
+    rows: usize,
+    mut f: impl FnMut(usize, Vec<f32>) -> Result<(), GraphFormatError>,
+) -> Result<(), GraphFormatError> {
+    matrix.flush()?;
+    let file = matrix.get_mut();
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(&*file);
+    let mut bytes = vec![0; dimension * 4];
+    for row in 0..rows {
+        reader.read_exact(&mut bytes)?;
+        let embeddings = bytes
+            .chunks_exact(4)
+            .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+            .collect();
+        f(row, embeddings)?;
+    }
+    drop(reader);
+    file.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+
+    use super::*;
+    use crate::{
+        fixtures::random_graph,
+        graph::{Graph, GraphView},
+        streaming::TempPath,
+    };
+
+    // File at `path` opened for reading and writing.
+    fn spool(path: &TempPath) -> File {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path.path())
+            .unwrap()
+    }
+
+    #[test]
+    fn written_graph_matches_to_bytes() {
+        let graph = random_graph(5, 4, 42);
+        let (matrix, metadata) = (TempPath::new("matrix"), TempPath::new("json"));
+        let mut writer = GraphWriter::new(spool(&matrix), spool(&metadata));
+        let mut stripped = graph.clone();
+        let mut node_ids = stripped.node_map().keys().cloned().collect::<Vec<NodeId>>();
+        node_ids.sort();
+        for id in &node_ids {
+            let node = stripped.node_map_mut().get_mut(id).unwrap();
+            writer.push(node).unwrap();
+            assert!(node.embeddings().is_empty());
+        }
+        let mut bytes = vec![];
+        writer.finish(&stripped, None, &mut bytes).unwrap();
+
+        let expected = graph.to_bytes().unwrap();
+        let view = GraphView::parse(&bytes).unwrap();
+        let expected_view = GraphView::parse(&expected).unwrap();
+        assert_eq!(view.header(), expected_view.header());
+        assert_eq!(view.node_ids(), expected_view.node_ids());
+        assert_eq!(view.matrix(), expected_view.matrix());
+        let loaded = Graph::from_slice(&bytes).unwrap();
+        for node in graph.node_map().values() {
+            assert_eq!(loaded.node_map()[node.id()].embeddings(), node.embeddings());
+        }
+    }
+
+    #[test]
+    fn push_rejects_inconsistent_dimensions() {
+        let (matrix, metadata) = (TempPath::new("matrix"), TempPath::new("json"));
+        let mut writer = GraphWriter::new(spool(&matrix), spool(&metadata));
+        let mut graph = random_graph(1, 4, 42);
+        let node = graph.node_map_mut().values_mut().next().unwrap();
+        writer.push(node).unwrap();
+        node.embeddings = vec![1.0];
+        assert!(matches!(
+            writer.push(node),
+            Err(GraphFormatError::InconsistentDimension { expected: 4, .. })
+        ));
+    }
+}
@@ -1,5 +1,21 @@
+mod document_metadata;
+mod edge;
+mod edge_kind;
+mod format_error;
 mod graph;
+mod graph_header;
+mod graph_view;
+mod graph_writer;
 mod node;
+mod source_span;
 
+pub use document_metadata::DocumentMetadata;
+pub use edge::Edge;
+pub use edge_kind::EdgeKind;
+pub use format_error::GraphFormatError;
 pub use graph::Graph;
+pub use graph_header::{GraphHeader, GRAPH_HEADER_LEN, GRAPH_MAGIC, GRAPH_VERSION};
+pub use graph_view::GraphView;
+pub use graph_writer::GraphWriter;
 pub use node::{Node, NodeId};
+pub use source_span::SourceSpan;
@@ -2,6 +2,8 @@ use derive_getters::Getters;
 use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
 
+use super::SourceSpan;
+
 pub type NodeId = String;
 pub type RankId = String;
 
@@ -14,6 +16,24 @@ pub struct Node {
     pub rank_id: RankId,
     pub hash: String,
     pub embeddings: Vec<f32>,
+    /// Titles of the headings the node is under, outermost first.
+    #[serde(default)]
+    #[builder(default)]
+    pub heading_path: Vec<String>,
+    /// Sub-chunks of the node in text order, searched in place of the node
+    /// once it is retrieved.
+    #[serde(default)]
+    #[builder(default)]
+    pub sub_chunk_ids: Vec<NodeId>,
+    /// SimHash of the text, to find near duplicate nodes across documents.
+    #[serde(default)]
+    #[builder(default = None)]
+    pub simhash: Option<u64>,
+    /// Region of the parsed document the node was built from, covering only
+    /// the lines of the node.
+    #[serde(default)]
+    #[builder(default = None)]
+    pub span: Option<SourceSpan>,
     #[builder(default = None)]
     pub reference: Option<String>, // reference document
 }
@@ -27,6 +47,10 @@ impl Default for Node {
             rank_id: Default::default(),
             hash: Default::default(),
             embeddings: Default::default(),
+            heading_path: Default::default(),
+            sub_chunk_ids: Default::default(),
+            simhash: Default::default(),
+            span: Default::default(),
             reference: Default::default(),
         }
     }
@@ -0,0 +1,78 @@
+use derive_getters::Getters;
+use document::document::Position;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Region of the parsed document, `data.json`, a node was built from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct SourceSpan {
+    /// Offset of the first character, as in `Position`.
+    start: usize,
+    /// Offset after the last character.
+    end: usize,
+    /// 1-indexed pages, empty for formats without pages.
+    #[serde(default)]
+    #[builder(default)]
+    pages: Vec<usize>,
+}
+
+impl SourceSpan {
+    /// Smallest span covering `positions`, `None` when none is known.
+    pub fn covering<'p>(positions: impl IntoIterator<Item = &'p Option<Position>>) -> Option<Self> {
+        let positions = positions.into_iter().flatten().collect::<Vec<&Position>>();
+        let start = positions.iter().map(|x| *x.start().offset()).min()?;
+        let end = positions.iter().map(|x| *x.end().offset()).max()?;
+        let pages = positions
+            .iter()
+            .flat_map(|x| [*x.start().page(), *x.end().page()])
+            .flatten()
+            .collect::<Vec<usize>>();
+        let pages = match (pages.iter().min(), pages.iter().max()) {
+            (Some(first), Some(last)) => (*first..=*last).collect(),
+            _ => vec![],
+        };
+        Some(Self { start, end, pages })
+    }
+
+    /// Cuts the span to its first `len` characters. Pages are kept, as the
+    /// page of the new end is unknown.
+    pub fn truncate(&mut self, len: usize) {
+        self.end = self.end.min(self.start + len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use document::document::Point;
+
+    use super::*;
+
+    fn position(start: usize, end: usize, page: Option<usize>) -> Option<Position> {
+        let point = |offset| {
+            Point::builder()
+                .line(1)
+                .column(0)
+                .offset(offset)
+                .page(page)
+                .build()
+        };
+        Some(
+            Position::builder()
+                .start(point(start))
+                .end(point(end))
+                .build(),
+        )
+    }
+
+    #[test]
+    fn span_covers_every_position() {
+        let positions = [position(40, 60, Some(3)), None, position(10, 20, Some(1))];
+        let span = SourceSpan::covering(&positions).unwrap();
+        assert_eq!((*span.start(), *span.end()), (10, 60));
+        assert_eq!(span.pages(), &[1, 2, 3]);
+
+        let span = SourceSpan::covering(&[position(5, 8, None)]).unwrap();
+        assert!(span.pages().is_empty());
+        assert!(SourceSpan::covering(&[None, None]).is_none());
+    }
+}
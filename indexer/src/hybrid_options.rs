@@ -0,0 +1,22 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Weights used to fuse BM25 and vector rankings with reciprocal rank fusion.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct HybridOptions {
+    #[builder(default = 1.0)]
+    vector_weight: f32,
+    #[builder(default = 1.0)]
+    lexical_weight: f32,
+    /// Larger values flatten the difference between top and lower ranks.
+    #[builder(default = 60.0)]
+    rank_constant: f32,
+}
+
+impl Default for HybridOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
@@ -1,59 +1,891 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, iter};
 
 use anyhow::Result;
 use common::generate_id;
+use document::{chunking::OverlappedChunker, document::Position};
 use rayon::prelude::*;
+use tracing::{info, warn};
 
 use crate::{
-    embedding::EmbeddingModel,
-    graph::{Graph, Node},
+    ann::{HnswIndex, HnswParams, SearchStrategy},
+    embedding::{EmbeddingModel, EmbeddingStats},
+    fingerprint,
+    graph::{Edge, EdgeKind, Graph, Node, NodeId, SourceSpan},
+    lexical::Bm25Index,
+    math,
+    quantization::{Quantization, QuantizationReport, QuantizedEmbeddings, DEFAULT_RESCORE_FACTOR},
     IndexingMeta,
+    IndexingReport,
+    IndexingSection,
 };
 
+// Nodes used as queries to measure the recall of quantized embeddings.
+pub(crate) const RECALL_QUERIES: usize = 100;
+pub(crate) const RECALL_K: usize = 10;
+// Token size of the sub-chunks returned as search contexts.
+const SUB_CHUNK_SIZE: usize = 100;
+// Closest chunks linked by semantic and cross-document edges, and the
+// similarity they need.
+const SIMILAR_NODES: usize = 3;
+const SIMILARITY_THRESHOLD: f32 = 0.75;
+// Chunk pairs compared when linking to a graph without ANN index, above which
+// the graph is not linked.
+const MAX_EXACT_LINK_COMPARISONS: usize = 10_000_000;
+
+// Embeddings of earlier nodes by content hash and occurrence of the hash, the
+// n-th node with a text reusing the embeddings of the n-th earlier one.
+pub(crate) type PreviousEmbeddings<'g> = HashMap<(&'g str, usize), Cow<'g, [f32]>>;
+
+// Embeddings to reuse for a text by its hash and the occurrence of the hash,
+// `None` when there are none.
+pub(crate) type EmbeddingLookup<'p> = dyn Fn(&str, usize) -> Result<Option<Vec<f32>>> + Sync + 'p;
+
 #[derive(Debug)]
 pub struct Indexer {
     model: EmbeddingModel,
+    quantization: Option<Quantization>,
 }
 
 impl Indexer {
     pub fn new(model: EmbeddingModel) -> Result<Self> {
-        Ok(Self { model })
+        Ok(Self {
+            model,
+            quantization: None,
+        })
+    }
+
+    pub fn model(&self) -> &EmbeddingModel {
+        &self.model
+    }
+
+    /// Quantizes the embeddings of indexed graphs. Quantized graphs are
+    /// searched through their quantized embeddings instead of an HNSW index.
+    pub fn set_quantization(&mut self, quantization: Option<Quantization>) {
+        self.quantization = quantization;
+    }
+
+    pub fn quantization(&self) -> Option<Quantization> {
+        self.quantization
     }
 
     pub async fn index(&self, texts: Vec<&str>, meta: IndexingMeta) -> Result<Graph> {
+        let section = IndexingSection::builder()
+            .chunks(texts.iter().map(|text| text.to_string()).collect())
+            .build();
+        let (graph, _) = self.reindex(vec![section], meta, None).await?;
+        Ok(graph)
+    }
+
+    /// Indexes the chunks of `sections`, reusing the embeddings of `previous`
+    /// for chunks and sub-chunks whose text is still in the document, the
+    /// n-th chunk with a text reusing the n-th one of `previous`. Embeddings
+    /// are only reused when `previous` was indexed with the same model. They
+    /// are dequantized from int8 graphs, and never reused from binary ones.
+    ///
+    /// Chunks of titled sections get a parent section node, whose parent is
+    /// the graph. Section and graph embeddings are the centroids of their
+    /// chunk embeddings.
+    pub async fn reindex(
+        &self,
+        sections: Vec<IndexingSection>,
+        meta: IndexingMeta,
+        previous: Option<&Graph>,
+    ) -> Result<(Graph, IndexingReport)> {
         let reference = meta.id();
-        let mut node_map = HashMap::new();
+        let model_id = self.model.model_id();
+        let stats = self.model.stats();
+        let graph_id = previous.map_or_else(generate_id, |graph| graph.id().to_string());
+        let section_ids = sections
+            .iter()
+            .map(|section| {
+                section
+                    .title()
+                    .as_ref()
+                    .map(|_| common::generate_id_with_data(section.text()))
+            })
+            .collect::<Vec<Option<NodeId>>>();
+        let parent_ids = sections
+            .iter()
+            .zip(&section_ids)
+            .flat_map(|(section, id)| iter::repeat_n(id, section.chunks().len()))
+            .collect::<Vec<&Option<NodeId>>>();
+        let heading_paths = sections
+            .iter()
+            .flat_map(|section| iter::repeat_n(section.heading_path(), section.chunks().len()))
+            .collect::<Vec<&Vec<String>>>();
+        let positions = sections
+            .iter()
+            .flat_map(|section| {
+                (0..section.chunks().len()).map(|index| {
+                    section
+                        .chunk_positions()
+                        .get(index)
+                        .map_or(&[][..], |positions| positions.as_slice())
+                })
+            })
+            .collect::<Vec<&[Option<Position>]>>();
+        let texts = sections
+            .iter()
+            .flat_map(|section| section.chunks().iter().map(|chunk| chunk.as_str()))
+            .collect::<Vec<&str>>();
+        let reusable = previous
+            .filter(|graph| graph.index_model().as_deref() == Some(model_id.as_str()))
+            .filter(|graph| {
+                let binary = graph
+                    .quantized()
+                    .as_ref()
+                    .is_some_and(|quantized| quantized.quantization() == Quantization::Binary);
+                if binary {
+                    info!(
+                        "embeddings of binary quantized graph {} not reused",
+                        graph.id()
+                    );
+                }
+                !binary
+            });
+        let (previous_chunks, previous_sub_chunks) = match reusable {
+            Some(graph) => (
+                previous_embeddings(graph, graph.node_map().values()),
+                previous_embeddings(graph, graph.sub_chunk_map().values()),
+            ),
+            None => Default::default(),
+        };
 
-        let nodes = texts
-            .par_iter()
-            .enumerate()
-            .map(|(index, text)| {
-                let result = self.model.run(text).unwrap();
-                let node = Node::builder()
-                    .id(common::generate_id_with_data(text))
-                    .hash(common::hash(text.as_bytes()))
-                    .rank_id(format!("{}::{}", reference.to_string(), index))
-                    .reference(Some(reference.to_string()))
-                    .data(text.to_string())
-                    .embeddings(result)
-                    .build();
-                node
+        let hashes = texts
+            .iter()
+            .map(|text| common::hash(text.as_bytes()))
+            .collect::<Vec<String>>();
+        let keys = hash_occurrences(hashes.iter().map(|hash| hash.as_str()));
+        let mut embeddings = self
+            .embed_reusing(&texts, &hashes, &|hash, occurrence| {
+                Ok(previous_chunks
+                    .get(&(hash, occurrence))
+                    .map(|embeddings| embeddings.to_vec()))
             })
-            .collect::<Vec<Node>>();
+            .await?
+            .into_iter();
 
-        for node in nodes {
+        let mut node_map = HashMap::new();
+        let mut chunk_ids = vec![];
+        let mut chunk_positions = HashMap::new();
+        let mut kept = 0;
+        for (index, (text, hash)) in texts.iter().zip(&hashes).enumerate() {
+            let mut node = chunk_node(
+                text,
+                hash.clone(),
+                reference,
+                index,
+                parent_ids[index].clone(),
+                heading_paths[index],
+            );
+            node.embeddings = embeddings.next().unwrap_or_default();
+            node.span = SourceSpan::covering(positions[index]);
+            if node_map.contains_key(node.id()) {
+                continue;
+            }
+            if previous_chunks.contains_key(&keys[index]) {
+                kept += 1;
+            }
+            chunk_positions.insert(node.id().to_string(), positions[index]);
+            chunk_ids.push(node.id().to_string());
             node_map.insert(node.id().to_string(), node);
         }
 
-        let graph = Graph::builder()
-            .id(generate_id())
+        let sub_chunk_map = self
+            .index_sub_chunks(
+                &mut node_map,
+                &chunk_ids,
+                &chunk_positions,
+                &|hash, occurrence| {
+                    Ok(previous_sub_chunks
+                        .get(&(hash, occurrence))
+                        .map(|embeddings| embeddings.to_vec()))
+                },
+            )
+            .await?;
+
+        let added = node_map.len() - kept;
+        let removed = previous
+            .map_or(0, |graph| graph.node_count())
+            .saturating_sub(kept);
+
+        let mut section_embeddings: HashMap<&str, Vec<&[f32]>> = HashMap::new();
+        for node in node_map.values() {
+            if let Some(parent_id) = node.parent_id() {
+                section_embeddings
+                    .entry(parent_id.as_str())
+                    .or_default()
+                    .push(node.embeddings());
+            }
+        }
+        let mut section_map = HashMap::new();
+        for (index, (section, id)) in sections.iter().zip(&section_ids).enumerate() {
+            let Some(embeddings) = id
+                .as_ref()
+                .and_then(|id| section_embeddings.get(id.as_str()))
+            else {
+                continue;
+            };
+            let sub_chunk_ids = chunk_ids
+                .iter()
+                .map(|chunk_id| &node_map[chunk_id])
+                .filter(|node| node.parent_id() == id)
+                .flat_map(|node| node.sub_chunk_ids().clone())
+                .collect();
+            let node = Node::builder()
+                .id(id.clone().unwrap())
+                .hash(common::hash(section.text().as_bytes()))
+                .rank_id(format!("{}::section::{}", reference, index))
+                .reference(Some(reference.to_string()))
+                .data(section.text().to_string())
+                .embeddings(math::centroid(embeddings))
+                .parent_id(Some(graph_id.clone()))
+                .heading_path(section.heading_path().clone())
+                .sub_chunk_ids(sub_chunk_ids)
+                .span(section.span().clone())
+                .build();
+            section_map.insert(node.id().to_string(), node);
+        }
+
+        let structural_edges = structural_edges(&chunk_ids, &node_map);
+        let chunk_embeddings = node_map
+            .values()
+            .map(|node| node.embeddings().as_slice())
+            .collect::<Vec<&[f32]>>();
+        let document_embedding = math::centroid(&chunk_embeddings);
+
+        let mut graph = Graph::builder()
+            .id(graph_id)
             .title(meta.title().to_string())
+            .metadata(meta.metadata().clone())
             .node_map(node_map)
-            .index_model(Some(self.model.get_model_id()))
+            .section_map(section_map)
+            .sub_chunk_map(sub_chunk_map)
+            .index_model(Some(model_id))
+            .embeddings(Some(document_embedding))
+            .min_hash(fingerprint::min_hash(texts.iter().copied()))
             .reference(Some(reference.to_string()))
             .reference_link(Some(meta.external_link().to_string()))
             .build();
+        let lexical_index = Bm25Index::build(&graph);
+        graph.set_lexical_index(Some(lexical_index));
+        let quantization_report = match self.quantization {
+            Some(quantization) => {
+                let (quantized, quantization_report) = quantize(&graph, quantization);
+                graph.set_quantized(Some(quantized));
+                Some(quantization_report)
+            }
+            None => {
+                let ann_index = HnswIndex::build(&graph, HnswParams::default());
+                graph.set_ann_index(Some(ann_index));
+                None
+            }
+        };
+        let semantic_edges = semantic_edges(&graph);
+        let edge_map = graph.edge_map_mut();
+        *edge_map = structural_edges;
+        for (id, edge) in semantic_edges {
+            edge_map.entry(id).or_default().push(edge);
+        }
 
-        Ok(graph)
+        let report = IndexingReport::builder()
+            .added(added)
+            .kept(kept)
+            .removed(removed)
+            .quantization(quantization_report)
+            .embedding(self.embedding_stats(stats))
+            .build();
+
+        Ok((graph, report))
+    }
+
+    /// Adds cross-document edges from the chunks of `graph` to the closest
+    /// chunks of `other`, a graph indexed earlier with the same model. Each
+    /// chunk keeps its best cross-document edges over all linked graphs.
+    /// Edges are only stored in `graph`, and are followed both ways.
+    ///
+    /// Closest chunks are found through the ANN index of `other`. Graphs
+    /// without one are scanned, unless the scan would compare more than
+    /// `MAX_EXACT_LINK_COMPARISONS` chunk pairs, in which case they are not
+    /// linked.
+    pub fn link_document(&self, graph: &mut Graph, other: &Graph) {
+        if other.id() == graph.id() || other.index_model() != graph.index_model() {
+            return;
+        }
+        let Some(document) = other.reference().clone() else {
+            return;
+        };
+        let comparisons = graph.node_count() * other.node_count();
+        if other.ann_index().is_none() && comparisons > MAX_EXACT_LINK_COMPARISONS {
+            warn!(
+                "skip linking to graph {}: no ann index for {} comparisons",
+                other.id(),
+                comparisons
+            );
+            return;
+        }
+        let edges = graph
+            .node_map()
+            .par_iter()
+            .map(|(id, node)| {
+                let edges = similar_nodes(other, &graph.embedding(node))
+                    .into_iter()
+                    .map(|(similarity, target)| {
+                        Edge::builder()
+                            .kind(EdgeKind::CrossDocument)
+                            .target(target.id().to_string())
+                            .document(Some(document.clone()))
+                            .weight(similarity)
+                            .build()
+                    })
+                    .collect::<Vec<Edge>>();
+                (id.to_string(), edges)
+            })
+            .collect::<Vec<(NodeId, Vec<Edge>)>>();
+
+        for (id, edges) in edges {
+            if edges.is_empty() {
+                continue;
+            }
+            let node_edges = graph.edge_map_mut().entry(id).or_default();
+            let (mut cross_document, mut kept): (Vec<Edge>, Vec<Edge>) = node_edges
+                .drain(..)
+                .chain(edges)
+                .partition(|edge| *edge.kind() == EdgeKind::CrossDocument);
+            cross_document.sort_by(|a, b| b.weight().partial_cmp(a.weight()).unwrap());
+            cross_document.truncate(SIMILAR_NODES);
+            kept.extend(cross_document);
+            *node_edges = kept;
+        }
+    }
+
+    // Embeddings of `texts`, taken from `previous` when it has some for their
+    // hash and its occurrence.
+    pub(crate) async fn embed_reusing(
+        &self,
+        texts: &[&str],
+        hashes: &[String],
+        previous: &EmbeddingLookup<'_>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let reused = hash_occurrences(hashes.iter().map(|hash| hash.as_str()))
+            .into_iter()
+            .map(|(hash, occurrence)| previous(hash, occurrence))
+            .collect::<Result<Vec<Option<Vec<f32>>>>>()?;
+        let changed_texts = texts
+            .iter()
+            .zip(&reused)
+            .filter(|(_, embeddings)| embeddings.is_none())
+            .map(|(text, _)| *text)
+            .collect::<Vec<&str>>();
+        let mut new_embeddings = self.model.run_batches(&changed_texts).await?.into_iter();
+
+        reused
+            .into_iter()
+            .enumerate()
+            .map(|(index, embeddings)| match embeddings {
+                Some(embeddings) => Ok(embeddings),
+                None => new_embeddings
+                    .next()
+                    .ok_or(anyhow::anyhow!("missing embedding for text {}", index)),
+            })
+            .collect()
+    }
+
+    // Counts of the model since `earlier`, its snapshot at the start of a run.
+    pub(crate) fn embedding_stats(
+        &self,
+        earlier: Option<EmbeddingStats>,
+    ) -> Option<EmbeddingStats> {
+        self.model
+            .stats()
+            .zip(earlier)
+            .map(|(stats, earlier)| stats.since(&earlier))
+    }
+
+    // Splits every chunk into sub-chunks of `SUB_CHUNK_SIZE` tokens, the
+    // contexts returned by searches, and links them to their chunk. The span
+    // of a sub-chunk covers the positions of its lines in `chunk_positions`.
+    pub(crate) async fn index_sub_chunks(
+        &self,
+        node_map: &mut HashMap<NodeId, Node>,
+        chunk_ids: &[NodeId],
+        chunk_positions: &HashMap<NodeId, &[Option<Position>]>,
+        previous: &EmbeddingLookup<'_>,
+    ) -> Result<HashMap<NodeId, Node>> {
+        let chunker = OverlappedChunker::with_size(SUB_CHUNK_SIZE);
+        let mut sub_chunks = vec![];
+        for chunk_id in chunk_ids {
+            let lines = node_map[chunk_id]
+                .data()
+                .split("\n")
+                .map(|line| line.to_string())
+                .collect::<Vec<String>>();
+            let positions = chunk_positions.get(chunk_id).copied().unwrap_or_default();
+            for range in chunker.line_ranges_with_remainder(&lines) {
+                // The text of a chunk ends with a newline, so its last line
+                // is empty and has no position.
+                let span = SourceSpan::covering(
+                    &positions[range.start.min(positions.len())..range.end.min(positions.len())],
+                );
+                let text = lines[range]
+                    .iter()
+                    .map(|line| format!("{}\n", line))
+                    .collect::<String>();
+                sub_chunks.push((chunk_id, text, span));
+            }
+        }
+
+        let texts = sub_chunks
+            .iter()
+            .map(|(_, text, _)| text.as_str())
+            .collect::<Vec<&str>>();
+        let hashes = texts
+            .iter()
+            .map(|text| common::hash(text.as_bytes()))
+            .collect::<Vec<String>>();
+        let embeddings = self.embed_reusing(&texts, &hashes, previous).await?;
+
+        let mut sub_chunk_map = HashMap::new();
+        for (((chunk_id, text, span), hash), embeddings) in
+            sub_chunks.into_iter().zip(hashes).zip(embeddings)
+        {
+            let chunk = node_map.get_mut(chunk_id).unwrap();
+            let index = chunk.sub_chunk_ids.len();
+            let node = Node::builder()
+                .id(format!("{}::{}", chunk_id, index))
+                .hash(hash)
+                .rank_id(format!("{}::{}", chunk.rank_id(), index))
+                .reference(chunk.reference().clone())
+                .simhash(Some(fingerprint::simhash(&text)))
+                .data(text)
+                .embeddings(embeddings)
+                .parent_id(Some(chunk_id.to_string()))
+                .heading_path(chunk.heading_path().clone())
+                .span(span)
+                .build();
+            chunk.sub_chunk_ids.push(node.id().to_string());
+            sub_chunk_map.insert(node.id().to_string(), node);
+        }
+
+        Ok(sub_chunk_map)
+    }
+}
+
+// Quantizes the node embeddings of `graph`, and measures the recall of the
+// searches through them against the full precision embeddings.
+pub(crate) fn quantize(
+    graph: &Graph,
+    quantization: Quantization,
+) -> (QuantizedEmbeddings, QuantizationReport) {
+    let quantized = QuantizedEmbeddings::build(graph, quantization);
+    let queries = graph
+        .node_map()
+        .values()
+        .take(RECALL_QUERIES)
+        .map(|node| node.embeddings().as_slice())
+        .collect::<Vec<&[f32]>>();
+    let report = quantized.evaluate(graph, &queries, RECALL_K, DEFAULT_RESCORE_FACTOR);
+    (quantized, report)
+}
+
+// `(hash, n)` for the n-th occurrence of every hash of `hashes`.
+pub(crate) fn hash_occurrences<'h>(
+    hashes: impl IntoIterator<Item = &'h str>,
+) -> Vec<(&'h str, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    hashes
+        .into_iter()
+        .map(|hash| {
+            let count = counts.entry(hash).or_default();
+            *count += 1;
+            (hash, *count - 1)
+        })
+        .collect()
+}
+
+// Embeddings of `nodes` of `graph` to reuse, dequantized when the graph was
+// loaded with quantized embeddings. Nodes without embeddings are left out.
+fn previous_embeddings<'g>(
+    graph: &'g Graph,
+    nodes: impl Iterator<Item = &'g Node>,
+) -> PreviousEmbeddings<'g> {
+    let mut nodes = nodes.collect::<Vec<&Node>>();
+    nodes.sort_by(|a, b| a.id().cmp(b.id()));
+    let keys = hash_occurrences(nodes.iter().map(|node| node.hash().as_str()));
+    keys.into_iter()
+        .zip(nodes)
+        .map(|(key, node)| (key, graph.embedding(node)))
+        .filter(|(_, embeddings)| !embeddings.is_empty())
+        .collect()
+}
+
+// Chunk node `index` of a document, without embeddings.
+pub(crate) fn chunk_node(
+    text: &str,
+    hash: String,
+    reference: &str,
+    index: usize,
+    parent_id: Option<NodeId>,
+    heading_path: &[String],
+) -> Node {
+    Node::builder()
+        .id(common::generate_id_with_data(text))
+        .hash(hash)
+        .rank_id(format!("{}::{}", reference, index))
+        .reference(Some(reference.to_string()))
+        .data(text.to_string())
+        .embeddings(vec![])
+        .parent_id(parent_id)
+        .heading_path(heading_path.to_vec())
+        .simhash(Some(fingerprint::simhash(text)))
+        .build()
+}
+
+// Sequential edges between consecutive chunks, and section edges between the
+// chunks of a section.
+pub(crate) fn structural_edges(
+    chunk_ids: &[NodeId],
+    node_map: &HashMap<NodeId, Node>,
+) -> HashMap<NodeId, Vec<Edge>> {
+    let mut edge_map: HashMap<NodeId, Vec<Edge>> = HashMap::new();
+    for pair in chunk_ids.windows(2) {
+        let next = Edge::builder()
+            .kind(EdgeKind::Next)
+            .target(pair[1].clone())
+            .build();
+        edge_map.entry(pair[0].clone()).or_default().push(next);
+        let previous = Edge::builder()
+            .kind(EdgeKind::Previous)
+            .target(pair[0].clone())
+            .build();
+        edge_map.entry(pair[1].clone()).or_default().push(previous);
+    }
+
+    let mut sections: HashMap<&str, Vec<&NodeId>> = HashMap::new();
+    for id in chunk_ids {
+        if let Some(parent_id) = node_map[id].parent_id() {
+            sections.entry(parent_id.as_str()).or_default().push(id);
+        }
+    }
+    for ids in sections.values() {
+        for source in ids {
+            let edges = edge_map.entry(source.to_string()).or_default();
+            for target in ids.iter().filter(|target| *target != source) {
+                let edge = Edge::builder()
+                    .kind(EdgeKind::Section)
+                    .target(target.to_string())
+                    .build();
+                edges.push(edge);
+            }
+        }
+    }
+    edge_map
+}
+
+// Semantic edges from every chunk of `graph` to its closest other chunks.
+pub(crate) fn semantic_edges(graph: &Graph) -> Vec<(NodeId, Edge)> {
+    graph
+        .node_map()
+        .par_iter()
+        .flat_map(|(id, node)| {
+            similar_nodes(graph, &graph.embedding(node))
+                .into_iter()
+                .filter(|(_, target)| target.id() != id)
+                .take(SIMILAR_NODES)
+                .map(|(similarity, target)| {
+                    let edge = Edge::builder()
+                        .kind(EdgeKind::Semantic)
+                        .target(target.id().to_string())
+                        .weight(similarity)
+                        .build();
+                    (id.to_string(), edge)
+                })
+                .collect::<Vec<(NodeId, Edge)>>()
+        })
+        .collect()
+}
+
+// Chunks of `graph` closest to the node with `embeddings`, above the
+// similarity threshold, with room for the node itself.
+fn similar_nodes<'g>(graph: &'g Graph, embeddings: &[f32]) -> Vec<(f32, &'g Node)> {
+    let strategy = SearchStrategy::default();
+    graph
+        .search_nodes(embeddings, SIMILAR_NODES + 1, &strategy, None)
+        .into_iter()
+        .filter(|(similarity, _)| *similarity >= SIMILARITY_THRESHOLD)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{fixtures::HashProvider, math};
+
+    fn meta() -> IndexingMeta {
+        IndexingMeta::builder()
+            .id("document".to_string())
+            .title("document".to_string())
+            .external_link(String::new())
+            .build()
+    }
+
+    // One untitled section of `texts`.
+    fn chunks(texts: &[&str]) -> Vec<IndexingSection> {
+        vec![IndexingSection::builder()
+            .chunks(texts.iter().map(|text| text.to_string()).collect())
+            .build()]
+    }
+
+    fn indexer(provider: &Arc<HashProvider>) -> Indexer {
+        Indexer::new(EmbeddingModel::Custom(provider.clone())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reindex_embeds_only_changed_chunks() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let indexer = indexer(&provider);
+        let previous = indexer
+            .index(vec!["alpha", "beta", "gamma"], meta())
+            .await
+            .unwrap();
+        // Every chunk is embedded with its single sub-chunk.
+        assert_eq!(provider.embedded(), 6);
+
+        let (graph, report) = indexer
+            .reindex(
+                chunks(&["alpha", "gamma", "delta"]),
+                meta(),
+                Some(&previous),
+            )
+            .await
+            .unwrap();
+        assert_eq!(provider.embedded(), 8);
+        assert_eq!(
+            (*report.added(), *report.kept(), *report.removed()),
+            (1, 2, 1)
+        );
+        assert_eq!(graph.id(), previous.id());
+        for node in graph.node_map().values() {
+            if let Some(old) = previous.node_map().get(node.id()) {
+                assert_eq!(node.embeddings(), old.embeddings());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reindex_with_another_model_embeds_all_chunks() {
+        let other = Arc::new(HashProvider::new("fake::b", 8));
+        let previous = indexer(&other)
+            .index(vec!["alpha", "beta"], meta())
+            .await
+            .unwrap();
+
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let (_, report) = indexer(&provider)
+            .reindex(chunks(&["alpha", "beta"]), meta(), Some(&previous))
+            .await
+            .unwrap();
+        assert_eq!(provider.embedded(), 4);
+        assert_eq!((*report.added(), *report.kept()), (2, 0));
+    }
+    #[tokio::test]
+    async fn chunks_are_split_into_sub_chunks() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let text = (0..150)
+            .map(|index| format!("word{index}"))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let graph = indexer(&provider)
+            .index(vec![text.as_str(), "short"], meta())
+            .await
+            .unwrap();
+
+        assert_eq!(graph.node_map().len(), 2);
+        for node in graph.node_map().values() {
+            let sub_chunks = graph.sub_chunks(node);
+            assert!(!sub_chunks.is_empty());
+            for sub_chunk in sub_chunks {
+                assert_eq!(sub_chunk.parent_id().as_deref(), Some(node.id().as_str()));
+                assert_eq!(sub_chunk.embeddings().len(), 8);
+                assert!(node
+                    .data()
+                    .contains(sub_chunk.data().split_whitespace().next().unwrap()));
+            }
+        }
+        assert_eq!(
+            provider.embedded(),
+            graph.node_map().len() + graph.sub_chunk_map().len()
+        );
+    }
+
+    #[tokio::test]
+    async fn titled_sections_get_parent_nodes() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let sections = vec![
+            IndexingSection::builder()
+                .chunks(vec!["preface".to_string()])
+                .build(),
+            IndexingSection::builder()
+                .title(Some("Pumps".to_string()))
+                .depth(1)
+                .text("Pumps\nalpha beta".to_string())
+                .chunks(vec!["alpha".to_string(), "beta".to_string()])
+                .build(),
+        ];
+        let (graph, _) = indexer(&provider)
+            .reindex(sections, meta(), None)
+            .await
+            .unwrap();
+
+        assert_eq!(graph.section_map().len(), 1);
+        let section = graph.section_map().values().next().unwrap();
+        assert_eq!(section.data(), "Pumps\nalpha beta");
+        assert_eq!(section.parent_id().as_deref(), Some(graph.id().as_str()));
+        let children = graph
+            .node_map()
+            .values()
+            .filter(|node| node.parent_id().as_deref() == Some(section.id().as_str()))
+            .collect::<Vec<&Node>>();
+        assert_eq!(children.len(), 2);
+
+        let norm = section
+            .embeddings()
+            .iter()
+            .map(|value| value * value)
+            .sum::<f32>()
+            .sqrt();
+        assert!((norm - 1.0).abs() < 1e-5);
+        let mean = (0..8)
+            .map(|d| {
+                children
+                    .iter()
+                    .map(|node| node.embeddings()[d])
+                    .sum::<f32>()
+            })
+            .collect::<Vec<f32>>();
+        assert!(math::cosine_similarity_slice(&mean, section.embeddings()) > 0.9999);
+    }
+
+    // Chunks of `graph` in text order.
+    fn chunks_in_order(graph: &Graph) -> Vec<&Node> {
+        let mut chunks = graph.node_map().values().collect::<Vec<&Node>>();
+        chunks.sort_by_key(|node| node.rank_id().to_string());
+        chunks
+    }
+
+    #[tokio::test]
+    async fn consecutive_chunks_are_linked() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let graph = indexer(&provider)
+            .index(vec!["alpha", "beta", "gamma"], meta())
+            .await
+            .unwrap();
+        let chunks = chunks_in_order(&graph);
+
+        let targets = |node: &Node, kind: EdgeKind| {
+            graph
+                .edges(node)
+                .iter()
+                .filter(|edge| *edge.kind() == kind)
+                .map(|edge| edge.target().as_str())
+                .collect::<Vec<&str>>()
+        };
+        assert_eq!(targets(chunks[0], EdgeKind::Next), [chunks[1].id()]);
+        assert!(targets(chunks[0], EdgeKind::Previous).is_empty());
+        assert_eq!(targets(chunks[1], EdgeKind::Next), [chunks[2].id()]);
+        assert_eq!(targets(chunks[1], EdgeKind::Previous), [chunks[0].id()]);
+        assert!(targets(chunks[2], EdgeKind::Next).is_empty());
+    }
+
+    #[tokio::test]
+    async fn link_document_adds_cross_document_edges() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let indexer = indexer(&provider);
+        let other_meta = IndexingMeta::builder()
+            .id("other".to_string())
+            .title("other".to_string())
+            .external_link(String::new())
+            .build();
+        let other = indexer
+            .index(vec!["alpha", "beta"], other_meta)
+            .await
+            .unwrap();
+        let mut graph = indexer.index(vec!["alpha", "delta"], meta()).await.unwrap();
+
+        let itself = graph.clone();
+        indexer.link_document(&mut graph, &itself);
+        indexer.link_document(&mut graph, &other);
+        let cross_document = graph
+            .node_map()
+            .values()
+            .flat_map(|node| graph.edges(node))
+            .filter(|edge| *edge.kind() == EdgeKind::CrossDocument)
+            .collect::<Vec<&Edge>>();
+        assert!(!cross_document.is_empty());
+        for edge in cross_document {
+            assert_eq!(edge.document().as_deref(), Some("other"));
+            assert!(other.node_map().contains_key(edge.target()));
+            assert!(*edge.weight() >= SIMILARITY_THRESHOLD);
+        }
+    }
+
+    #[test]
+    fn hash_occurrences_count_repeated_hashes() {
+        let keys = hash_occurrences(["a", "b", "a", "a"]);
+        assert_eq!(keys, [("a", 0), ("b", 0), ("a", 1), ("a", 2)]);
+    }
+
+    #[tokio::test]
+    async fn embed_reusing_matches_hash_occurrences() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let indexer = indexer(&provider);
+        let previous = PreviousEmbeddings::from([(("hash", 0), Cow::Owned(vec![1.0; 8]))]);
+        let hashes = vec!["hash".to_string(), "hash".to_string()];
+
+        let embeddings = indexer
+            .embed_reusing(&["text", "text"], &hashes, &|hash, occurrence| {
+                Ok(previous
+                    .get(&(hash, occurrence))
+                    .map(|embeddings| embeddings.to_vec()))
+            })
+            .await
+            .unwrap();
+        assert_eq!(provider.embedded(), 1);
+        assert_eq!(embeddings[0], vec![1.0; 8]);
+        assert_ne!(embeddings[1], embeddings[0]);
+    }
+
+    // Graph of two chunks indexed with `quantization`, then saved and loaded.
+    async fn stored_graph(provider: &Arc<HashProvider>, quantization: Quantization) -> Graph {
+        let mut indexer = indexer(provider);
+        indexer.set_quantization(Some(quantization));
+        let graph = indexer.index(vec!["alpha", "beta"], meta()).await.unwrap();
+        Graph::from_slice(&graph.to_bytes().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reindex_reuses_int8_but_not_binary_embeddings() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let previous = stored_graph(&provider, Quantization::Int8).await;
+        let embedded = provider.embedded();
+        let (graph, report) = indexer(&provider)
+            .reindex(chunks(&["alpha", "beta"]), meta(), Some(&previous))
+            .await
+            .unwrap();
+        assert_eq!(provider.embedded(), embedded);
+        assert_eq!(*report.kept(), 2);
+        for node in graph.node_map().values() {
+            assert_eq!(node.embeddings().len(), 8);
+        }
+
+        let previous = stored_graph(&provider, Quantization::Binary).await;
+        let embedded = provider.embedded();
+        indexer(&provider)
+            .reindex(chunks(&["alpha", "beta"]), meta(), Some(&previous))
+            .await
+            .unwrap();
+        assert_eq!(provider.embedded(), embedded + 4);
     }
 }
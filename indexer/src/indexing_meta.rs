@@ -1,9 +1,13 @@
 use derive_getters::Getters;
 use typed_builder::TypedBuilder;
 
+use crate::graph::DocumentMetadata;
+
 #[derive(Clone, Debug, TypedBuilder, Getters)]
 pub struct IndexingMeta {
     id: String,
     title: String,
     external_link: String,
+    #[builder(default)]
+    metadata: DocumentMetadata,
 }
@@ -0,0 +1,22 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::{embedding::EmbeddingStats, quantization::QuantizationReport};
+
+/// Node counts of a re-indexed graph compared to the previous one.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct IndexingReport {
+    /// Nodes embedded in this run.
+    added: usize,
+    /// Nodes whose embeddings were reused from the previous graph.
+    kept: usize,
+    /// Nodes of the previous graph that are no longer in the document.
+    removed: usize,
+    /// Recall of the quantized embeddings against full precision ones.
+    #[builder(default = None)]
+    quantization: Option<QuantizationReport>,
+    /// Inputs longer than the max sequence length of the model in this run.
+    #[builder(default = None)]
+    embedding: Option<EmbeddingStats>,
+}
@@ -0,0 +1,38 @@
+use derive_getters::Getters;
+use document::document::Position;
+use typed_builder::TypedBuilder;
+
+use crate::graph::SourceSpan;
+
+/// Chunks of one document section, indexed under a common parent node.
+#[derive(Clone, Debug, TypedBuilder, Getters)]
+pub struct IndexingSection {
+    /// Sections without title, e.g. the content before the first heading,
+    /// get no parent node.
+    #[builder(default = None)]
+    title: Option<String>,
+    #[builder(default = 0)]
+    depth: u8,
+    /// Titles of the enclosing headings, ending with `title`.
+    #[builder(default)]
+    heading_path: Vec<String>,
+    /// Full text of the section, kept in its parent node.
+    #[builder(default)]
+    text: String,
+    chunks: Vec<String>,
+    /// Region of the parsed document the section comes from.
+    #[builder(default = None)]
+    span: Option<SourceSpan>,
+    /// Positions of the lines of every chunk, empty when unknown.
+    #[builder(default)]
+    chunk_positions: Vec<Vec<Option<Position>>>,
+}
+
+impl IndexingSection {
+    /// Region of chunk `index`, `None` when unknown.
+    pub fn chunk_span(&self, index: usize) -> Option<SourceSpan> {
+        self.chunk_positions
+            .get(index)
+            .and_then(SourceSpan::covering)
+    }
+}
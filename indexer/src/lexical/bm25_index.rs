@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::tokenize;
+use crate::graph::{Graph, Node, NodeId};
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+
+/// Okapi BM25 inverted index over the `data` of a graph's nodes.
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct Bm25Index {
+    node_ids: Vec<NodeId>,
+    /// Number of terms in each node.
+    lengths: Vec<u32>,
+    average_length: f32,
+    /// Term to `(node position, term frequency)` pairs.
+    postings: HashMap<String, Vec<(u32, u32)>>,
+}
+
+impl Bm25Index {
+    pub fn build(graph: &Graph) -> Self {
+        let mut node_ids = graph.node_map().keys().cloned().collect::<Vec<NodeId>>();
+        node_ids.sort();
+
+        let mut lengths = vec![];
+        let mut postings: HashMap<String, Vec<(u32, u32)>> = HashMap::new();
+        for (position, node_id) in node_ids.iter().enumerate() {
+            let (length, frequencies) = term_frequencies(graph.node_map()[node_id].data());
+            lengths.push(length);
+            for (term, frequency) in frequencies {
+                postings
+                    .entry(term)
+                    .or_default()
+                    .push((position as u32, frequency));
+            }
+        }
+        let average_length = match lengths.len() {
+            0 => 0.0,
+            count => lengths.iter().sum::<u32>() as f32 / count as f32,
+        };
+
+        Self {
+            node_ids,
+            lengths,
+            average_length,
+            postings,
+        }
+    }
+
+    /// Returns up to `k` nodes of `graph` matching `query` and `filter`, best
+    /// first. Returns `None` when the index was not built from `graph`.
+    pub fn search<'g>(
+        &self,
+        graph: &'g Graph,
+        query: &str,
+        k: usize,
+        filter: impl Fn(&Node) -> bool,
+    ) -> Option<Vec<(f32, &'g Node)>> {
+        let nodes = self.nodes(graph)?;
+
+        let mut scores = vec![0.0; nodes.len()];
+        for term in tokenize(query) {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+            let idf = self.idf(postings.len());
+            for &(position, frequency) in postings {
+                let position = position as usize;
+                scores[position] += idf * self.term_weight(frequency, self.lengths[position]);
+            }
+        }
+
+        let mut result = scores
+            .into_iter()
+            .zip(nodes)
+            .filter(|(score, node)| *score > 0.0 && filter(node))
+            .collect::<Vec<(f32, &Node)>>();
+        result.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        result.truncate(k);
+
+        Some(result)
+    }
+
+    /// Scores any text against `query` using the term statistics of the
+    /// indexed graph, e.g. a sub-chunk of one of its nodes.
+    pub fn score_text(&self, query: &str, text: &str) -> f32 {
+        let (length, frequencies) = term_frequencies(text);
+        tokenize(query)
+            .iter()
+            .filter_map(|term| {
+                let frequency = *frequencies.get(term)?;
+                let document_frequency = self.postings.get(term).map_or(0, |p| p.len());
+                Some(self.idf(document_frequency) * self.term_weight(frequency, length))
+            })
+            .sum()
+    }
+
+    fn idf(&self, document_frequency: usize) -> f32 {
+        let count = self.node_ids.len() as f32;
+        let document_frequency = document_frequency as f32;
+        (1.0 + (count - document_frequency + 0.5) / (document_frequency + 0.5)).ln()
+    }
+
+    fn term_weight(&self, frequency: u32, length: u32) -> f32 {
+        let frequency = frequency as f32;
+        let norm = match self.average_length > 0.0 {
+            true => 1.0 - B + B * length as f32 / self.average_length,
+            false => 1.0,
+        };
+        frequency * (K1 + 1.0) / (frequency + K1 * norm)
+    }
+
+    fn nodes<'g>(&self, graph: &'g Graph) -> Option<Vec<&'g Node>> {
+        if self.node_ids.len() != graph.node_count() {
+            return None;
+        }
+        self.node_ids
+            .iter()
+            .map(|id| graph.node_map().get(id))
+            .collect()
+    }
+}
+
+/// Number of terms of `text` and the frequency of each.
+pub(crate) fn term_frequencies(text: &str) -> (u32, HashMap<String, u32>) {
+    let terms = tokenize(text);
+    let length = terms.len() as u32;
+    let mut frequencies: HashMap<String, u32> = HashMap::new();
+    for term in terms {
+        *frequencies.entry(term).or_default() += 1;
+    }
+    (length, frequencies)
+}
@@ -0,0 +1,93 @@
+use std::{cell::RefCell, collections::BTreeMap, path::Path};
+
+use serde::{
+    ser::{Error, SerializeMap, SerializeStruct},
+    Serialize,
+    Serializer,
+};
+
+use super::bm25_index::term_frequencies;
+use crate::{
+    graph::{Node, NodeId},
+    streaming::PostingSpool,
+    Result,
+};
+
+/// Builds the BM25 index of a streamed graph a batch of nodes at a time. The
+/// postings are spooled to a file and merged when the writer is serialized,
+/// like the `Bm25Index` it stands for. It can only be serialized once.
+pub(crate) struct Bm25Writer {
+    node_ids: Vec<NodeId>,
+    lengths: Vec<u32>,
+    postings: RefCell<Option<PostingSpool>>,
+}
+
+impl Bm25Writer {
+    /// Writer spooling the postings to `path`.
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            node_ids: vec![],
+            lengths: vec![],
+            postings: RefCell::new(Some(PostingSpool::create(path)?)),
+        })
+    }
+
+    /// Indexes the next `nodes`, in order.
+    pub(crate) fn push<'n>(&mut self, nodes: impl IntoIterator<Item = &'n Node>) -> Result<()> {
+        let mut postings: BTreeMap<String, Vec<(u32, u32)>> = BTreeMap::new();
+        for node in nodes {
+            let position = self.node_ids.len() as u32;
+            let (length, frequencies) = term_frequencies(node.data());
+            self.node_ids.push(node.id().to_string());
+            self.lengths.push(length);
+            for (term, frequency) in frequencies {
+                postings
+                    .entry(term)
+                    .or_default()
+                    .push((position, frequency));
+            }
+        }
+        self.postings
+            .get_mut()
+            .as_mut()
+            .ok_or(anyhow::anyhow!("bm25 postings already written"))?
+            .push_run(postings)
+    }
+}
+
+impl Serialize for Bm25Writer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let postings = self
+            .postings
+            .borrow_mut()
+            .take()
+            .ok_or(S::Error::custom("bm25 postings already written"))?
+            .merge()
+            .map_err(S::Error::custom)?;
+        let average_length = match self.lengths.len() {
+            0 => 0.0,
+            count => self.lengths.iter().sum::<u32>() as f32 / count as f32,
+        };
+
+        let mut index = serializer.serialize_struct("Bm25Index", 4)?;
+        index.serialize_field("node_ids", &self.node_ids)?;
+        index.serialize_field("lengths", &self.lengths)?;
+        index.serialize_field("average_length", &average_length)?;
+        index.serialize_field("postings", &MergedPostings(RefCell::new(postings)))?;
+        index.end()
+    }
+}
+
+// Postings of every term, read from their runs as they are serialized.
+struct MergedPostings<I>(RefCell<I>);
+
+impl<I: Iterator<Item = Result<(String, Vec<(u32, u32)>)>>> Serialize for MergedPostings<I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for entry in &mut *self.0.borrow_mut() {
+            let (term, postings) = entry.map_err(S::Error::custom)?;
+            map.serialize_entry(&term, &postings)?;
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,7 @@
+mod bm25_index;
+mod bm25_writer;
+mod tokenize;
+
+pub use bm25_index::Bm25Index;
+pub(crate) use bm25_writer::Bm25Writer;
+pub use tokenize::tokenize;
@@ -0,0 +1,25 @@
+// Characters kept inside a token so identifiers such as `ISO-9001`,
+// `v1.2.3` or `snake_case` survive as one term.
+const JOINERS: [char; 4] = ['-', '_', '.', '/'];
+
+/// Lowercased terms of `text`. Tokens containing joiners are emitted both
+/// whole and split into their parts.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    for word in text.split(|c: char| !(c.is_alphanumeric() || JOINERS.contains(&c))) {
+        let word = word.trim_matches(&JOINERS[..]);
+        if word.is_empty() {
+            continue;
+        }
+        let word = word.to_lowercase();
+        if word.contains(&JOINERS[..]) {
+            tokens.extend(
+                word.split(&JOINERS[..])
+                    .filter(|part| !part.is_empty())
+                    .map(|part| part.to_string()),
+            );
+        }
+        tokens.push(word);
+    }
+    tokens
+}
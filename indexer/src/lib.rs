@@ -1,15 +1,58 @@
 mod context;
+mod diversity_options;
 mod embedding;
+mod expansion_options;
+#[cfg(test)]
+mod fixtures;
+mod hybrid_options;
 mod indexer;
 mod indexing_meta;
+mod indexing_report;
+mod indexing_section;
+mod provenance;
+mod search_filter;
+mod search_options;
+mod token_budget;
 
+pub mod ann;
+pub mod evaluation;
+pub mod fingerprint;
 pub mod graph;
+pub mod lexical;
 pub mod math;
+pub mod quantization;
+pub mod rerank;
+pub mod streaming;
 pub mod utils;
 
+pub use async_trait::async_trait;
 pub use context::Context;
-pub use embedding::{EmbeddingModel, MiniLMEmbeddingModel, ModelId, OpenAIAdaV2EmbeddingModel};
+pub use diversity_options::DiversityOptions;
+pub use embedding::{
+    EmbeddingError,
+    EmbeddingModel,
+    EmbeddingProvider,
+    EmbeddingRegistry,
+    EmbeddingStats,
+    MiniLMEmbeddingModel,
+    ModelId,
+    ModelSpec,
+    OpenAIEmbeddingConfig,
+    OpenAIEmbeddingModel,
+    OpenAIProvider,
+    PoolingMode,
+    TransformerConfig,
+    TransformerEmbeddingModel,
+};
+pub use expansion_options::ExpansionOptions;
+pub use hybrid_options::HybridOptions;
 pub use indexer::Indexer;
 pub use indexing_meta::IndexingMeta;
+pub use indexing_report::IndexingReport;
+pub use indexing_section::IndexingSection;
+pub use provenance::Provenance;
+pub use search_filter::SearchFilter;
+pub use search_options::SearchOptions;
+pub use token_budget::TokenBudget;
 
 type Result<T> = anyhow::Result<T>;
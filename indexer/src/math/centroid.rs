@@ -0,0 +1,33 @@
+/// Normalized mean of `embeddings`.
+pub fn centroid(embeddings: &[&[f32]]) -> Vec<f32> {
+    let dimension = embeddings.first().map_or(0, |embedding| embedding.len());
+    let mut mean = vec![0.0; dimension];
+    for embedding in embeddings {
+        for (sum, value) in mean.iter_mut().zip(embedding.iter()) {
+            *sum += value;
+        }
+    }
+    let norm = mean.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        mean.iter_mut().for_each(|value| *value /= norm);
+    }
+    mean
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn centroid_is_normalized_mean() {
+        let centroid = centroid(&[&[1.0, 0.0], &[0.0, 1.0]]);
+        let expected = 1.0 / 2f32.sqrt();
+        assert!(centroid.iter().all(|value| (value - expected).abs() < 1e-6));
+    }
+
+    #[test]
+    fn centroid_of_opposite_or_no_embeddings() {
+        assert_eq!(centroid(&[&[1.0, -1.0], &[-1.0, 1.0]]), [0.0, 0.0]);
+        assert!(centroid(&[]).is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Weighted reciprocal rank fusion.
+///
+/// Each ranking is a weight and its keys, best first. A key scores
+/// `weight / (rank_constant + rank)` for every ranking it appears in, with
+/// ranks starting at 1.
+pub fn reciprocal_rank_fusion<K>(rankings: &[(f32, Vec<K>)], rank_constant: f32) -> HashMap<K, f32>
+where
+    K: Clone + Eq + Hash,
+{
+    let mut scores = HashMap::new();
+    for (weight, keys) in rankings {
+        for (index, key) in keys.iter().enumerate() {
+            let rank = (index + 1) as f32;
+            *scores.entry(key.clone()).or_insert(0.0) += weight / (rank_constant + rank);
+        }
+    }
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuses_ranks_of_every_ranking() {
+        let rankings = [(1.0, vec!["a", "b", "c"]), (1.0, vec!["b", "c"])];
+        let scores = reciprocal_rank_fusion(&rankings, 60.0);
+
+        assert_eq!(scores.len(), 3);
+        assert_eq!(scores["a"], 1.0 / 61.0);
+        assert_eq!(scores["b"], 1.0 / 62.0 + 1.0 / 61.0);
+        assert_eq!(scores["c"], 1.0 / 63.0 + 1.0 / 62.0);
+        assert!(scores["b"] > scores["c"] && scores["c"] > scores["a"]);
+    }
+
+    #[test]
+    fn weights_scale_rankings() {
+        let rankings = [(0.0, vec!["a"]), (2.0, vec!["b"])];
+        let scores = reciprocal_rank_fusion(&rankings, 0.0);
+
+        assert_eq!(scores["a"], 0.0);
+        assert_eq!(scores["b"], 2.0);
+    }
+
+    #[test]
+    fn empty_rankings() {
+        let rankings: [(f32, Vec<&str>); 0] = [];
+        assert!(reciprocal_rank_fusion(&rankings, 60.0).is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+use super::cosine_similarity_slice;
+
+/// Greedy maximal marginal relevance.
+///
+/// Picks up to `k` items, each maximizing
+/// `lambda * relevance - (1 - lambda) * max similarity to the picked items`,
+/// and returns their indices in picking order. Relevance should be on the
+/// same scale as cosine similarity. Items with an empty or zero embedding are
+/// never counted as similar to another.
+pub fn maximal_marginal_relevance(
+    relevance: &[f32],
+    embeddings: &[&[f32]],
+    lambda: f32,
+    k: usize,
+) -> Vec<usize> {
+    let mut picked: Vec<usize> = vec![];
+    let mut remaining = (0..relevance.len()).collect::<Vec<usize>>();
+    let mut redundancy = vec![f32::MIN; relevance.len()];
+    while picked.len() < k && !remaining.is_empty() {
+        let (position, &index) = remaining
+            .iter()
+            .enumerate()
+            .max_by(|(_, &a), (_, &b)| {
+                let a = marginal_relevance(relevance[a], redundancy[a], lambda);
+                let b = marginal_relevance(relevance[b], redundancy[b], lambda);
+                a.total_cmp(&b)
+            })
+            .unwrap();
+        remaining.swap_remove(position);
+        picked.push(index);
+        if embeddings[index].is_empty() {
+            continue;
+        }
+        for &other in &remaining {
+            if embeddings[other].len() != embeddings[index].len() {
+                continue;
+            }
+            let similarity = cosine_similarity_slice(embeddings[index], embeddings[other]);
+            if !similarity.is_nan() {
+                redundancy[other] = redundancy[other].max(similarity);
+            }
+        }
+    }
+    picked
+}
+
+fn marginal_relevance(relevance: f32, redundancy: f32, lambda: f32) -> f32 {
+    match redundancy == f32::MIN {
+        true => relevance,
+        false => lambda * relevance - (1.0 - lambda) * redundancy,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_diversity_picks_by_relevance() {
+        let embeddings: [&[f32]; 3] = [&[1.0, 0.0], &[1.0, 0.0], &[0.0, 1.0]];
+        let picked = maximal_marginal_relevance(&[0.5, 0.9, 0.7], &embeddings, 1.0, 3);
+        assert_eq!(picked, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn redundant_items_are_picked_last() {
+        let embeddings: [&[f32]; 3] = [&[1.0, 0.0], &[1.0, 0.0], &[0.0, 1.0]];
+        let picked = maximal_marginal_relevance(&[0.9, 0.85, 0.5], &embeddings, 0.5, 3);
+        assert_eq!(picked, vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn picks_at_most_k_items() {
+        let embeddings: [&[f32]; 2] = [&[1.0, 0.0], &[0.0, 1.0]];
+        assert_eq!(
+            maximal_marginal_relevance(&[0.1, 0.2], &embeddings, 0.5, 1),
+            vec![1]
+        );
+        assert_eq!(
+            maximal_marginal_relevance(&[0.1, 0.2], &embeddings, 0.5, 5).len(),
+            2
+        );
+        assert!(maximal_marginal_relevance(&[], &[], 0.5, 5).is_empty());
+    }
+
+    #[test]
+    fn items_without_embeddings_are_not_redundant() {
+        let embeddings: [&[f32]; 3] = [&[1.0, 0.0], &[], &[0.0, 0.0]];
+        let picked = maximal_marginal_relevance(&[0.9, 0.8, 0.7], &embeddings, 0.5, 3);
+        assert_eq!(picked, vec![0, 1, 2]);
+    }
+}
@@ -1,7 +1,15 @@
+mod centroid;
+mod fusion;
+mod mmr;
 mod normalize;
 mod pooling;
 mod similarity;
+mod windows;
 
+pub use centroid::centroid;
+pub use fusion::reciprocal_rank_fusion;
+pub use mmr::maximal_marginal_relevance;
 pub use normalize::normalize;
-pub use pooling::mean_pooling;
-pub use similarity::cosine_similarity;
+pub use pooling::{cls_pooling, last_token_pooling, mean_pooling};
+pub use similarity::{cosine_similarity, cosine_similarity_slice};
+pub use windows::pool_windows;
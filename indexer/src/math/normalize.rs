@@ -6,6 +6,32 @@ pub fn normalize(tensor: &Array<f32, ndarray::IxDyn>) -> Array<f32, ndarray::IxD
         row.mapv(|x| x.powi(2)).sum().sqrt()
     });
     let eps = 1e-12;
-    let normalized_tensor = tensor / norm.mapv(|v| if v > eps { v } else { 1.0 });
+    // Keep the reduced axis so each row of a batch is divided by its own norm.
+    let norm = norm
+        .mapv(|v| if v > eps { v } else { 1.0 })
+        .insert_axis(ndarray::Axis(dim));
+    let normalized_tensor = tensor / norm;
     normalized_tensor
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_rows_of_a_batch() {
+        let tensor = Array::from_shape_vec(vec![2, 2], vec![3.0, 4.0, 0.0, 2.0])
+            .unwrap()
+            .into_dyn();
+        let normalized = normalize(&tensor);
+        assert_eq!(normalized.into_raw_vec(), vec![0.6, 0.8, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn normalize_keeps_zero_rows() {
+        let tensor = Array::from_shape_vec(vec![1, 3], vec![0.0; 3])
+            .unwrap()
+            .into_dyn();
+        assert_eq!(normalize(&tensor).into_raw_vec(), vec![0.0; 3]);
+    }
+}
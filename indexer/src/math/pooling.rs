@@ -29,3 +29,34 @@ pub fn mean_pooling(
 
     Ok(result)
 }
+
+// Embedding of the first token of every input, e.g. `[CLS]`.
+pub fn cls_pooling(
+    model_output: SmallVec<[TValue; 4]>,
+) -> Result<ArrayBase<OwnedRepr<f32>, Dim<IxDynImpl>>> {
+    let token_embeddings = model_output[0].clone().into_tensor().into_array::<f32>()?;
+    Ok(token_embeddings.index_axis(Axis(1), 0).to_owned())
+}
+
+// Embedding of the last token of every input that is not padding. Inputs are
+// padded on the right.
+pub fn last_token_pooling(
+    model_output: SmallVec<[TValue; 4]>,
+    attention_mask: &[u32],
+) -> Result<ArrayBase<OwnedRepr<f32>, Dim<IxDynImpl>>> {
+    let token_embeddings = model_output[0].clone().into_tensor().into_array::<f32>()?;
+    let length = token_embeddings.shape()[1];
+    let rows = token_embeddings
+        .outer_iter()
+        .enumerate()
+        .map(|(row, tokens)| {
+            let last = attention_mask[row * length..(row + 1) * length]
+                .iter()
+                .rposition(|&mask| mask > 0)
+                .unwrap_or(0);
+            tokens.index_axis(Axis(0), last).to_owned()
+        })
+        .collect::<Vec<ArrayBase<OwnedRepr<f32>, Dim<IxDynImpl>>>>();
+    let rows = rows.iter().map(|row| row.view()).collect::<Vec<_>>();
+    Ok(ndarray::stack(Axis(0), &rows)?)
+}
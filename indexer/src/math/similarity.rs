@@ -1,4 +1,4 @@
-use ndarray::Array1;
+use ndarray::{Array1, ArrayView1};
 
 pub fn cosine_similarity(a: Vec<f32>, b: Vec<f32>) -> f32 {
     let a = Array1::from(a);
@@ -8,3 +8,14 @@ pub fn cosine_similarity(a: Vec<f32>, b: Vec<f32>) -> f32 {
     let norm_b = b.dot(&b).sqrt();
     dot_product / (norm_a * norm_b)
 }
+
+// Same as `cosine_similarity` but borrows the vectors, for hot loops that
+// must not clone embeddings.
+pub fn cosine_similarity_slice(a: &[f32], b: &[f32]) -> f32 {
+    let a = ArrayView1::from(a);
+    let b = ArrayView1::from(b);
+    let dot_product = a.dot(&b);
+    let norm_a = a.dot(&a).sqrt();
+    let norm_b = b.dot(&b).sqrt();
+    dot_product / (norm_a * norm_b)
+}
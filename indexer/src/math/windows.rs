@@ -0,0 +1,33 @@
+use ndarray::{ArrayD, Axis, IxDyn};
+
+/// Sums of the `(windows, dimension)` window embeddings of each of `inputs`
+/// inputs, weighted by `weights`. `owners` holds the input of every window.
+pub fn pool_windows(
+    window_embeddings: &ArrayD<f32>,
+    owners: &[usize],
+    weights: &[f32],
+    inputs: usize,
+) -> ArrayD<f32> {
+    let dimension = window_embeddings.shape()[1];
+    let mut embeddings = ArrayD::<f32>::zeros(IxDyn(&[inputs, dimension]));
+    for (window, (&owner, &weight)) in owners.iter().zip(weights).enumerate() {
+        embeddings
+            .index_axis_mut(Axis(0), owner)
+            .scaled_add(weight, &window_embeddings.index_axis(Axis(0), window));
+    }
+    embeddings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_are_pooled_into_their_input() {
+        let windows =
+            ArrayD::from_shape_vec(IxDyn(&[3, 2]), vec![1.0, 0.0, 0.0, 1.0, 2.0, 2.0]).unwrap();
+        let embeddings = pool_windows(&windows, &[0, 0, 2], &[1.0, 3.0, 0.5], 3);
+        assert_eq!(embeddings.shape(), [3, 2]);
+        assert_eq!(embeddings.into_raw_vec(), [1.0, 3.0, 0.0, 0.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,79 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::graph::{Graph, Node, SourceSpan};
+
+/// Where the text of a context comes from, to link back to its document.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct Provenance {
+    /// Key of the document, or the `reference` of its graph when it was not
+    /// loaded from storage.
+    #[builder(default = None)]
+    document_id: Option<String>,
+    #[builder(default)]
+    title: String,
+    /// Titles of the headings the text is under, outermost first.
+    #[builder(default)]
+    heading_path: Vec<String>,
+    /// Pages and character offsets of the text in `data.json`, `None` for
+    /// graphs indexed by older versions.
+    #[builder(default = None)]
+    span: Option<SourceSpan>,
+}
+
+impl Provenance {
+    /// Provenance of `node`, a node of `graph`.
+    pub fn of(graph: &Graph, node: &Node) -> Self {
+        Self::builder()
+            .document_id(graph.document_key().clone().or(graph.reference().clone()))
+            .title(graph.title().to_string())
+            .heading_path(node.heading_path().clone())
+            .span(node.span().clone())
+            .build()
+    }
+
+    /// Cuts the span to the first `len` characters of the text.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if let Some(span) = self.span.as_mut() {
+            span.truncate(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn provenance_of_a_node() {
+        let span = SourceSpan::builder()
+            .start(10)
+            .end(42)
+            .pages(vec![2])
+            .build();
+        let node = Node::builder()
+            .id("chunk".to_string())
+            .data("Replace worn seals.".to_string())
+            .rank_id("manual::0".to_string())
+            .hash(String::new())
+            .embeddings(vec![])
+            .heading_path(vec!["Pumps".to_string(), "Seals".to_string()])
+            .span(Some(span.clone()))
+            .build();
+        let graph = Graph::builder()
+            .id("graph".to_string())
+            .title("Manual".to_string())
+            .node_map(Default::default())
+            .index_model(None)
+            .reference(Some("manual".to_string()))
+            .build();
+
+        let provenance = Provenance::of(&graph, &node);
+        assert_eq!(provenance.document_id().as_deref(), Some("manual"));
+        assert_eq!(provenance.title(), "Manual");
+        assert_eq!(provenance.heading_path(), &["Pumps", "Seals"]);
+        assert_eq!(provenance.span(), &Some(span));
+    }
+}
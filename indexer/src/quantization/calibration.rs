@@ -0,0 +1,265 @@
+use std::convert::Infallible;
+
+use serde::{Deserialize, Serialize};
+
+use super::Quantization;
+
+/// Per-dimension statistics of the embeddings of one graph, used to quantize
+/// them and to approximate the original values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum Calibration {
+    Int8 {
+        min: Vec<f32>,
+        scale: Vec<f32>,
+    },
+    Binary {
+        threshold: Vec<f32>,
+        /// Mean of the values at or below the threshold.
+        low: Vec<f32>,
+        /// Mean of the values above the threshold.
+        high: Vec<f32>,
+    },
+}
+
+impl Calibration {
+    pub fn fit(quantization: Quantization, vectors: &[&[f32]], dimension: usize) -> Self {
+        let result = Self::fit_scan(quantization, dimension, |visit| {
+            vectors.iter().for_each(|vector| visit(vector));
+            Ok::<(), Infallible>(())
+        });
+        match result {
+            Ok(calibration) => calibration,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Same as `fit` over the vectors `scan` passes to its callback. `scan` is
+    /// called once per pass over them, so they can be read back from a file
+    /// instead of held in memory.
+    pub fn fit_scan<E>(
+        quantization: Quantization,
+        dimension: usize,
+        mut scan: impl FnMut(&mut dyn FnMut(&[f32])) -> Result<(), E>,
+    ) -> Result<Self, E> {
+        let calibration = match quantization {
+            Quantization::Int8 => {
+                let mut min = vec![f32::MAX; dimension];
+                let mut max = vec![f32::MIN; dimension];
+                let mut count = 0;
+                scan(&mut |vector| {
+                    for (d, &value) in vector.iter().enumerate() {
+                        min[d] = min[d].min(value);
+                        max[d] = max[d].max(value);
+                    }
+                    count += 1;
+                })?;
+                if count == 0 {
+                    min.fill(0.0);
+                    max.fill(0.0);
+                }
+                let scale = min
+                    .iter()
+                    .zip(&max)
+                    .map(|(min, max)| ((max - min) / 255.0).max(f32::EPSILON))
+                    .collect();
+                Calibration::Int8 { min, scale }
+            }
+            Quantization::Binary => {
+                let mut threshold = vec![0.0; dimension];
+                let mut count = 0;
+                scan(&mut |vector| {
+                    for (d, &value) in vector.iter().enumerate() {
+                        threshold[d] += value;
+                    }
+                    count += 1;
+                })?;
+                threshold
+                    .iter_mut()
+                    .for_each(|sum| *sum /= count.max(1) as f32);
+                let mut low = vec![(0.0, 0); dimension];
+                let mut high = vec![(0.0, 0); dimension];
+                scan(&mut |vector| {
+                    for (d, &value) in vector.iter().enumerate() {
+                        let side = match value > threshold[d] {
+                            true => &mut high[d],
+                            false => &mut low[d],
+                        };
+                        side.0 += value;
+                        side.1 += 1;
+                    }
+                })?;
+                let mean = |sides: Vec<(f32, usize)>| {
+                    sides
+                        .into_iter()
+                        .zip(&threshold)
+                        .map(|((sum, count), &threshold)| match count {
+                            0 => threshold,
+                            _ => sum / count as f32,
+                        })
+                        .collect::<Vec<f32>>()
+                };
+                let low = mean(low);
+                let high = mean(high);
+                Calibration::Binary {
+                    threshold,
+                    low,
+                    high,
+                }
+            }
+        };
+        Ok(calibration)
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        match self {
+            Calibration::Int8 { .. } => Quantization::Int8,
+            Calibration::Binary { .. } => Quantization::Binary,
+        }
+    }
+
+    /// Appends the quantized `vector` to `out`.
+    pub fn encode(&self, vector: &[f32], out: &mut Vec<u8>) {
+        match self {
+            Calibration::Int8 { min, scale } => {
+                out.extend(vector.iter().zip(min.iter().zip(scale)).map(
+                    |(value, (min, scale))| ((value - min) / scale).round().clamp(0.0, 255.0) as u8,
+                ));
+            }
+            Calibration::Binary { threshold, .. } => {
+                let start = out.len();
+                out.resize(start + threshold.len().div_ceil(8), 0);
+                for (d, (value, threshold)) in vector.iter().zip(threshold).enumerate() {
+                    if value > threshold {
+                        out[start + d / 8] |= 1 << (d % 8);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Approximation of the vector `code` was quantized from.
+    pub fn decode(&self, code: &[u8]) -> Vec<f32> {
+        match self {
+            Calibration::Int8 { min, scale } => code
+                .iter()
+                .zip(min.iter().zip(scale))
+                .map(|(&code, (min, scale))| min + code as f32 * scale)
+                .collect(),
+            Calibration::Binary { low, high, .. } => (0..low.len())
+                .map(|d| match code[d / 8] & (1 << (d % 8)) != 0 {
+                    true => high[d],
+                    false => low[d],
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vectors() -> Vec<Vec<f32>> {
+        vec![
+            vec![-1.0, 0.5, 0.0],
+            vec![1.0, 0.25, 2.0],
+            vec![0.0, -0.5, 1.0],
+        ]
+    }
+
+    #[test]
+    fn int8_round_trip() {
+        let vectors = vectors();
+        let samples = vectors
+            .iter()
+            .map(|v| v.as_slice())
+            .collect::<Vec<&[f32]>>();
+        let calibration = Calibration::fit(Quantization::Int8, &samples, 3);
+        let Calibration::Int8 { scale, .. } = &calibration else {
+            panic!("int8 calibration expected");
+        };
+
+        for vector in &vectors {
+            let mut code = vec![];
+            calibration.encode(vector, &mut code);
+            assert_eq!(code.len(), 3);
+            let decoded = calibration.decode(&code);
+            for d in 0..3 {
+                assert!((decoded[d] - vector[d]).abs() <= scale[d] / 2.0 + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn int8_clamps_values_out_of_range() {
+        let calibration = Calibration::fit(Quantization::Int8, &[&[0.0], &[1.0]], 1);
+        let mut code = vec![];
+        calibration.encode(&[-5.0], &mut code);
+        calibration.encode(&[5.0], &mut code);
+        assert_eq!(code, vec![0, 255]);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let vectors = vectors();
+        let samples = vectors
+            .iter()
+            .map(|v| v.as_slice())
+            .collect::<Vec<&[f32]>>();
+        let calibration = Calibration::fit(Quantization::Binary, &samples, 3);
+        let Calibration::Binary {
+            threshold,
+            low,
+            high,
+        } = &calibration
+        else {
+            panic!("binary calibration expected");
+        };
+        let means = [0.0, 0.25 / 3.0, 1.0];
+        assert!(threshold
+            .iter()
+            .zip(means)
+            .all(|(a, b)| (a - b).abs() < 1e-6));
+
+        let mut code = vec![];
+        calibration.encode(&vectors[0], &mut code);
+        assert_eq!(code, vec![0b010]);
+        assert_eq!(calibration.decode(&code), vec![low[0], high[1], low[2]]);
+    }
+
+    #[test]
+    fn binary_codes_pack_eight_dimensions_per_byte() {
+        let vector = (0..10).map(|d| d as f32).collect::<Vec<f32>>();
+        let calibration = Calibration::fit(Quantization::Binary, &[&vector, &[0.0; 10]], 10);
+        let mut code = vec![];
+        calibration.encode(&vector, &mut code);
+        assert_eq!(code, vec![0b1111_1110, 0b11]);
+    }
+
+    #[test]
+    fn fit_scan_matches_fit() {
+        let vectors = vectors();
+        let samples = vectors
+            .iter()
+            .map(|v| v.as_slice())
+            .collect::<Vec<&[f32]>>();
+        for (quantization, expected_passes) in [(Quantization::Int8, 1), (Quantization::Binary, 2)]
+        {
+            let mut passes = 0;
+            let scanned = Calibration::fit_scan(quantization, 3, |visit| {
+                passes += 1;
+                samples.iter().for_each(|vector| visit(vector));
+                Ok::<(), ()>(())
+            })
+            .unwrap();
+            let fitted = Calibration::fit(quantization, &samples, 3);
+            assert_eq!(passes, expected_passes);
+            assert_eq!(
+                serde_json::to_string(&scanned).unwrap(),
+                serde_json::to_string(&fitted).unwrap()
+            );
+        }
+        assert!(Calibration::fit_scan(Quantization::Int8, 3, |_| Err("read")).is_err());
+    }
+}
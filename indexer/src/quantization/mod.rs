@@ -0,0 +1,9 @@
+mod calibration;
+mod quantization;
+mod quantization_report;
+mod quantized_embeddings;
+
+pub use calibration::Calibration;
+pub use quantization::Quantization;
+pub use quantization_report::QuantizationReport;
+pub use quantized_embeddings::{QuantizedEmbeddings, DEFAULT_RESCORE_FACTOR};
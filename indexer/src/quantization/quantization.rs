@@ -0,0 +1,36 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    /// One byte per dimension, scaled between the per-dimension bounds of the
+    /// graph.
+    Int8,
+    /// One bit per dimension, set when the value is above the per-dimension
+    /// mean of the graph.
+    Binary,
+}
+
+impl Quantization {
+    /// Bytes used by one quantized vector.
+    pub fn row_len(&self, dimension: usize) -> usize {
+        match self {
+            Quantization::Int8 => dimension,
+            Quantization::Binary => dimension.div_ceil(8),
+        }
+    }
+}
+
+impl FromStr for Quantization {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "int8" => Ok(Quantization::Int8),
+            "binary" => Ok(Quantization::Binary),
+            _ => anyhow::bail!("unknown quantization: {}", s),
+        }
+    }
+}
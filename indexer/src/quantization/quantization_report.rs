@@ -0,0 +1,18 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use super::Quantization;
+
+/// Quality and size of quantized embeddings compared to full precision ones.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+pub struct QuantizationReport {
+    quantization: Quantization,
+    k: usize,
+    queries: usize,
+    /// Share of the exact top `k` nodes also returned by the quantized search,
+    /// averaged over the queries.
+    recall: f32,
+    full_precision_bytes: usize,
+    quantized_bytes: usize,
+}
@@ -0,0 +1,436 @@
+use std::collections::{HashMap, HashSet};
+
+use derive_getters::Getters;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{Calibration, Quantization, QuantizationReport};
+use crate::{
+    graph::{Graph, Node, NodeId},
+    math,
+};
+
+/// Candidates kept from the quantized scan per requested result.
+pub const DEFAULT_RESCORE_FACTOR: usize = 4;
+
+/// Quantized embeddings of the nodes of a `Graph`, one row per node in node
+/// id order.
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct QuantizedEmbeddings {
+    calibration: Calibration,
+    dimension: usize,
+    node_ids: Vec<NodeId>,
+    /// Stored as the embedding matrix of binary graphs, not in the metadata.
+    #[serde(default)]
+    codes: Vec<u8>,
+    /// Rows of the sub-chunks and sections in `child_codes`, by id.
+    #[serde(skip)]
+    child_rows: HashMap<NodeId, usize>,
+    /// Codes of the sub-chunk and section rows of binary graphs, kept
+    /// quantized and dequantized one row at a time when scored.
+    #[serde(skip)]
+    child_codes: Vec<u8>,
+}
+
+// Query prepared once for scoring every row.
+enum QueryCode {
+    // Dot product with the dequantized row is `offset + Σ weights[d] * code[d]`.
+    Int8 { offset: f32, weights: Vec<f32> },
+    Binary(Vec<u8>),
+}
+
+impl QuantizedEmbeddings {
+    pub fn build(graph: &Graph, quantization: Quantization) -> Self {
+        let mut node_ids = graph.node_map().keys().cloned().collect::<Vec<NodeId>>();
+        node_ids.sort();
+        let vectors = node_ids
+            .iter()
+            .map(|id| graph.node_map()[id].embeddings().as_slice())
+            .collect::<Vec<&[f32]>>();
+        let dimension = vectors.first().map_or(0, |vector| vector.len());
+
+        // Sub-chunks are stored with the same calibration, so it covers them.
+        let samples = vectors
+            .iter()
+            .copied()
+            .chain(
+                graph
+                    .sub_chunk_map()
+                    .values()
+                    .map(|node| node.embeddings().as_slice()),
+            )
+            .collect::<Vec<&[f32]>>();
+        let calibration = Calibration::fit(quantization, &samples, dimension);
+        let mut codes = Vec::with_capacity(vectors.len() * quantization.row_len(dimension));
+        for vector in &vectors {
+            calibration.encode(vector, &mut codes);
+        }
+
+        Self {
+            calibration,
+            dimension,
+            node_ids,
+            codes,
+            child_rows: HashMap::new(),
+            child_codes: vec![],
+        }
+    }
+
+    /// Embeddings of the nodes `node_ids`, sorted, whose rows were already
+    /// encoded with `calibration` into `codes`, in the same order.
+    pub fn from_codes(
+        calibration: Calibration,
+        dimension: usize,
+        node_ids: Vec<NodeId>,
+        codes: Vec<u8>,
+    ) -> Self {
+        Self {
+            calibration,
+            dimension,
+            node_ids,
+            codes,
+            child_rows: HashMap::new(),
+            child_codes: vec![],
+        }
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        self.calibration.quantization()
+    }
+
+    pub fn row_len(&self) -> usize {
+        self.quantization().row_len(self.dimension)
+    }
+
+    pub fn code(&self, row: usize) -> &[u8] {
+        let row_len = self.row_len();
+        &self.codes[row * row_len..(row + 1) * row_len]
+    }
+
+    /// Code of the sub-chunk or section `id`, `None` when the graph was not
+    /// loaded from a binary graph.
+    pub fn child_code(&self, id: &str) -> Option<&[u8]> {
+        let row_len = self.row_len();
+        self.child_rows
+            .get(id)
+            .map(|row| &self.child_codes[row * row_len..(row + 1) * row_len])
+    }
+
+    /// Dequantized embeddings of the node, sub-chunk or section `id`.
+    pub fn embedding(&self, id: &str) -> Option<Vec<f32>> {
+        let row = self
+            .node_ids
+            .binary_search_by(|node_id| node_id.as_str().cmp(id))
+            .ok()
+            .filter(|_| !self.codes.is_empty());
+        let code = match row {
+            Some(row) => Some(self.code(row)),
+            None => self.child_code(id),
+        };
+        code.map(|code| self.calibration.decode(code))
+    }
+
+    pub(crate) fn without_codes(&self) -> Self {
+        Self {
+            calibration: self.calibration.clone(),
+            dimension: self.dimension,
+            node_ids: self.node_ids.clone(),
+            codes: vec![],
+            child_rows: HashMap::new(),
+            child_codes: vec![],
+        }
+    }
+
+    pub(crate) fn set_codes(&mut self, codes: Vec<u8>) {
+        self.codes = codes;
+    }
+
+    /// Sets the codes of the sub-chunk and section rows `ids`, in order.
+    pub(crate) fn set_child_codes(&mut self, ids: &[NodeId], codes: Vec<u8>) {
+        self.child_rows = ids
+            .iter()
+            .enumerate()
+            .map(|(row, id)| (id.to_string(), row))
+            .collect();
+        self.child_codes = codes;
+    }
+
+    /// Scans the quantized rows, then rescores the best `k * rescore_factor`
+    /// with the full precision query against the node embeddings, or against
+    /// the dequantized rows when the graph was loaded without them. Rows of
+    /// nodes not matching `filter` are skipped. Returns `None` when the
+    /// embeddings were not built from `graph`.
+    pub fn search<'g>(
+        &self,
+        graph: &'g Graph,
+        query: &[f32],
+        k: usize,
+        rescore_factor: usize,
+        filter: impl Fn(&Node) -> bool + Sync,
+    ) -> Option<Vec<(f32, &'g Node)>> {
+        self.search_with(graph, query, k, rescore_factor, true, filter)
+    }
+
+    /// Compares the quantized search against an exact full precision scan of
+    /// `graph`, which must still hold its node embeddings. Candidates are
+    /// rescored against the dequantized rows, as for graphs loaded from
+    /// storage.
+    pub fn evaluate(
+        &self,
+        graph: &Graph,
+        queries: &[&[f32]],
+        k: usize,
+        rescore_factor: usize,
+    ) -> QuantizationReport {
+        let exact = queries
+            .par_iter()
+            .map(|query| {
+                let mut exact = graph
+                    .node_map()
+                    .values()
+                    .map(|node| {
+                        (
+                            math::cosine_similarity_slice(query, node.embeddings()),
+                            node,
+                        )
+                    })
+                    .collect::<Vec<(f32, &Node)>>();
+                exact.sort_by(|a, b| b.0.total_cmp(&a.0));
+                exact.truncate(k);
+                exact
+                    .into_iter()
+                    .map(|(_, node)| node.id().to_string())
+                    .collect::<HashSet<NodeId>>()
+            })
+            .collect::<Vec<HashSet<NodeId>>>();
+        self.evaluate_against(graph, queries, &exact, k, rescore_factor)
+    }
+
+    /// Same as `evaluate` with the ids of the `k` nodes closest to each query
+    /// already known, for graphs that do not hold their node embeddings.
+    pub fn evaluate_against(
+        &self,
+        graph: &Graph,
+        queries: &[&[f32]],
+        exact: &[HashSet<NodeId>],
+        k: usize,
+        rescore_factor: usize,
+    ) -> QuantizationReport {
+        let recalls = queries
+            .par_iter()
+            .zip(exact)
+            .map(|(query, expected)| {
+                let found = self
+                    .search_with(graph, query, k, rescore_factor, false, |_| true)
+                    .unwrap_or_default()
+                    .iter()
+                    .filter(|(_, node)| expected.contains(node.id()))
+                    .count();
+                found as f32 / expected.len().max(1) as f32
+            })
+            .collect::<Vec<f32>>();
+
+        QuantizationReport::builder()
+            .quantization(self.quantization())
+            .k(k)
+            .queries(queries.len())
+            .recall(recalls.iter().sum::<f32>() / recalls.len().max(1) as f32)
+            .full_precision_bytes(self.node_ids.len() * self.dimension * 4)
+            .quantized_bytes(self.codes.len())
+            .build()
+    }
+
+    fn search_with<'g>(
+        &self,
+        graph: &'g Graph,
+        query: &[f32],
+        k: usize,
+        rescore_factor: usize,
+        full_precision: bool,
+        filter: impl Fn(&Node) -> bool + Sync,
+    ) -> Option<Vec<(f32, &'g Node)>> {
+        let nodes = self.nodes(graph)?;
+        let query_code = self.prepare(query);
+        let mut candidates = self
+            .codes
+            .par_chunks(self.row_len().max(1))
+            .enumerate()
+            .filter(|(row, _)| filter(nodes[*row]))
+            .map(|(row, code)| (self.approximate_score(&query_code, code), row))
+            .collect::<Vec<(f32, usize)>>();
+        candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        candidates.truncate(k * rescore_factor.max(1));
+
+        let mut result = candidates
+            .into_iter()
+            .map(|(_, row)| {
+                let node = nodes[row];
+                let score = match full_precision && node.embeddings().len() == self.dimension {
+                    true => math::cosine_similarity_slice(query, node.embeddings()),
+                    false => {
+                        let embeddings = self.calibration.decode(self.code(row));
+                        math::cosine_similarity_slice(query, &embeddings)
+                    }
+                };
+                (score, node)
+            })
+            .collect::<Vec<(f32, &Node)>>();
+        result.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        result.truncate(k);
+        Some(result)
+    }
+
+    fn nodes<'g>(&self, graph: &'g Graph) -> Option<Vec<&'g Node>> {
+        if self.node_ids.len() != graph.node_count() {
+            return None;
+        }
+        self.node_ids
+            .iter()
+            .map(|id| graph.node_map().get(id))
+            .collect()
+    }
+
+    fn prepare(&self, query: &[f32]) -> QueryCode {
+        match &self.calibration {
+            Calibration::Int8 { min, scale } => QueryCode::Int8 {
+                offset: query.iter().zip(min).map(|(q, min)| q * min).sum(),
+                weights: query
+                    .iter()
+                    .zip(scale)
+                    .map(|(q, scale)| q * scale)
+                    .collect(),
+            },
+            Calibration::Binary { .. } => {
+                let mut code = vec![];
+                self.calibration.encode(query, &mut code);
+                QueryCode::Binary(code)
+            }
+        }
+    }
+
+    fn approximate_score(&self, query_code: &QueryCode, code: &[u8]) -> f32 {
+        match query_code {
+            QueryCode::Int8 { offset, weights } => {
+                offset
+                    + weights
+                        .iter()
+                        .zip(code)
+                        .map(|(weight, &code)| weight * code as f32)
+                        .sum::<f32>()
+            }
+            // Fewer differing bits scores higher.
+            QueryCode::Binary(query_code) => {
+                -(query_code
+                    .iter()
+                    .zip(code)
+                    .map(|(a, b)| (a ^ b).count_ones())
+                    .sum::<u32>() as f32)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::random_graph;
+
+    #[test]
+    fn int8_embeddings_round_trip() {
+        let graph = random_graph(100, 32, 7);
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Int8);
+        assert_eq!(quantized.codes().len(), 100 * 32);
+
+        for (row, id) in quantized.node_ids().iter().enumerate() {
+            let embeddings = quantized.calibration().decode(quantized.code(row));
+            let error = embeddings
+                .iter()
+                .zip(graph.node_map()[id].embeddings())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f32::max);
+            assert!(error < 0.01, "error: {error}");
+        }
+        let node = &graph.node_map()["node-0000"];
+        assert_eq!(
+            quantized.embedding(node.id()),
+            Some(quantized.calibration().decode(quantized.code(0)))
+        );
+        assert!(quantized.embedding("missing").is_none());
+        assert!(quantized.without_codes().embedding(node.id()).is_none());
+    }
+
+    #[test]
+    fn int8_search_finds_query_node() {
+        let graph = random_graph(200, 32, 7);
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Int8);
+
+        for node in graph.node_map().values().take(20) {
+            let result = quantized
+                .search(&graph, node.embeddings(), 5, DEFAULT_RESCORE_FACTOR, |_| {
+                    true
+                })
+                .unwrap();
+            assert_eq!(result.len(), 5);
+            assert_eq!(result[0].1.id(), node.id());
+            assert!((result[0].0 - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn binary_search_rescores_candidates() {
+        let graph = random_graph(200, 64, 7);
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Binary);
+        assert_eq!(quantized.codes().len(), 200 * 8);
+
+        for node in graph.node_map().values().take(20) {
+            let result = quantized
+                .search(&graph, node.embeddings(), 5, DEFAULT_RESCORE_FACTOR, |_| {
+                    true
+                })
+                .unwrap();
+            assert_eq!(result[0].1.id(), node.id());
+            assert!(result.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+        }
+    }
+
+    #[test]
+    fn search_skips_filtered_nodes() {
+        let graph = random_graph(50, 16, 7);
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Int8);
+        let query = graph.node_map()["node-0003"].embeddings();
+
+        let result = quantized
+            .search(&graph, query, 5, DEFAULT_RESCORE_FACTOR, |node| {
+                node.id() != "node-0003"
+            })
+            .unwrap();
+        assert!(result.iter().all(|(_, node)| node.id() != "node-0003"));
+        assert!(quantized
+            .search(
+                &random_graph(10, 16, 7),
+                query,
+                5,
+                DEFAULT_RESCORE_FACTOR,
+                |_| true
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn evaluate_reports_recall_and_size() {
+        let graph = random_graph(200, 32, 7);
+        let quantized = QuantizedEmbeddings::build(&graph, Quantization::Int8);
+        let queries = graph
+            .node_map()
+            .values()
+            .take(10)
+            .map(|node| node.embeddings().as_slice())
+            .collect::<Vec<&[f32]>>();
+
+        let report = quantized.evaluate(&graph, &queries, 10, DEFAULT_RESCORE_FACTOR);
+        assert_eq!(*report.queries(), 10);
+        assert!(*report.recall() >= 0.9, "recall: {}", report.recall());
+        assert_eq!(*report.full_precision_bytes(), 200 * 32 * 4);
+        assert_eq!(*report.quantized_bytes(), 200 * 32);
+    }
+}
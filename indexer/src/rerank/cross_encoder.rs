@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+use tokenizers::{Tokenizer, TruncationParams};
+use tract_onnx::prelude::*;
+
+use crate::Result;
+
+const BATCH_SIZE: usize = 16;
+const MAX_LENGTH: usize = 512;
+
+/// Cross-encoder scoring the relevance of a text to a query, e.g.
+/// `cross-encoder/ms-marco-MiniLM-L-6-v2` exported to ONNX.
+#[derive(Debug)]
+pub struct CrossEncoderModel {
+    tokenizer: Tokenizer,
+    model_name: String,
+    model: SimplePlan<TypedFact, Box<dyn TypedOp>, Graph<TypedFact, Box<dyn TypedOp>>>,
+}
+
+impl CrossEncoderModel {
+    pub fn from_file(model_name: &str, model_path: &str) -> Result<Self> {
+        let model_dir = PathBuf::from(model_path);
+        let mut tokenizer = Tokenizer::from_file(Path::join(&model_dir, "tokenizer.json"))
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: MAX_LENGTH,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let model = tract_onnx::onnx()
+            .model_for_path(Path::join(&model_dir, "model.onnx"))?
+            .into_optimized()?
+            .into_runnable()?;
+
+        Ok(Self {
+            tokenizer,
+            model_name: model_name.to_string(),
+            model,
+        })
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    /// Relevance of each text to `query`, between 0 and 1.
+    pub fn score(&self, query: &str, texts: &[&str]) -> Result<Vec<f32>> {
+        let scores = texts
+            .par_chunks(BATCH_SIZE)
+            .map(|batch| self.score_batch(query, batch))
+            .collect::<Result<Vec<Vec<f32>>>>()?;
+        Ok(scores.into_iter().flatten().collect())
+    }
+
+    // Same padding scheme as `MiniLMEmbeddingModel`, with the query and the
+    // text encoded as one pair per row.
+    fn score_batch(&self, query: &str, texts: &[&str]) -> Result<Vec<f32>> {
+        if texts.is_empty() {
+            return Ok(vec![]);
+        }
+        let pairs = texts.iter().map(|&text| (query, text)).collect::<Vec<_>>();
+        let encoded_inputs = self
+            .tokenizer
+            .encode_batch(pairs, true)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        let batch_size = encoded_inputs.len();
+        let length = encoded_inputs.iter().map(|x| x.len()).max().unwrap_or(0);
+
+        let mut input_ids = vec![0i64; batch_size * length];
+        let mut attention_mask = vec![0i64; batch_size * length];
+        let mut token_type_ids = vec![0i64; batch_size * length];
+        for (row, encoded_input) in encoded_inputs.iter().enumerate() {
+            let offset = row * length;
+            for (column, &id) in encoded_input.get_ids().iter().enumerate() {
+                input_ids[offset + column] = id as i64;
+            }
+            for (column, &mask) in encoded_input.get_attention_mask().iter().enumerate() {
+                attention_mask[offset + column] = mask as i64;
+            }
+            for (column, &type_id) in encoded_input.get_type_ids().iter().enumerate() {
+                token_type_ids[offset + column] = type_id as i64;
+            }
+        }
+
+        let input_ids: Tensor =
+            tract_ndarray::Array2::from_shape_vec((batch_size, length), input_ids)?.into();
+        let input_attention_mask: Tensor =
+            tract_ndarray::Array2::from_shape_vec((batch_size, length), attention_mask)?.into();
+        let input_token_type_ids: Tensor =
+            tract_ndarray::Array2::from_shape_vec((batch_size, length), token_type_ids)?.into();
+
+        let outputs = self.model.run(tvec!(
+            input_ids.into(),
+            input_attention_mask.into(),
+            input_token_type_ids.into()
+        ))?;
+
+        // Logits have shape `(batch, 1)`.
+        let logits = outputs[0].to_array_view::<f32>()?;
+        let scores = logits
+            .iter()
+            .map(|&logit| 1.0 / (1.0 + (-logit).exp()))
+            .collect();
+        Ok(scores)
+    }
+}
@@ -0,0 +1,5 @@
+mod cross_encoder;
+mod rerank_options;
+
+pub use cross_encoder::CrossEncoderModel;
+pub use rerank_options::RerankOptions;
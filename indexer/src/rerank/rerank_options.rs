@@ -0,0 +1,40 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+/// Second retrieval stage that rescores the best contexts with a cross-encoder.
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct RerankOptions {
+    /// Contexts passed to the cross-encoder. Only these are returned.
+    #[builder(default = 20)]
+    top_n: usize,
+}
+
+impl Default for RerankOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearchOptions;
+
+    #[test]
+    fn rerank_options_defaults() {
+        let options: RerankOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(*options.top_n(), 20);
+        let options: RerankOptions = serde_json::from_str(r#"{"top_n": 5}"#).unwrap();
+        assert_eq!(*options.top_n(), 5);
+    }
+
+    #[test]
+    fn rerank_is_off_unless_requested() {
+        let options: SearchOptions = serde_json::from_str("{}").unwrap();
+        assert!(options.rerank().is_none());
+        let options: SearchOptions = serde_json::from_str(r#"{"rerank": {}}"#).unwrap();
+        assert_eq!(*options.rerank().as_ref().unwrap().top_n(), 20);
+    }
+}
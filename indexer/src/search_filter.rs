@@ -0,0 +1,191 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::graph::{Graph, Node};
+
+/// Restricts a search to matching documents and nodes. Unset fields match
+/// everything. Text comparisons ignore case.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct SearchFilter {
+    /// Part of the document title.
+    #[builder(default = None)]
+    title: Option<String>,
+    #[builder(default = None)]
+    creator: Option<String>,
+    /// Earliest creation time, unix timestamp in seconds.
+    #[builder(default = None)]
+    created_after: Option<i64>,
+    /// Latest creation time, unix timestamp in seconds.
+    #[builder(default = None)]
+    created_before: Option<i64>,
+    /// Any of these file extensions, e.g. `pdf`.
+    #[builder(default)]
+    file_types: Vec<String>,
+    /// Parts of headings the node must be under, outermost first. Headings in
+    /// between may be skipped.
+    #[builder(default)]
+    heading_path: Vec<String>,
+    /// All of these tags.
+    #[builder(default)]
+    tags: Vec<String>,
+}
+
+impl SearchFilter {
+    /// Whether the document attributes of `graph` match. Graphs indexed
+    /// without metadata only match filters on their title.
+    pub fn matches_graph(&self, graph: &Graph) -> bool {
+        let metadata = graph.metadata();
+        let title = self
+            .title
+            .as_ref()
+            .is_none_or(|title| contains(graph.title(), title));
+        let creator = self
+            .creator
+            .as_ref()
+            .is_none_or(|creator| metadata.creator().as_ref() == Some(creator));
+        let created_after = self
+            .created_after
+            .is_none_or(|after| metadata.creation_time().is_some_and(|time| time >= after));
+        let created_before = self
+            .created_before
+            .is_none_or(|before| metadata.creation_time().is_some_and(|time| time <= before));
+        let file_type = self.file_types.is_empty()
+            || metadata.file_type().as_ref().is_some_and(|file_type| {
+                self.file_types
+                    .iter()
+                    .any(|other| other.eq_ignore_ascii_case(file_type))
+            });
+        let tags = self.tags.iter().all(|tag| {
+            metadata
+                .tags()
+                .iter()
+                .any(|other| other.to_lowercase() == tag.to_lowercase())
+        });
+        title && creator && created_after && created_before && file_type && tags
+    }
+
+    /// Whether the heading path of `node` matches.
+    pub fn matches_node(&self, node: &Node) -> bool {
+        let mut headings = node.heading_path().iter();
+        self.heading_path
+            .iter()
+            .all(|part| headings.any(|heading| contains(heading, part)))
+    }
+
+    /// Whether some nodes may not match, so that searches must check every
+    /// candidate.
+    pub fn filters_nodes(&self) -> bool {
+        !self.heading_path.is_empty()
+    }
+}
+
+fn contains(text: &str, part: &str) -> bool {
+    text.to_lowercase().contains(&part.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::graph::DocumentMetadata;
+
+    fn graph(metadata: DocumentMetadata) -> Graph {
+        Graph::builder()
+            .id("graph".to_string())
+            .title("Pump Maintenance Manual".to_string())
+            .node_map(HashMap::new())
+            .index_model(None)
+            .metadata(metadata)
+            .build()
+    }
+
+    fn metadata() -> DocumentMetadata {
+        DocumentMetadata::builder()
+            .creator(Some("alice".to_string()))
+            .creation_time(Some(1_700_000_000))
+            .file_type(Some("pdf".to_string()))
+            .tags(vec!["Manual".to_string(), "pumps".to_string()])
+            .build()
+    }
+
+    #[test]
+    fn matches_graph_on_every_set_field() {
+        let graph = graph(metadata());
+        assert!(SearchFilter::default().matches_graph(&graph));
+
+        let filter = SearchFilter::builder()
+            .title(Some("maintenance".to_string()))
+            .creator(Some("alice".to_string()))
+            .created_after(Some(1_600_000_000))
+            .created_before(Some(1_700_000_000))
+            .file_types(vec!["DOCX".to_string(), "PDF".to_string()])
+            .tags(vec!["manual".to_string()])
+            .build();
+        assert!(filter.matches_graph(&graph));
+
+        for filter in [
+            SearchFilter::builder()
+                .title(Some("valves".to_string()))
+                .build(),
+            SearchFilter::builder()
+                .creator(Some("bob".to_string()))
+                .build(),
+            SearchFilter::builder()
+                .created_after(Some(1_800_000_000))
+                .build(),
+            SearchFilter::builder()
+                .created_before(Some(1_600_000_000))
+                .build(),
+            SearchFilter::builder()
+                .file_types(vec!["docx".to_string()])
+                .build(),
+            SearchFilter::builder()
+                .tags(vec!["manual".to_string(), "valves".to_string()])
+                .build(),
+        ] {
+            assert!(!filter.matches_graph(&graph), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn graphs_without_metadata_only_match_title_filters() {
+        let graph = graph(DocumentMetadata::default());
+        let filter = SearchFilter::builder()
+            .title(Some("pump".to_string()))
+            .build();
+        assert!(filter.matches_graph(&graph));
+        let filter = SearchFilter::builder().created_after(Some(0)).build();
+        assert!(!filter.matches_graph(&graph));
+    }
+
+    #[test]
+    fn matches_node_on_heading_path_in_order() {
+        let node = Node::builder()
+            .id("node".to_string())
+            .data(String::new())
+            .rank_id(String::new())
+            .hash(String::new())
+            .embeddings(vec![])
+            .heading_path(vec![
+                "2 Maintenance".to_string(),
+                "2.1 Pumps".to_string(),
+                "2.1.3 Seals".to_string(),
+            ])
+            .build();
+
+        let filter = |path: &[&str]| {
+            SearchFilter::builder()
+                .heading_path(path.iter().map(|part| part.to_string()).collect())
+                .build()
+        };
+        assert!(filter(&[]).matches_node(&node));
+        assert!(filter(&["maintenance", "seals"]).matches_node(&node));
+        assert!(!filter(&["seals", "maintenance"]).matches_node(&node));
+        assert!(!filter(&["valves"]).matches_node(&node));
+        assert!(filter(&["pumps"]).filters_nodes());
+        assert!(!filter(&[]).filters_nodes());
+    }
+}
@@ -0,0 +1,65 @@
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
+
+use crate::{
+    ann::SearchStrategy,
+    rerank::RerankOptions,
+    DiversityOptions,
+    ExpansionOptions,
+    HybridOptions,
+    SearchFilter,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize, TypedBuilder, Getters)]
+#[serde(default)]
+pub struct SearchOptions {
+    /// Nodes taken from each graph before they are re-chunked and scored.
+    #[builder(default = 10)]
+    top_k: usize,
+    /// Graphs searched per query, picked by their document embedding. Graphs
+    /// without document embedding are always searched. `None` searches all.
+    #[builder(default = None)]
+    top_documents: Option<usize>,
+    #[builder(default)]
+    strategy: SearchStrategy,
+    /// Retrieved nodes are replaced by their parent section when at least
+    /// this many nodes of the section are retrieved. `None` keeps nodes as is.
+    #[builder(default = None)]
+    expand_sections: Option<usize>,
+    /// Adds the nodes linked to the best retrieved nodes by graph edges when
+    /// set, before nodes are replaced by their sections.
+    #[builder(default = None)]
+    expansion: Option<ExpansionOptions>,
+    /// Fuses BM25 and vector rankings when set. Scores of the returned
+    /// contexts are then fused scores rather than cosine similarities.
+    #[builder(default = None)]
+    hybrid: Option<HybridOptions>,
+    /// Rescores the best contexts with a cross-encoder when set. Scores of
+    /// the returned contexts are then cross-encoder scores.
+    #[builder(default = None)]
+    rerank: Option<RerankOptions>,
+    /// Merges contexts of different documents with near duplicate texts into
+    /// the best ranked one, which then lists the references of all.
+    #[builder(default = true)]
+    collapse_duplicates: bool,
+    /// Drops overlapping contexts and diversifies the order of the rest when
+    /// set.
+    #[builder(default = None)]
+    diversity: Option<DiversityOptions>,
+    /// Searches only the documents and nodes matching the filter when set.
+    #[builder(default = None)]
+    filter: Option<SearchFilter>,
+}
+
+impl SearchOptions {
+    pub fn set_filter(&mut self, filter: Option<SearchFilter>) {
+        self.filter = filter;
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
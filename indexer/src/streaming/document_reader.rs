@@ -0,0 +1,158 @@
+use std::{fmt, io::Read};
+
+use document::document::Node;
+use serde::de::{DeserializeSeed, Deserializer, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::Result;
+
+/// Reads the nodes of a `Document` stored as JSON one node at a time, so
+/// large documents are never held in memory at once. Reads the output of
+/// `DocumentWriter` and of `serde_json`.
+pub struct DocumentReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> DocumentReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Calls `f` with every node, in document order. Stops at the first
+    /// error of `f`.
+    pub fn for_each_node<F: FnMut(Node) -> Result<()>>(self, mut f: F) -> Result<()> {
+        let mut deserializer = serde_json::Deserializer::from_reader(self.reader);
+        (&mut deserializer).deserialize_map(DocumentVisitor { f: &mut f })?;
+        deserializer.end()?;
+        Ok(())
+    }
+}
+
+// Visits the fields of the document, handing the `nodes` array to `NodesSeed`
+// and skipping the others.
+struct DocumentVisitor<'f, F> {
+    f: &'f mut F,
+}
+
+impl<'de, 'f, F: FnMut(Node) -> Result<()>> Visitor<'de> for DocumentVisitor<'f, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "nodes" => map.next_value_seed(NodesSeed { f: &mut *self.f })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Deserializes the `nodes` array an element at a time.
+struct NodesSeed<'f, F> {
+    f: &'f mut F,
+}
+
+impl<'de, 'f, F: FnMut(Node) -> Result<()>> DeserializeSeed<'de> for NodesSeed<'f, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'f, F: FnMut(Node) -> Result<()>> Visitor<'de> for NodesSeed<'f, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of nodes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        while let Some(node) = seq.next_element::<Node>()? {
+            (self.f)(node).map_err(A::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use document::document::{Document, DocumentMeta, Text};
+
+    use super::*;
+    use crate::streaming::DocumentWriter;
+
+    fn text(value: &str) -> Node {
+        Node::Text(
+            Text::builder()
+                .value(value.to_string())
+                .position(None)
+                .build(),
+        )
+    }
+
+    fn document() -> Document {
+        let meta = DocumentMeta::builder()
+            .title("manual".to_string())
+            .language(None)
+            .author(None)
+            .creator(None)
+            .producer(None)
+            .subject(None)
+            .description(None)
+            .keywords(None)
+            .creation_date(None)
+            .modification_date(None)
+            .build();
+        Document::builder()
+            .meta(meta)
+            .nodes(vec![text("first"), text("second"), text("third")])
+            .build()
+    }
+
+    fn read_nodes(json: &[u8]) -> Vec<Node> {
+        let mut nodes = vec![];
+        DocumentReader::new(json)
+            .for_each_node(|node| {
+                nodes.push(node);
+                Ok(())
+            })
+            .unwrap();
+        nodes
+    }
+
+    #[test]
+    fn reads_nodes_in_order() {
+        let document = document();
+        let json = serde_json::to_string(&document).unwrap();
+        assert_eq!(read_nodes(json.as_bytes()), *document.nodes());
+
+        let mut writer = DocumentWriter::new(vec![]).unwrap();
+        for node in document.nodes() {
+            writer.push(node).unwrap();
+        }
+        let written = writer.finish(document.meta()).unwrap();
+        assert_eq!(read_nodes(&written), *document.nodes());
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let json = serde_json::to_string(&document()).unwrap();
+        let mut read = 0;
+        let result = DocumentReader::new(json.as_bytes()).for_each_node(|_| {
+            read += 1;
+            anyhow::bail!("stop")
+        });
+        assert!(result.is_err());
+        assert_eq!(read, 1);
+    }
+}
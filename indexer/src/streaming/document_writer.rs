@@ -0,0 +1,37 @@
+use std::io::Write;
+
+use document::document::{DocumentMeta, Node};
+
+use crate::Result;
+
+/// Writes a `Document` as JSON one node at a time, so documents parsed in
+/// batches are never held in memory at once.
+pub struct DocumentWriter<W: Write> {
+    out: W,
+    nodes: usize,
+}
+
+impl<W: Write> DocumentWriter<W> {
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(b"{\"nodes\":[")?;
+        Ok(Self { out, nodes: 0 })
+    }
+
+    pub fn push(&mut self, node: &Node) -> Result<()> {
+        if self.nodes > 0 {
+            self.out.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.out, node)?;
+        self.nodes += 1;
+        Ok(())
+    }
+
+    /// Ends the document with its `meta`, known once all nodes are parsed.
+    pub fn finish(mut self, meta: &DocumentMeta) -> Result<W> {
+        self.out.write_all(b"],\"meta\":")?;
+        serde_json::to_writer(&mut self.out, meta)?;
+        self.out.write_all(b"}")?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
@@ -0,0 +1,17 @@
+mod document_reader;
+mod document_writer;
+mod posting_spool;
+mod spool;
+mod spooled_graph;
+mod stored_graph;
+mod streaming_indexer;
+mod temp_path;
+
+pub use document_reader::DocumentReader;
+pub use document_writer::DocumentWriter;
+pub(crate) use posting_spool::PostingSpool;
+pub use spool::{SectionSpool, Spool};
+pub(crate) use spooled_graph::SpooledGraph;
+pub use stored_graph::StoredGraph;
+pub use streaming_indexer::StreamingIndexer;
+pub use temp_path::TempPath;
@@ -0,0 +1,163 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::Result;
+
+// Postings of one term, `(node position, term frequency)` pairs.
+type Postings = Vec<(u32, u32)>;
+
+// Read buffer of every run while merging.
+const RUN_BUFFER_BYTES: usize = 4096;
+
+/// BM25 postings of a streamed graph, written to a file a batch at a time as
+/// runs sorted by term, and merged term by term when the graph is written.
+/// Only one line of every run is in memory while merging.
+pub(crate) struct PostingSpool {
+    out: BufWriter<File>,
+    runs: Vec<(u64, u64)>,
+    len: u64,
+}
+
+impl PostingSpool {
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self {
+            out: BufWriter::new(file),
+            runs: vec![],
+            len: 0,
+        })
+    }
+
+    /// Appends the postings of a batch of nodes as a run.
+    pub(crate) fn push_run(&mut self, postings: BTreeMap<String, Postings>) -> Result<()> {
+        if postings.is_empty() {
+            return Ok(());
+        }
+        let start = self.len;
+        for entry in postings {
+            let mut line = serde_json::to_vec(&entry)?;
+            line.push(b'\n');
+            self.out.write_all(&line)?;
+            self.len += line.len() as u64;
+        }
+        self.runs.push((start, self.len));
+        Ok(())
+    }
+
+    /// Postings of every term, in term order, with the postings of the runs
+    /// concatenated in the order they were pushed.
+    pub(crate) fn merge(self) -> Result<impl Iterator<Item = Result<(String, Postings)>>> {
+        let file = Arc::new(Mutex::new(
+            self.out.into_inner().map_err(|err| err.into_error())?,
+        ));
+        let mut runs = self
+            .runs
+            .into_iter()
+            .map(|(start, end)| {
+                let run = RunReader {
+                    file: file.clone(),
+                    position: start,
+                    end,
+                };
+                BufReader::with_capacity(RUN_BUFFER_BYTES, run)
+            })
+            .collect::<Vec<BufReader<RunReader>>>();
+        let mut heads = vec![];
+        let mut heap = BinaryHeap::new();
+        for (index, run) in runs.iter_mut().enumerate() {
+            let head = next_entry(run)?;
+            if let Some((term, _)) = &head {
+                heap.push(Reverse((term.clone(), index)));
+            }
+            heads.push(head.map(|(_, postings)| postings));
+        }
+
+        Ok(PostingMerge { runs, heads, heap })
+    }
+}
+
+// Merges the runs by term.
+struct PostingMerge {
+    runs: Vec<BufReader<RunReader>>,
+    heads: Vec<Option<Postings>>,
+    heap: BinaryHeap<Reverse<(String, usize)>>,
+}
+
+impl PostingMerge {
+    // Takes the head of run `index` and reads its next entry.
+    fn advance(&mut self, index: usize) -> Result<Postings> {
+        let postings = self.heads[index].take().unwrap_or_default();
+        if let Some((term, next)) = next_entry(&mut self.runs[index])? {
+            self.heap.push(Reverse((term, index)));
+            self.heads[index] = Some(next);
+        }
+        Ok(postings)
+    }
+}
+
+impl Iterator for PostingMerge {
+    type Item = Result<(String, Postings)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((term, index)) = self.heap.pop()?;
+        let mut indexes = vec![index];
+        while let Some(Reverse((next, _))) = self.heap.peek() {
+            if *next != term {
+                break;
+            }
+            let Reverse((_, index)) = self.heap.pop().unwrap();
+            indexes.push(index);
+        }
+        indexes.sort();
+
+        let mut postings = vec![];
+        for index in indexes {
+            match self.advance(index) {
+                Ok(run_postings) => postings.extend(run_postings),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(Ok((term, postings)))
+    }
+}
+
+// Next `(term, postings)` line of a run.
+fn next_entry(run: &mut BufReader<RunReader>) -> Result<Option<(String, Postings)>> {
+    let mut line = String::new();
+    if run.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+// Reads the bytes of one run of the shared spool file.
+struct RunReader {
+    file: Arc<Mutex<File>>,
+    position: u64,
+    end: u64,
+}
+
+impl Read for RunReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = (self.end - self.position).min(buf.len() as u64) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(self.position))?;
+        let read = file.read(&mut buf[..len])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+use document::document::Section;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Result;
+
+/// Sections written to a file as they are split, and read back one at a time
+/// once parsing is done.
+pub type SectionSpool = Spool<Section>;
+
+/// Records written to a file one JSON line at a time, and read back one at a
+/// time in the same order.
+pub struct Spool<T> {
+    out: BufWriter<File>,
+    records: PhantomData<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Spool<T> {
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            records: PhantomData,
+        })
+    }
+
+    pub fn push(&mut self, record: &T) -> Result<()> {
+        serde_json::to_writer(&mut self.out, record)?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Records of the spool at `path`, in the order they were written.
+    pub fn read(path: &Path) -> Result<impl Iterator<Item = Result<T>>> {
+        let reader = BufReader::new(File::open(path)?);
+        let records = serde_json::Deserializer::from_reader(reader)
+            .into_iter::<T>()
+            .map(|record| Ok(record?));
+        Ok(records)
+    }
+}
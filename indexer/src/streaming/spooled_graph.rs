@@ -0,0 +1,99 @@
+use std::{cell::RefCell, collections::HashMap, path::Path};
+
+use serde::{
+    ser::{Error, SerializeMap, SerializeStruct},
+    Serialize,
+    Serializer,
+};
+
+use super::Spool;
+use crate::{
+    graph::{DocumentMetadata, Edge, Node, NodeId},
+    lexical::Bm25Writer,
+    quantization::QuantizedEmbeddings,
+};
+
+/// Graph whose nodes, sections, sub-chunks and edges are spooled to files,
+/// serialized like a `Graph` by reading them back one record at a time.
+/// Edges of `edges` are added to the spooled edges of the same node, and
+/// taken when serialized, so it can only be serialized once.
+pub(crate) struct SpooledGraph<'a> {
+    pub(crate) id: &'a str,
+    pub(crate) title: &'a str,
+    pub(crate) metadata: &'a DocumentMetadata,
+    pub(crate) nodes: &'a Path,
+    pub(crate) sections: &'a Path,
+    pub(crate) sub_chunks: &'a Path,
+    pub(crate) spooled_edges: &'a Path,
+    pub(crate) edges: RefCell<HashMap<NodeId, Vec<Edge>>>,
+    pub(crate) index_model: String,
+    pub(crate) embeddings: Vec<f32>,
+    pub(crate) min_hash: Vec<u32>,
+    pub(crate) reference: &'a str,
+    pub(crate) reference_link: &'a str,
+    pub(crate) lexical_index: &'a Bm25Writer,
+    /// Without codes, stored as the embedding matrix.
+    pub(crate) quantized: Option<QuantizedEmbeddings>,
+}
+
+impl Serialize for SpooledGraph<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut graph = serializer.serialize_struct("Graph", 15)?;
+        graph.serialize_field("id", self.id)?;
+        graph.serialize_field("title", self.title)?;
+        graph.serialize_field("metadata", self.metadata)?;
+        graph.serialize_field("node_map", &SpooledNodes(self.nodes))?;
+        graph.serialize_field("section_map", &SpooledNodes(self.sections))?;
+        graph.serialize_field("sub_chunk_map", &SpooledNodes(self.sub_chunks))?;
+        let edges = SpooledEdges {
+            path: self.spooled_edges,
+            edges: &self.edges,
+        };
+        graph.serialize_field("edge_map", &edges)?;
+        graph.serialize_field("index_model", &Some(&self.index_model))?;
+        graph.serialize_field("embeddings", &Some(&self.embeddings))?;
+        graph.serialize_field("min_hash", &self.min_hash)?;
+        graph.serialize_field("reference", &Some(self.reference))?;
+        graph.serialize_field("reference_link", &Some(self.reference_link))?;
+        graph.serialize_field("hash", &None::<String>)?;
+        graph.serialize_field("lexical_index", &Some(self.lexical_index))?;
+        graph.serialize_field("quantized", &self.quantized)?;
+        graph.end()
+    }
+}
+
+// Nodes of a spool, by id.
+struct SpooledNodes<'a>(&'a Path);
+
+impl Serialize for SpooledNodes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for node in Spool::<Node>::read(self.0).map_err(S::Error::custom)? {
+            let node = node.map_err(S::Error::custom)?;
+            map.serialize_entry(node.id(), &node)?;
+        }
+        map.end()
+    }
+}
+
+// Edges of a spool by source node, merged with the edges of `edges`.
+struct SpooledEdges<'a> {
+    path: &'a Path,
+    edges: &'a RefCell<HashMap<NodeId, Vec<Edge>>>,
+}
+
+impl Serialize for SpooledEdges<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut edges = self.edges.borrow_mut();
+        let mut map = serializer.serialize_map(None)?;
+        for record in Spool::<(NodeId, Vec<Edge>)>::read(self.path).map_err(S::Error::custom)? {
+            let (id, mut node_edges) = record.map_err(S::Error::custom)?;
+            node_edges.extend(edges.remove(&id).unwrap_or_default());
+            map.serialize_entry(&id, &node_edges)?;
+        }
+        for (id, node_edges) in edges.drain() {
+            map.serialize_entry(&id, &node_edges)?;
+        }
+        map.end()
+    }
+}
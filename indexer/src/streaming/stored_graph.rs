@@ -0,0 +1,178 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    graph::{DocumentMetadata, GraphHeader, NodeId, GRAPH_HEADER_LEN},
+    quantization::{Calibration, Quantization},
+    Result,
+};
+
+/// Binary graph file of an earlier version of a document, read without
+/// loading it: its document fields, and the embeddings of its chunks and
+/// sub-chunks by content hash, read a row at a time.
+pub struct StoredGraph {
+    file: Mutex<File>,
+    header: GraphHeader,
+    fields: StoredFields,
+    /// Rows of the chunks by hash, in node id order.
+    chunk_rows: HashMap<String, Vec<usize>>,
+    sub_chunk_rows: HashMap<String, usize>,
+}
+
+// Metadata section of the graph, reduced to what is kept.
+#[derive(Deserialize)]
+struct StoredMetadata {
+    graph: StoredFields,
+    node_ids: Vec<NodeId>,
+}
+
+#[derive(Deserialize)]
+struct StoredFields {
+    title: String,
+    #[serde(default)]
+    metadata: DocumentMetadata,
+    #[serde(default)]
+    node_map: HashMap<NodeId, StoredNode>,
+    #[serde(default)]
+    sub_chunk_map: HashMap<NodeId, StoredNode>,
+    index_model: Option<String>,
+    #[serde(default)]
+    reference: Option<String>,
+    #[serde(default)]
+    reference_link: Option<String>,
+    #[serde(default)]
+    quantized: Option<StoredQuantization>,
+}
+
+#[derive(Deserialize)]
+struct StoredNode {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct StoredQuantization {
+    calibration: Calibration,
+}
+
+impl StoredGraph {
+    /// Reads the header and metadata of the binary graph at `path`. Node
+    /// texts are skipped, only ids and hashes are kept.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path)?;
+        let mut bytes = [0; GRAPH_HEADER_LEN];
+        file.read_exact(&mut bytes)?;
+        let header = GraphHeader::read_prefix(&bytes)?;
+        file.seek(SeekFrom::Start(header.metadata_range().start as u64))?;
+        let reader = BufReader::new(&file).take(*header.metadata_len());
+        let StoredMetadata {
+            mut graph,
+            node_ids,
+        } = serde_json::from_reader(reader)?;
+
+        let rows = node_ids
+            .iter()
+            .enumerate()
+            .map(|(row, id)| (id.as_str(), row))
+            .collect::<HashMap<&str, usize>>();
+        let mut chunks = graph
+            .node_map
+            .drain()
+            .collect::<Vec<(NodeId, StoredNode)>>();
+        chunks.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut chunk_rows: HashMap<String, Vec<usize>> = HashMap::new();
+        for (id, node) in chunks {
+            if let Some(&row) = rows.get(id.as_str()) {
+                chunk_rows.entry(node.hash).or_default().push(row);
+            }
+        }
+        let sub_chunk_rows = graph
+            .sub_chunk_map
+            .drain()
+            .filter_map(|(id, node)| Some((node.hash, *rows.get(id.as_str())?)))
+            .collect();
+
+        Ok(Self {
+            file: Mutex::new(file),
+            header,
+            fields: graph,
+            chunk_rows,
+            sub_chunk_rows,
+        })
+    }
+
+    pub fn title(&self) -> &str {
+        &self.fields.title
+    }
+
+    pub fn metadata(&self) -> &DocumentMetadata {
+        &self.fields.metadata
+    }
+
+    pub fn index_model(&self) -> Option<&str> {
+        self.fields.index_model.as_deref()
+    }
+
+    pub fn reference(&self) -> Option<&str> {
+        self.fields.reference.as_deref()
+    }
+
+    pub fn reference_link(&self) -> Option<&str> {
+        self.fields.reference_link.as_deref()
+    }
+
+    pub fn quantization(&self) -> Option<Quantization> {
+        *self.header.quantization()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.chunk_rows.values().map(|rows| rows.len()).sum()
+    }
+
+    /// Embeddings of the `occurrence`-th chunk with text hash `hash`, in node
+    /// id order, dequantized for quantized graphs.
+    pub fn chunk_embedding(&self, hash: &str, occurrence: usize) -> Result<Option<Vec<f32>>> {
+        match self
+            .chunk_rows
+            .get(hash)
+            .and_then(|rows| rows.get(occurrence))
+        {
+            Some(&row) => Ok(Some(self.read_row(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Embeddings of a sub-chunk with text hash `hash`, dequantized for
+    /// quantized graphs.
+    pub fn sub_chunk_embedding(&self, hash: &str) -> Result<Option<Vec<f32>>> {
+        match self.sub_chunk_rows.get(hash) {
+            Some(&row) => Ok(Some(self.read_row(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Reads row `row` of the embedding matrix.
+    fn read_row(&self, row: usize) -> Result<Vec<f32>> {
+        let row_len = self.header.row_len();
+        let mut bytes = vec![0; row_len];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start((GRAPH_HEADER_LEN + row * row_len) as u64))?;
+        file.read_exact(&mut bytes)?;
+        let quantized = self.fields.quantized.as_ref();
+        let embeddings = match (self.header.quantization(), quantized) {
+            (None, _) => bytes
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+                .collect(),
+            (Some(_), Some(quantized)) => quantized.calibration.decode(&bytes),
+            (Some(_), None) => anyhow::bail!("quantized graph without calibration"),
+        };
+        Ok(embeddings)
+    }
+}
@@ -0,0 +1,740 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use common::generate_id;
+use tracing::info;
+
+use super::{Spool, SpooledGraph, StoredGraph, TempPath};
+use crate::{
+    ann::{HnswIndex, HnswParams},
+    embedding::EmbeddingStats,
+    fingerprint::{DocumentFingerprint, MinHasher},
+    graph::{Edge, EdgeKind, Graph, GraphWriter, Node, NodeId},
+    indexer::{
+        chunk_node,
+        hash_occurrences,
+        semantic_edges,
+        structural_edges,
+        RECALL_K,
+        RECALL_QUERIES,
+    },
+    lexical::Bm25Writer,
+    math,
+    quantization::{
+        Calibration,
+        Quantization,
+        QuantizationReport,
+        QuantizedEmbeddings,
+        DEFAULT_RESCORE_FACTOR,
+    },
+    Indexer,
+    IndexingMeta,
+    IndexingReport,
+    IndexingSection,
+    Result,
+};
+
+// Chunks embedded together. Sections are never split across batches.
+const BATCH_CHUNKS: usize = 64;
+
+/// Indexes sections as they are read, a batch at a time. Embeddings are
+/// spooled to a `GraphWriter`, and nodes, edges and BM25 postings to
+/// temporary files, so only node ids and hashes stay in memory while indexing.
+///
+/// Chunk rows are read back once all sections are indexed, to link the
+/// document to others and to build its ANN index or quantized embeddings and
+/// its semantic edges like `Indexer::reindex`. Memory then grows with the
+/// chunk embeddings only, or with their codes for quantized graphs, whose
+/// calibration is fitted over the spooled chunk and sub-chunk rows.
+/// Embeddings of an earlier graph of the document set by `set_previous` are
+/// reused, read a row at a time.
+pub struct StreamingIndexer<'i> {
+    indexer: &'i Indexer,
+    meta: IndexingMeta,
+    graph_id: String,
+    files: SpoolFiles,
+    writer: GraphWriter,
+    nodes: Spool<Node>,
+    sections: Spool<Node>,
+    sub_chunks: Spool<Node>,
+    edges: Spool<(NodeId, Vec<Edge>)>,
+    lexical_index: Bm25Writer,
+    previous: Option<StoredGraph>,
+    previous_nodes: usize,
+    batch: Vec<IndexingSection>,
+    chunk_ids: HashSet<NodeId>,
+    section_ids: HashSet<NodeId>,
+    /// Chunks seen by text hash, to reuse the embeddings of the n-th earlier
+    /// chunk with the same text.
+    hash_counts: HashMap<String, usize>,
+    chunks: usize,
+    sections_count: usize,
+    kept: usize,
+    /// Edges of the last indexed chunk, spooled once the next chunk is known.
+    last_edges: Option<(NodeId, Vec<Edge>)>,
+    embedding_sum: Vec<f32>,
+    min_hasher: MinHasher,
+    stats: Option<EmbeddingStats>,
+    /// Chunks with their embeddings or codes, read back once all sections are
+    /// indexed.
+    chunk_graph: Option<Graph>,
+    quantization_report: Option<QuantizationReport>,
+}
+
+// Temporary files of the indexer, removed when it is dropped.
+struct SpoolFiles {
+    matrix: TempPath,
+    metadata: TempPath,
+    nodes: TempPath,
+    sections: TempPath,
+    sub_chunks: TempPath,
+    edges: TempPath,
+    postings: TempPath,
+}
+
+impl<'i> StreamingIndexer<'i> {
+    pub fn new(indexer: &'i Indexer, meta: IndexingMeta) -> Result<Self> {
+        let files = SpoolFiles {
+            matrix: TempPath::new("matrix"),
+            metadata: TempPath::new("json"),
+            nodes: TempPath::new("jsonl"),
+            sections: TempPath::new("jsonl"),
+            sub_chunks: TempPath::new("jsonl"),
+            edges: TempPath::new("jsonl"),
+            postings: TempPath::new("jsonl"),
+        };
+        let writer = GraphWriter::new(
+            read_write(files.matrix.path())?,
+            read_write(files.metadata.path())?,
+        );
+        Ok(Self {
+            indexer,
+            meta,
+            graph_id: generate_id(),
+            writer,
+            nodes: Spool::create(files.nodes.path())?,
+            sections: Spool::create(files.sections.path())?,
+            sub_chunks: Spool::create(files.sub_chunks.path())?,
+            edges: Spool::create(files.edges.path())?,
+            lexical_index: Bm25Writer::create(files.postings.path())?,
+            files,
+            previous: None,
+            previous_nodes: 0,
+            batch: vec![],
+            chunk_ids: HashSet::new(),
+            section_ids: HashSet::new(),
+            hash_counts: HashMap::new(),
+            chunks: 0,
+            sections_count: 0,
+            kept: 0,
+            last_edges: None,
+            embedding_sum: vec![],
+            min_hasher: MinHasher::new(),
+            stats: indexer.model().stats(),
+            chunk_graph: None,
+            quantization_report: None,
+        })
+    }
+
+    /// Reuses the embeddings of `previous`, the graph of an earlier version of
+    /// the document, for unchanged chunks and sub-chunks. Graphs of another
+    /// model or binary quantized are not reused.
+    pub fn set_previous(&mut self, previous: StoredGraph) {
+        self.previous_nodes = previous.node_count();
+        let model_id = self.indexer.model().model_id();
+        if previous.index_model() != Some(model_id.as_str()) {
+            return;
+        }
+        if previous.quantization() == Some(Quantization::Binary) {
+            info!("embeddings of binary quantized graph not reused");
+            return;
+        }
+        self.previous = Some(previous);
+    }
+
+    /// Adds the next section of the document. Buffered sections are indexed
+    /// once they hold `BATCH_CHUNKS` chunks.
+    pub async fn push(&mut self, section: IndexingSection) -> Result<()> {
+        if self.chunk_graph.is_some() {
+            anyhow::bail!("section pushed after the graph was linked");
+        }
+        for chunk in section.chunks() {
+            self.min_hasher.add(chunk);
+        }
+        self.batch.push(section);
+        let buffered = self
+            .batch
+            .iter()
+            .map(|section| section.chunks().len())
+            .sum::<usize>();
+        if buffered >= BATCH_CHUNKS {
+            self.index_batch().await?;
+        }
+        Ok(())
+    }
+
+    /// Adds cross-document edges to `other` like `Indexer::link_document`.
+    /// No section can be pushed afterwards.
+    pub async fn link_document(&mut self, other: &Graph) -> Result<()> {
+        self.index_batch().await?;
+        let indexer = self.indexer;
+        indexer.link_document(self.chunk_graph()?, other);
+        Ok(())
+    }
+
+    /// Indexes the buffered sections and writes the graph to `out`. Returns
+    /// the fingerprint of the document with the report, to be stored next to
+    /// the graph, and the ANN index of graphs that are not quantized.
+    pub async fn finish(
+        mut self,
+        out: impl Write,
+    ) -> Result<(IndexingReport, DocumentFingerprint, Option<HnswIndex>)> {
+        self.index_batch().await?;
+        if let Some(last_edges) = self.last_edges.take() {
+            self.edges.push(&last_edges)?;
+        }
+        self.chunk_graph()?;
+        let mut chunk_graph = self.chunk_graph.take().unwrap();
+        let quantized = chunk_graph.quantized().clone();
+        let mut edges = std::mem::take(chunk_graph.edge_map_mut());
+        for (id, edge) in semantic_edges(&chunk_graph) {
+            edges.entry(id).or_default().push(edge);
+        }
+        let ann_index = chunk_graph.ann_index().clone();
+        drop(chunk_graph);
+
+        let Self {
+            indexer,
+            meta,
+            graph_id,
+            files,
+            writer,
+            nodes,
+            sections,
+            sub_chunks,
+            edges: spooled_edges,
+            lexical_index,
+            previous_nodes,
+            hash_counts,
+            chunk_ids,
+            kept,
+            embedding_sum,
+            min_hasher,
+            stats,
+            quantization_report,
+            ..
+        } = self;
+        nodes.finish()?;
+        sections.finish()?;
+        sub_chunks.finish()?;
+        spooled_edges.finish()?;
+        let min_hash = min_hasher.finish();
+        let graph = SpooledGraph {
+            id: &graph_id,
+            title: meta.title(),
+            metadata: meta.metadata(),
+            nodes: files.nodes.path(),
+            sections: files.sections.path(),
+            sub_chunks: files.sub_chunks.path(),
+            spooled_edges: files.edges.path(),
+            edges: RefCell::new(edges),
+            index_model: indexer.model().model_id(),
+            embeddings: math::centroid(&[embedding_sum.as_slice()]),
+            min_hash: min_hash.clone(),
+            reference: meta.id(),
+            reference_link: meta.external_link(),
+            lexical_index: &lexical_index,
+            quantized: quantized
+                .as_ref()
+                .map(|quantized| quantized.without_codes()),
+        };
+        writer.finish(&graph, quantized.as_ref(), out)?;
+
+        let chunk_hashes = hash_counts.into_keys().collect();
+        let fingerprint = DocumentFingerprint::new(meta.id(), min_hash, chunk_hashes);
+        let report = IndexingReport::builder()
+            .added(chunk_ids.len() - kept)
+            .kept(kept)
+            .removed(previous_nodes.saturating_sub(kept))
+            .quantization(quantization_report)
+            .embedding(indexer.embedding_stats(stats))
+            .build();
+        Ok((report, fingerprint, ann_index))
+    }
+
+    // Graph of the chunks, read back from the spooled rows the first time,
+    // once all sections are indexed. Chunks keep their embeddings, searched
+    // through an ANN index, or only their codes when the graph is quantized.
+    fn chunk_graph(&mut self) -> Result<&mut Graph> {
+        if self.chunk_graph.is_none() {
+            let mut chunk_ids = self.chunk_ids.iter().cloned().collect::<Vec<NodeId>>();
+            chunk_ids.sort();
+            let quantization = self.indexer.quantization();
+            let mut rows = HashMap::new();
+            if quantization.is_none() {
+                let chunk_set = &self.chunk_ids;
+                self.writer.scan_rows(|id, embeddings| {
+                    if chunk_set.contains(id) {
+                        rows.insert(id.clone(), embeddings);
+                    }
+                    Ok(())
+                })?;
+            }
+            let ann_index = quantization.is_none().then(|| {
+                let vectors = chunk_ids
+                    .iter()
+                    .map(|id| rows[id].as_slice())
+                    .collect::<Vec<&[f32]>>();
+                HnswIndex::build_rows(chunk_ids.clone(), &vectors, HnswParams::default())
+            });
+            let node_map = chunk_ids
+                .into_iter()
+                .map(|id| {
+                    let node = Node::builder()
+                        .embeddings(rows.remove(&id).unwrap_or_default())
+                        .id(id)
+                        .data(String::new())
+                        .rank_id(String::new())
+                        .hash(String::new())
+                        .build();
+                    (node.id().to_string(), node)
+                })
+                .collect::<HashMap<NodeId, Node>>();
+            let mut graph = Graph::builder()
+                .id(self.graph_id.clone())
+                .title(self.meta.title().to_string())
+                .node_map(node_map)
+                .index_model(Some(self.indexer.model().model_id()))
+                .reference(Some(self.meta.id().to_string()))
+                .build();
+            graph.set_ann_index(ann_index);
+            if let Some(quantization) = quantization {
+                let (quantized, report) =
+                    quantize_rows(&mut self.writer, &self.section_ids, &graph, quantization)?;
+                graph.set_quantized(Some(quantized));
+                self.quantization_report = Some(report);
+            }
+            self.chunk_graph = Some(graph);
+        }
+        Ok(self.chunk_graph.as_mut().unwrap())
+    }
+
+    // Embeds the chunks and sub-chunks of the buffered sections, reusing the
+    // embeddings of the previous graph, and spools their embeddings, nodes,
+    // edges and postings with those of the section nodes.
+    async fn index_batch(&mut self) -> Result<()> {
+        let sections = std::mem::take(&mut self.batch);
+        let reference = self.meta.id().to_string();
+        let mut node_map = HashMap::new();
+        let mut chunk_ids = vec![];
+        let mut chunk_positions = HashMap::new();
+        let mut section_ids = vec![];
+        let mut texts = vec![];
+        let mut hashes = vec![];
+        let mut reused = vec![];
+        for section in &sections {
+            let section_id = section
+                .title()
+                .as_ref()
+                .map(|_| common::generate_id_with_data(section.text()));
+            for (index, text) in section.chunks().iter().enumerate() {
+                let hash = common::hash(text.as_bytes());
+                let occurrence = self.hash_counts.entry(hash.clone()).or_default();
+                *occurrence += 1;
+                let previous = match &self.previous {
+                    Some(previous) => previous.chunk_embedding(&hash, *occurrence - 1)?,
+                    None => None,
+                };
+                let mut node = chunk_node(
+                    text,
+                    hash.clone(),
+                    &reference,
+                    self.chunks,
+                    section_id.clone(),
+                    section.heading_path(),
+                );
+                node.span = section.chunk_span(index);
+                self.chunks += 1;
+                if self.chunk_ids.contains(node.id()) || node_map.contains_key(node.id()) {
+                    continue;
+                }
+                if previous.is_some() {
+                    self.kept += 1;
+                }
+                if let Some(positions) = section.chunk_positions().get(index) {
+                    chunk_positions.insert(node.id().to_string(), positions.as_slice());
+                }
+                texts.push(text.as_str());
+                hashes.push(hash);
+                reused.push(previous);
+                chunk_ids.push(node.id().to_string());
+                node_map.insert(node.id().to_string(), node);
+            }
+            section_ids.push(section_id);
+        }
+        if chunk_ids.is_empty() {
+            self.sections_count += sections.len();
+            return Ok(());
+        }
+
+        // Chunks are looked up by their occurrence in the whole document
+        // above, texts of this batch by their occurrence in the batch.
+        let keys = hash_occurrences(hashes.iter().map(|hash| hash.as_str()));
+        let reused = keys
+            .into_iter()
+            .zip(reused)
+            .filter_map(|(key, embeddings)| Some((key, embeddings?)))
+            .collect::<HashMap<(&str, usize), Vec<f32>>>();
+        let embeddings = self
+            .indexer
+            .embed_reusing(&texts, &hashes, &|hash, occurrence| {
+                Ok(reused.get(&(hash, occurrence)).cloned())
+            })
+            .await?;
+        for (id, embeddings) in chunk_ids.iter().zip(embeddings) {
+            node_map.get_mut(id).unwrap().embeddings = embeddings;
+        }
+        let previous = self.previous.as_ref();
+        let mut sub_chunk_map =
+            self.indexer
+                .index_sub_chunks(&mut node_map, &chunk_ids, &chunk_positions, &|hash, _| {
+                    match previous {
+                        Some(previous) => previous.sub_chunk_embedding(hash),
+                        None => Ok(None),
+                    }
+                })
+                .await?;
+
+        for (index, (section, id)) in sections.iter().zip(&section_ids).enumerate() {
+            let Some(id) = id.as_ref().filter(|id| !self.section_ids.contains(*id)) else {
+                continue;
+            };
+            let chunks = chunk_ids
+                .iter()
+                .map(|chunk_id| &node_map[chunk_id])
+                .filter(|node| node.parent_id().as_ref() == Some(id))
+                .collect::<Vec<&Node>>();
+            if chunks.is_empty() {
+                continue;
+            }
+            let embeddings = chunks
+                .iter()
+                .map(|node| node.embeddings().as_slice())
+                .collect::<Vec<&[f32]>>();
+            let mut node = Node::builder()
+                .id(id.to_string())
+                .hash(common::hash(section.text().as_bytes()))
+                .rank_id(format!(
+                    "{}::section::{}",
+                    reference,
+                    self.sections_count + index
+                ))
+                .reference(Some(reference.to_string()))
+                .data(section.text().to_string())
+                .embeddings(math::centroid(&embeddings))
+                .parent_id(Some(self.graph_id.clone()))
+                .heading_path(section.heading_path().clone())
+                .span(section.span().clone())
+                .sub_chunk_ids(
+                    chunks
+                        .iter()
+                        .flat_map(|node| node.sub_chunk_ids().clone())
+                        .collect(),
+                )
+                .build();
+            self.writer.push(&mut node)?;
+            self.sections.push(&node)?;
+            self.section_ids.insert(node.id().to_string());
+        }
+        self.sections_count += sections.len();
+
+        let mut edges = structural_edges(&chunk_ids, &node_map);
+        let first_chunk_id = chunk_ids[0].clone();
+        if let Some((last_chunk_id, mut last_edges)) = self.last_edges.take() {
+            let next = Edge::builder()
+                .kind(EdgeKind::Next)
+                .target(first_chunk_id.clone())
+                .build();
+            last_edges.push(next);
+            self.edges.push(&(last_chunk_id.clone(), last_edges))?;
+            let previous = Edge::builder()
+                .kind(EdgeKind::Previous)
+                .target(last_chunk_id)
+                .build();
+            edges.entry(first_chunk_id).or_default().push(previous);
+        }
+        let last_chunk_id = chunk_ids.last().unwrap();
+        self.last_edges = Some((
+            last_chunk_id.clone(),
+            edges.remove(last_chunk_id).unwrap_or_default(),
+        ));
+        for id in &chunk_ids {
+            if let Some(node_edges) = edges.remove(id) {
+                self.edges.push(&(id.clone(), node_edges))?;
+            }
+        }
+
+        self.lexical_index
+            .push(chunk_ids.iter().map(|id| &node_map[id]))?;
+        for id in &chunk_ids {
+            let mut node = node_map.remove(id).unwrap();
+            if self.embedding_sum.is_empty() {
+                self.embedding_sum = vec![0.0; node.embeddings().len()];
+            }
+            for (sum, value) in self.embedding_sum.iter_mut().zip(node.embeddings()) {
+                *sum += value;
+            }
+            self.writer.push(&mut node)?;
+            self.nodes.push(&node)?;
+            self.chunk_ids.insert(id.to_string());
+        }
+        let mut sub_chunk_ids = sub_chunk_map.keys().cloned().collect::<Vec<NodeId>>();
+        sub_chunk_ids.sort();
+        for id in sub_chunk_ids {
+            let mut node = sub_chunk_map.remove(&id).unwrap();
+            self.writer.push(&mut node)?;
+            self.sub_chunks.push(&node)?;
+        }
+
+        Ok(())
+    }
+}
+
+// File opened for reading and writing, truncated.
+fn read_write(path: &Path) -> Result<File> {
+    Ok(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?)
+}
+
+// Quantized embeddings of the chunks of `graph`, calibrated over the chunk
+// and sub-chunk rows spooled to `writer` and encoded a row at a time, with
+// their recall measured against exact scans of the chunk rows.
+fn quantize_rows(
+    writer: &mut GraphWriter,
+    section_ids: &HashSet<NodeId>,
+    graph: &Graph,
+    quantization: Quantization,
+) -> Result<(QuantizedEmbeddings, QuantizationReport)> {
+    let dimension = writer.dimension().unwrap_or_default();
+    let calibration = Calibration::fit_scan(quantization, dimension, |visit| {
+        writer.scan_rows(|id, embeddings| {
+            if !section_ids.contains(id) {
+                visit(&embeddings);
+            }
+            Ok(())
+        })
+    })?;
+
+    let mut node_ids = graph.node_map().keys().cloned().collect::<Vec<NodeId>>();
+    node_ids.sort();
+    let positions = node_ids
+        .iter()
+        .enumerate()
+        .map(|(position, id)| (id.as_str(), position))
+        .collect::<HashMap<&str, usize>>();
+    let row_len = quantization.row_len(dimension);
+    let mut codes = vec![0; node_ids.len() * row_len];
+    let mut code = vec![];
+    let mut queries = vec![];
+    writer.scan_rows(|id, embeddings| {
+        if let Some(&position) = positions.get(id.as_str()) {
+            code.clear();
+            calibration.encode(&embeddings, &mut code);
+            codes[position * row_len..(position + 1) * row_len].copy_from_slice(&code);
+            if queries.len() < RECALL_QUERIES {
+                queries.push(embeddings);
+            }
+        }
+        Ok(())
+    })?;
+
+    // Closest chunks of every query by position, truncated as they grow.
+    let mut closest: Vec<Vec<(f32, usize)>> = vec![vec![]; queries.len()];
+    writer.scan_rows(|id, embeddings| {
+        if let Some(&position) = positions.get(id.as_str()) {
+            for (query, closest) in queries.iter().zip(&mut closest) {
+                let similarity = math::cosine_similarity_slice(query, &embeddings);
+                closest.push((similarity, position));
+                if closest.len() >= RECALL_K * 4 {
+                    closest.sort_by(|a, b| b.0.total_cmp(&a.0));
+                    closest.truncate(RECALL_K);
+                }
+            }
+        }
+        Ok(())
+    })?;
+    let exact = closest
+        .into_iter()
+        .map(|mut closest| {
+            closest.sort_by(|a, b| b.0.total_cmp(&a.0));
+            closest
+                .into_iter()
+                .take(RECALL_K)
+                .map(|(_, position)| node_ids[position].clone())
+                .collect::<HashSet<NodeId>>()
+        })
+        .collect::<Vec<HashSet<NodeId>>>();
+
+    let quantized = QuantizedEmbeddings::from_codes(calibration, dimension, node_ids, codes);
+    let queries = queries
+        .iter()
+        .map(|query| query.as_slice())
+        .collect::<Vec<&[f32]>>();
+    let report =
+        quantized.evaluate_against(graph, &queries, &exact, RECALL_K, DEFAULT_RESCORE_FACTOR);
+    Ok((quantized, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use super::*;
+    use crate::{fixtures::HashProvider, EmbeddingModel};
+
+    fn meta() -> IndexingMeta {
+        IndexingMeta::builder()
+            .id("document".to_string())
+            .title("document".to_string())
+            .external_link(String::new())
+            .build()
+    }
+
+    // Titled sections of ten chunks, more than a batch in all.
+    fn sections() -> Vec<IndexingSection> {
+        (0..8)
+            .map(|section| {
+                let chunks = (0..10)
+                    .map(|chunk| format!("chunk {chunk} of section {section}"))
+                    .collect::<Vec<String>>();
+                IndexingSection::builder()
+                    .title(Some(format!("Section {section}")))
+                    .depth(1)
+                    .text(chunks.join("\n"))
+                    .chunks(chunks)
+                    .build()
+            })
+            .collect()
+    }
+
+    // Sequential edges of `graph` as (source, kind, target).
+    fn sequential_edges(graph: &Graph) -> HashSet<(&str, EdgeKind, &str)> {
+        graph
+            .node_map()
+            .values()
+            .flat_map(|node| {
+                graph
+                    .edges(node)
+                    .iter()
+                    .filter(|edge| matches!(edge.kind(), EdgeKind::Next | EdgeKind::Previous))
+                    .map(|edge| (node.id().as_str(), *edge.kind(), edge.target().as_str()))
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn streamed_graph_matches_indexed_graph() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let indexer = Indexer::new(EmbeddingModel::Custom(provider)).unwrap();
+        let (indexed, _) = indexer.reindex(sections(), meta(), None).await.unwrap();
+
+        let mut streaming = StreamingIndexer::new(&indexer, meta()).unwrap();
+        for section in sections() {
+            streaming.push(section).await.unwrap();
+        }
+        let mut bytes = vec![];
+        let (report, fingerprint, _) = streaming.finish(&mut bytes).await.unwrap();
+        let streamed = Graph::from_slice(&bytes).unwrap();
+
+        assert_eq!(*report.added(), 80);
+        assert_eq!(streamed.node_map().len(), 80);
+        assert_eq!(streamed.section_map().len(), 8);
+        assert_eq!(
+            streamed.sub_chunk_map().len(),
+            indexed.sub_chunk_map().len()
+        );
+        for node in indexed.node_map().values() {
+            let streamed_node = &streamed.node_map()[node.id()];
+            assert_eq!(streamed_node.embeddings(), node.embeddings());
+            assert_eq!(streamed_node.rank_id(), node.rank_id());
+        }
+        assert_eq!(sequential_edges(&streamed), sequential_edges(&indexed));
+        assert_eq!(streamed.min_hash(), indexed.min_hash());
+        assert_eq!(fingerprint.min_hash(), streamed.min_hash());
+    }
+
+    #[tokio::test]
+    async fn streamed_quantized_graph_matches_indexed_graph() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let mut indexer = Indexer::new(EmbeddingModel::Custom(provider)).unwrap();
+        indexer.set_quantization(Some(Quantization::Int8));
+        let (indexed, _) = indexer.reindex(sections(), meta(), None).await.unwrap();
+        let indexed = Graph::from_slice(&indexed.to_bytes().unwrap()).unwrap();
+
+        let mut streaming = StreamingIndexer::new(&indexer, meta()).unwrap();
+        for section in sections() {
+            streaming.push(section).await.unwrap();
+        }
+        let mut bytes = vec![];
+        let (report, _, ann_index) = streaming.finish(&mut bytes).await.unwrap();
+        let streamed = Graph::from_slice(&bytes).unwrap();
+
+        assert!(ann_index.is_none());
+        let quantization = report.quantization().as_ref().unwrap();
+        assert_eq!(*quantization.queries(), 80);
+        assert!(*quantization.recall() > 0.0);
+        let calibration = |graph: &Graph| {
+            serde_json::to_string(graph.quantized().as_ref().unwrap().calibration()).unwrap()
+        };
+        assert_eq!(calibration(&streamed), calibration(&indexed));
+        for node in indexed.node_map().values() {
+            let streamed_node = &streamed.node_map()[node.id()];
+            assert_eq!(streamed.embedding(streamed_node), indexed.embedding(node));
+        }
+        for sub_chunk in indexed.sub_chunk_map().values() {
+            let streamed_sub_chunk = &streamed.sub_chunk_map()[sub_chunk.id()];
+            assert_eq!(
+                streamed.embedding(streamed_sub_chunk),
+                indexed.embedding(sub_chunk)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn previous_graph_embeddings_are_reused() {
+        let provider = Arc::new(HashProvider::new("fake::a", 8));
+        let indexer = Indexer::new(EmbeddingModel::Custom(provider.clone())).unwrap();
+        let (indexed, _) = indexer.reindex(sections(), meta(), None).await.unwrap();
+        let previous = TempPath::new("bin");
+        std::fs::write(previous.path(), indexed.to_bytes().unwrap()).unwrap();
+        let embedded = provider.embedded();
+
+        let mut streaming = StreamingIndexer::new(&indexer, meta()).unwrap();
+        streaming.set_previous(StoredGraph::open(previous.path()).unwrap());
+        for section in sections() {
+            streaming.push(section).await.unwrap();
+        }
+        let mut bytes = vec![];
+        let (report, _, _) = streaming.finish(&mut bytes).await.unwrap();
+        let streamed = Graph::from_slice(&bytes).unwrap();
+
+        assert_eq!(provider.embedded(), embedded);
+        assert_eq!(*report.added(), 0);
+        assert_eq!(*report.kept(), 80);
+        for node in indexed.node_map().values() {
+            assert_eq!(
+                streamed.node_map()[node.id()].embeddings(),
+                node.embeddings()
+            );
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::{Path, PathBuf};
+
+/// Path of a file in the temporary directory, removed when dropped.
+#[derive(Debug)]
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(extension: &str) -> Self {
+        let filename = format!("{}.{}", common::generate_id(), extension);
+        Self(std::env::temp_dir().join(filename))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -0,0 +1,177 @@
+use tiktoken_rs::{cl100k_base, get_bpe_from_model, model::get_context_size, CoreBPE};
+
+use crate::Result;
+
+// Characters ending a sentence, where packed texts may be truncated.
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', '\n', '。', '！', '？', '；'];
+
+/// Prompt token budget, counted with the tiktoken encoding of a chat model.
+pub struct TokenBudget {
+    bpe: CoreBPE,
+    available: usize,
+}
+
+impl TokenBudget {
+    /// Budget of `max_tokens` counted with the encoding of `model`, e.g.
+    /// `gpt-3.5-turbo`. Unknown models are counted with `cl100k_base`.
+    pub fn new(model: &str, max_tokens: usize) -> Result<Self> {
+        let bpe = match get_bpe_from_model(model) {
+            Ok(bpe) => bpe,
+            Err(_) => cl100k_base()?,
+        };
+        Ok(Self {
+            bpe,
+            available: max_tokens,
+        })
+    }
+
+    /// Budget of the whole context window of `model`.
+    pub fn for_model(model: &str) -> Result<Self> {
+        Self::new(model, get_context_size(model))
+    }
+
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+
+    /// Sets aside `tokens`, e.g. for the model output.
+    pub fn reserve(&mut self, tokens: usize) {
+        self.available = self.available.saturating_sub(tokens);
+    }
+
+    /// Sets aside the tokens of a fixed part of the prompt, e.g. the system
+    /// prompt and the query.
+    pub fn reserve_text(&mut self, text: &str) {
+        self.reserve(self.count(text));
+    }
+
+    /// Caps the budget to `max_tokens`.
+    pub fn limit(&mut self, max_tokens: usize) {
+        self.available = self.available.min(max_tokens);
+    }
+
+    /// Longest prefix of `texts`, in order, that fits in the budget once
+    /// joined by newlines. With `truncate`, the first text that does not fit
+    /// is cut after its last fitting sentence instead of being dropped.
+    pub fn pack<'t>(&self, texts: &[&'t str], truncate: bool) -> Vec<&'t str> {
+        let mut packed = vec![];
+        let mut used = 0;
+        for text in texts {
+            // One token for the newline joining the texts.
+            let tokens = self.count(text) + packed.len().min(1);
+            if used + tokens <= self.available {
+                used += tokens;
+                packed.push(*text);
+                continue;
+            }
+            if truncate {
+                let remaining = self.available.saturating_sub(used + packed.len().min(1));
+                if let Some(prefix) = self.truncate(text, remaining) {
+                    packed.push(prefix);
+                }
+            }
+            break;
+        }
+        packed
+    }
+
+    // Longest non-empty prefix of `text` ending a sentence within `tokens`.
+    // The text is encoded once and only sentences ending in the bytes of its
+    // first `tokens` tokens are counted, the last one fitting in practice.
+    fn truncate<'t>(&self, text: &'t str, tokens: usize) -> Option<&'t str> {
+        let encoded = self.bpe.encode_with_special_tokens(text);
+        let mut len = self
+            .bpe
+            ._decode_native(&encoded[..tokens.min(encoded.len())])
+            .len();
+        // The last token may end inside a character.
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        text[..len]
+            .char_indices()
+            .rev()
+            .filter(|(_, c)| SENTENCE_ENDS.contains(c))
+            .map(|(index, c)| &text[..index + c.len_utf8()])
+            .find(|prefix| self.count(prefix) <= tokens)
+            .filter(|prefix| !prefix.trim().is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(max_tokens: usize) -> TokenBudget {
+        TokenBudget::new("gpt-3.5-turbo", max_tokens).unwrap()
+    }
+
+    #[test]
+    fn packs_texts_that_fit() {
+        let texts = ["alpha beta gamma", "delta epsilon", "zeta"];
+        let counter = budget(0);
+        let joined = counter.count(texts[0]) + 1 + counter.count(texts[1]);
+
+        assert_eq!(budget(1000).pack(&texts, false), texts);
+        assert_eq!(budget(joined).pack(&texts, false), texts[..2]);
+        assert_eq!(budget(joined - 1).pack(&texts, false), texts[..1]);
+        assert!(budget(0).pack(&texts, false).is_empty());
+    }
+
+    #[test]
+    fn stops_at_first_text_that_does_not_fit() {
+        let long = "word ".repeat(50);
+        let texts = ["short", long.as_str(), "tiny"];
+        let packed = budget(20).pack(&texts, false);
+        assert_eq!(packed, vec!["short"]);
+    }
+
+    #[test]
+    fn truncates_at_sentence_end() {
+        let text = "First sentence. Second sentence. Third sentence.";
+        let counter = budget(0);
+        let available = counter.count("First sentence. Second sentence.");
+
+        assert_eq!(
+            budget(available).pack(&[text], true),
+            vec!["First sentence. Second sentence."]
+        );
+        assert!(budget(available).pack(&[text], false).is_empty());
+        assert!(budget(1).pack(&[text], true).is_empty());
+
+        let first = "Intro.";
+        let available = counter.count(first) + 1 + counter.count("First sentence.");
+        assert_eq!(
+            budget(available).pack(&[first, text], true),
+            vec![first, "First sentence."]
+        );
+    }
+
+    #[test]
+    fn truncates_long_texts_within_the_budget() {
+        let text = "Ein Satz über Größen. ".repeat(2000);
+        let budget = budget(100);
+        let packed = budget.pack(&[text.as_str()], true);
+        assert_eq!(packed.len(), 1);
+        assert!(packed[0].ends_with('.'));
+        assert!(budget.count(packed[0]) <= 100);
+        assert!(budget.count(&format!("{} Ein Satz über Größen.", packed[0])) > 100);
+    }
+
+    #[test]
+    fn reserves_and_limits_tokens() {
+        let mut budget = budget(100);
+        budget.reserve(30);
+        assert_eq!(budget.available(), 70);
+        budget.reserve_text("hello");
+        assert_eq!(budget.available(), 70 - budget.count("hello"));
+        budget.limit(10);
+        assert_eq!(budget.available(), 10);
+        budget.reserve(20);
+        assert_eq!(budget.available(), 0);
+    }
+}
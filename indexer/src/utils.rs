@@ -1,187 +1,1263 @@
-use std::{fmt::format, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::format,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
-use aws_sdk_s3::{primitives::ByteStream, Client};
-use document::{chunking::OverlappedChunker, docx::DocxDcoumentPraser, pdf::PdfDocumentParser};
+use aws_sdk_s3::{
+    operation::get_object::{GetObjectError, GetObjectOutput},
+    primitives::ByteStream,
+    Client,
+};
+use document::{
+    chunking::OverlappedChunker,
+    document::{Document, DocumentMeta, Node as DocumentNode, Section, SectionSplitter},
+    docx::DocxDcoumentPraser,
+    pdf::PdfDocumentParser,
+};
 use rayon::prelude::*;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
-    graph::{Graph, Node},
+    ann::HnswIndex,
+    fingerprint::{self, DocumentFingerprint, DuplicateDocuments},
+    graph::{DocumentMetadata, EdgeKind, Graph, Node, SourceSpan},
     math,
+    quantization::Quantization,
+    rerank::CrossEncoderModel,
+    streaming::{
+        DocumentReader,
+        DocumentWriter,
+        SectionSpool,
+        StoredGraph,
+        StreamingIndexer,
+        TempPath,
+    },
     Context,
+    DiversityOptions,
     EmbeddingModel,
+    EmbeddingRegistry,
+    ExpansionOptions,
+    HybridOptions,
     Indexer,
     IndexingMeta,
+    IndexingReport,
+    IndexingSection,
     MiniLMEmbeddingModel,
     ModelId,
+    Provenance,
+    SearchFilter,
+    SearchOptions,
+    TokenBudget,
+    TransformerEmbeddingModel,
 };
 
 const GRAPH_FILENAME: &str = "embedding.json";
+const GRAPH_BINARY_FILENAME: &str = "embedding.bin";
+const ANN_INDEX_FILENAME: &str = "ann.json";
+const FINGERPRINT_FILENAME: &str = "fingerprint.json";
+const GRAPH_VERSION_FILENAME: &str = "graph_version";
+const GRAPH_PREVIOUS_VERSION_FILENAME: &str = "graph_previous_version";
+const GRAPH_VERSIONS_FOLDER: &str = "graphs";
 const DATA_FILENAME: &str = "data.json";
 const PDFIUM_LIB_PATH: &str = "lib/libpdfium.so";
+// PDF files from this many pages, parsed page batch by page batch, and DOCX
+// files from this many lines are indexed with bounded memory.
+const STREAMING_MIN_PAGES: usize = 200;
+const STREAMING_MIN_LINES: usize = 20_000;
+const PAGES_PER_BATCH: usize = 20;
+/// Token size of the chunks of indexed documents.
+pub const CHUNK_SIZE: usize = 500;
 
 use crate::Result;
 
+/// Indexes an uploaded file. Its chunks are linked to the closest chunks of the
+/// documents of `related_keys` by cross-document edges.
 pub async fn build_index(
     client: &Client,
     meta: &IndexingMeta,
     document_key: &str,
     file_key: &str,
     keep_file: bool,
-) -> Result<()> {
+    related_keys: &[String],
+) -> Result<IndexingReport> {
     let bucket_name = common::vars::get_app_document_bucket()?;
     let resources_path = common::vars::get_app_resources_path()?;
     let filename = file_key.split("/").last().unwrap();
 
     // Download task
     let output = s3_helper::download_object(&client, &bucket_name, &file_key).await?;
-    let data = output.body.collect().await.map(|data| data.into_bytes())?;
-
-    let document = if filename.ends_with(".docx") {
-        let parser = DocxDcoumentPraser::new();
-        let document = parser.parse(data.to_vec())?;
-
-        document
-    } else if filename.ends_with(".pdf") {
-        let mut pdfium_lib_path = PathBuf::from(&resources_path);
-        pdfium_lib_path.push(PDFIUM_LIB_PATH);
-        let parser = PdfDocumentParser::new(pdfium_lib_path.to_str().unwrap())?;
-        let document = parser.parse(data.to_vec())?;
-        document
+    let data = TempPath::new("json");
+    let spool = TempPath::new("jsonl");
+    let document = if filename.ends_with(".pdf") {
+        let file = download_to_file(output.body, "pdf").await?;
+        let parser = pdf_parser(&resources_path)?;
+        let pages = parser.page_count(file.path())?;
+        match pages >= STREAMING_MIN_PAGES {
+            true => {
+                info!("start streaming index of {} pages: {}", pages, filename);
+                spool_document(data.path(), spool.path(), |push| {
+                    parser.parse_file_in_batches(file.path(), PAGES_PER_BATCH, |nodes| push(&nodes))
+                })?;
+                None
+            }
+            false => Some(parser.parse(std::fs::read(file.path())?)?),
+        }
     } else {
-        panic!("Unsupported file format.")
+        let data = output.body.collect().await.map(|data| data.into_bytes())?;
+        Some(parse_document(filename, data.to_vec(), &resources_path)?)
     };
-    info!("start index: {}", filename);
+    let document = match document {
+        Some(document) if document.text().lines().count() >= STREAMING_MIN_LINES => {
+            info!("start streaming index of parsed document: {}", filename);
+            spool_document(data.path(), spool.path(), |push| {
+                push(document.nodes())?;
+                Ok(document.meta().clone())
+            })?;
+            None
+        }
+        document => document,
+    };
+
+    let report = match document {
+        Some(document) => {
+            info!("start index: {}", filename);
+            index_document(
+                client,
+                &bucket_name,
+                &resources_path,
+                meta,
+                document_key,
+                document,
+                related_keys,
+            )
+            .await?
+        }
+        None => {
+            build_index_streaming(
+                client,
+                &bucket_name,
+                &resources_path,
+                meta,
+                document_key,
+                data.path(),
+                spool.path(),
+                related_keys,
+            )
+            .await?
+        }
+    };
+    if !keep_file {
+        s3_helper::delete_object(&client, &bucket_name, &file_key).await?;
+    }
+    Ok(report)
+}
+
+// Indexes a parsed document in memory and uploads its content to data.json.
+async fn index_document(
+    client: &Client,
+    bucket_name: &str,
+    resources_path: &str,
+    meta: &IndexingMeta,
+    document_key: &str,
+    document: Document,
+    related_keys: &[String],
+) -> Result<IndexingReport> {
     let content = serde_json::to_string(&document)?;
     let mut output_key = PathBuf::from(document_key);
     output_key.push(DATA_FILENAME);
     s3_helper::upload_object_with_content(
-        &client,
-        &bucket_name,
+        client,
+        bucket_name,
         output_key.to_str().unwrap(),
         ByteStream::from(content.as_bytes().to_vec()),
     )
     .await?;
     info!("uploaded txt file");
 
-    let chunker = OverlappedChunker::with_size(500);
-    let chunks = chunker.chunks(&document.all_text_in_lines());
-    let texts = chunks.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-    info!("splitted content");
+    let sections = indexing_sections(&document, CHUNK_SIZE);
+    info!("splitted content in {} sections", sections.len());
 
-    let model_id = ModelId::AllMiniLML6V2.to_string();
-    let model_name = model_id.split("::").last().unwrap_or("");
-    let mut path = PathBuf::from(&resources_path);
-    path.push("models");
-    path.push(model_name);
-    let model_path = path.to_str().unwrap();
-    let embedding_model = MiniLMEmbeddingModel::from_file(ModelId::AllMiniLML6V2, model_path)
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-    let model = EmbeddingModel::MiniLMEmbeddingModel(embedding_model);
-    info!("loaded model: {}", model_id);
+    let model = get_embedding_model(resources_path).await?;
+    info!("loaded model: {}", model.model_id());
+
+    let folder = graph_folder(client, bucket_name, document_key).await?;
+    let previous = download_graph(client, bucket_name, &folder).await?;
+    let report = index_sections(
+        client,
+        bucket_name,
+        document_key,
+        sections,
+        meta,
+        model,
+        previous.as_ref(),
+        related_keys,
+    )
+    .await?;
+    info!("indexed document: {}", document_key);
+    Ok(report)
+}
+
+/// Re-indexes a document with `model` from its parsed content in `data.json`,
+/// so the original file is not needed. The title, link and metadata are kept
+/// from its current graph, and its embeddings are reused when it was indexed
+/// with `model`. `data.json` is read a node at a time and indexed with bounded
+/// memory like large PDF files, and the new graph replaces the current one
+/// once fully uploaded.
+pub async fn rebuild_index(
+    client: &Client,
+    document_key: &str,
+    model: EmbeddingModel,
+) -> Result<IndexingReport> {
+    let bucket_name = common::vars::get_app_document_bucket()?;
+
+    let folder = graph_folder(&client, &bucket_name, document_key).await?;
+    // Graphs written by older versions are JSON, and loaded whole.
+    let (previous_file, previous) = download_stored_graph(&client, &bucket_name, &folder)
+        .await?
+        .unzip();
+    let (meta, index_model) = match &previous {
+        Some(previous) => (
+            previous_meta(
+                previous.reference(),
+                previous.title(),
+                previous.reference_link(),
+                previous.metadata(),
+            ),
+            previous.index_model().map(|model| model.to_string()),
+        ),
+        None => {
+            let previous = download_graph(&client, &bucket_name, &folder)
+                .await?
+                .ok_or(anyhow::anyhow!("no graph to rebuild: {}", document_key))?;
+            let meta = previous_meta(
+                previous.reference().as_deref(),
+                previous.title(),
+                previous.reference_link().as_deref(),
+                previous.metadata(),
+            );
+            (meta, previous.index_model().clone())
+        }
+    };
+    info!(
+        "rebuild index: {}, {} -> {}",
+        document_key,
+        index_model.as_deref().unwrap_or("unknown"),
+        model.model_id()
+    );
+
+    let data_file_key = format!("{}/{}", document_key, DATA_FILENAME);
+    let body = s3_helper::download_object(&client, &bucket_name, &data_file_key)
+        .await?
+        .body;
+    let data = download_to_file(body, "json").await?;
+
+    let spool = TempPath::new("jsonl");
+    let mut section_spool = SectionSpool::create(spool.path())?;
+    let mut splitter = SectionSplitter::new();
+    DocumentReader::new(BufReader::new(File::open(data.path())?)).for_each_node(|node| {
+        if let Some(section) = splitter.push(&node) {
+            section_spool.push(&section)?;
+        }
+        Ok(())
+    })?;
+    if let Some(section) = splitter.finish() {
+        section_spool.push(&section)?;
+    }
+    section_spool.finish()?;
+    drop(data);
+
+    let report = index_spooled_sections(
+        client,
+        &bucket_name,
+        document_key,
+        spool.path(),
+        &meta,
+        model,
+        previous,
+        &[],
+    )
+    .await?;
+    drop(previous_file);
+    Ok(report)
+}
+
+// Meta of a document re-indexed from the fields of its current graph.
+fn previous_meta(
+    reference: Option<&str>,
+    title: &str,
+    reference_link: Option<&str>,
+    metadata: &DocumentMetadata,
+) -> IndexingMeta {
+    IndexingMeta::builder()
+        .id(reference.map_or_else(common::generate_id, |reference| reference.to_string()))
+        .title(title.to_string())
+        .external_link(reference_link.unwrap_or_default().to_string())
+        .metadata(metadata.clone())
+        .build()
+}
+
+// Writes the nodes `parse` passes to its callback to data.json at `data` as
+// they come, and their sections to a spool at `spool`. `parse` returns the
+// document metadata once all nodes are passed.
+fn spool_document(
+    data: &Path,
+    spool: &Path,
+    parse: impl FnOnce(&mut dyn FnMut(&[DocumentNode]) -> Result<()>) -> Result<DocumentMeta>,
+) -> Result<()> {
+    let mut document_writer = DocumentWriter::new(BufWriter::new(File::create(data)?))?;
+    let mut section_spool = SectionSpool::create(spool)?;
+    let mut splitter = SectionSplitter::new();
+    let document_meta = parse(&mut |nodes| {
+        for node in nodes {
+            document_writer.push(node)?;
+            if let Some(section) = splitter.push(node) {
+                section_spool.push(&section)?;
+            }
+        }
+        Ok(())
+    })?;
+    if let Some(section) = splitter.finish() {
+        section_spool.push(&section)?;
+    }
+    section_spool.finish()?;
+    document_writer.finish(&document_meta)?;
+    Ok(())
+}
+
+// Indexes a large document with bounded memory from its content spooled by
+// `spool_document`. data.json is uploaded in parts, then the sections are
+// embedded in batches into a graph file, also uploaded in parts.
+async fn build_index_streaming(
+    client: &Client,
+    bucket_name: &str,
+    resources_path: &str,
+    meta: &IndexingMeta,
+    document_key: &str,
+    data: &Path,
+    spool: &Path,
+    related_keys: &[String],
+) -> Result<IndexingReport> {
+    let data_file_key = format!("{}/{}", document_key, DATA_FILENAME);
+    s3_helper::upload_object_multipart(&client, &bucket_name, data, &data_file_key).await?;
+    info!("uploaded txt file");
+
+    let model = get_embedding_model(&resources_path).await?;
+    info!("loaded model: {}", model.model_id());
+    let folder = graph_folder(&client, &bucket_name, document_key).await?;
+    let (previous_file, previous) = download_stored_graph(client, bucket_name, &folder)
+        .await?
+        .unzip();
+    let report = index_spooled_sections(
+        client,
+        bucket_name,
+        document_key,
+        spool,
+        meta,
+        model,
+        previous,
+        related_keys,
+    )
+    .await?;
+    drop(previous_file);
+    Ok(report)
+}
+
+// Embeds the sections of the spool at `spool` in batches into a graph file,
+// reusing the embeddings of `previous`, links it to the graphs of
+// `related_keys`, and uploads it in parts with its ANN index as a new graph
+// version of the document.
+async fn index_spooled_sections(
+    client: &Client,
+    bucket_name: &str,
+    document_key: &str,
+    spool: &Path,
+    meta: &IndexingMeta,
+    model: EmbeddingModel,
+    previous: Option<StoredGraph>,
+    related_keys: &[String],
+) -> Result<IndexingReport> {
+    let indexer = new_indexer(model)?;
+    let mut streaming = StreamingIndexer::new(&indexer, meta.clone())?;
+    if let Some(previous) = previous {
+        streaming.set_previous(previous);
+    }
+    let chunker = OverlappedChunker::with_size(CHUNK_SIZE);
+    for section in SectionSpool::read(spool)? {
+        streaming
+            .push(indexing_section(&section?, &chunker))
+            .await?;
+    }
+    for related_key in related_keys {
+        if let Some(other) = related_graph(client, bucket_name, related_key).await {
+            streaming.link_document(&other).await?;
+        }
+    }
+    let graph = TempPath::new("bin");
+    let (report, fingerprint, ann_index) = streaming
+        .finish(BufWriter::new(File::create(graph.path())?))
+        .await?;
+    info!(
+        "indexed doc: {}, added: {}, kept: {}, removed: {}",
+        document_key,
+        report.added(),
+        report.kept(),
+        report.removed()
+    );
+    log_quantization_report(&report);
+    log_embedding_stats(&report);
+
+    let version = common::generate_id();
+    let folder = version_folder(document_key, &version);
+    if let Some(ann_index) = &ann_index {
+        upload_ann_index(client, bucket_name, &folder, ann_index).await?;
+        info!("uploaded ann index: {}", document_key);
+    }
+    let graph_file_key = format!("{}/{}", folder, GRAPH_BINARY_FILENAME);
+    s3_helper::upload_object_multipart(&client, &bucket_name, graph.path(), &graph_file_key)
+        .await?;
+    upload_fingerprint(client, bucket_name, &folder, &fingerprint).await?;
+    switch_graph_version(client, bucket_name, document_key, &version).await?;
+    info!("uploaded indexed file: {}", document_key);
 
-    let indexer = Indexer::new(model).map_err(|e| anyhow::anyhow!(e.to_string()))?;
-    let graph = indexer
-        .index(texts, meta.clone())
+    Ok(report)
+}
+
+/// Parses a PDF or DOCX file, told apart by the extension of `filename`.
+pub fn parse_document(filename: &str, data: Vec<u8>, resources_path: &str) -> Result<Document> {
+    if filename.ends_with(".docx") {
+        let parser = DocxDcoumentPraser::new();
+        parser.parse(data)
+    } else if filename.ends_with(".pdf") {
+        pdf_parser(resources_path)?.parse(data)
+    } else {
+        anyhow::bail!("unsupported file format: {}", filename)
+    }
+}
+
+fn pdf_parser(resources_path: &str) -> Result<PdfDocumentParser> {
+    let mut pdfium_lib_path = PathBuf::from(resources_path);
+    pdfium_lib_path.push(PDFIUM_LIB_PATH);
+    PdfDocumentParser::new(pdfium_lib_path.to_str().unwrap())
+}
+
+/// Parses the PDF and DOCX files of `folder`, sorted by file name, with their
+/// file names. Files that fail to parse are skipped.
+pub fn parse_folder(folder: &Path, resources_path: &str) -> Result<Vec<(String, Document)>> {
+    let mut paths = std::fs::read_dir(folder)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    let mut documents = vec![];
+    for path in paths {
+        let Some(filename) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !(filename.ends_with(".pdf") || filename.ends_with(".docx")) {
+            continue;
+        }
+        match parse_document(filename, std::fs::read(&path)?, resources_path) {
+            Ok(document) => documents.push((filename.to_string(), document)),
+            Err(err) => warn!("skip document: {}: {}", filename, err),
+        }
+    }
+    Ok(documents)
+}
+
+/// Sections of `document` split in chunks of `chunk_size` tokens.
+pub fn indexing_sections(document: &Document, chunk_size: usize) -> Vec<IndexingSection> {
+    let chunker = OverlappedChunker::with_size(chunk_size);
+    document
+        .sections()
+        .iter()
+        .map(|section| indexing_section(section, &chunker))
+        .collect()
+}
+
+/// `section` split in chunks by `chunker`.
+pub fn indexing_section(section: &Section, chunker: &OverlappedChunker) -> IndexingSection {
+    let lines = section.lines();
+    let ranges = chunker.line_ranges_with_remainder(lines);
+    let chunks = ranges
+        .iter()
+        .map(|range| {
+            lines[range.clone()]
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect()
+        })
+        .collect();
+    // Sections parsed by older versions have no positions.
+    let chunk_positions = match section.positions().len() == lines.len() {
+        true => ranges
+            .into_iter()
+            .map(|range| section.positions()[range].to_vec())
+            .collect(),
+        false => vec![],
+    };
+    IndexingSection::builder()
+        .title(section.title().clone())
+        .depth(*section.depth())
+        .heading_path(section.path().clone())
+        .text(lines.join("\n"))
+        .chunks(chunks)
+        .span(SourceSpan::covering(section.positions()))
+        .chunk_positions(chunk_positions)
+        .build()
+}
+
+// Indexes `sections`, links them to the graphs of `related_keys` and uploads
+// the graph with its ANN index. The ANN index is written first, so readers
+// switch to the new graph and its index at once.
+async fn index_sections(
+    client: &Client,
+    bucket_name: &str,
+    document_key: &str,
+    sections: Vec<IndexingSection>,
+    meta: &IndexingMeta,
+    model: EmbeddingModel,
+    previous: Option<&Graph>,
+    related_keys: &[String],
+) -> Result<IndexingReport> {
+    let indexer = new_indexer(model)?;
+    let (mut graph, report) = indexer
+        .reindex(sections, meta.clone(), previous)
         .await
         .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-    info!("indexed doc: {}", &file_key);
-    let output_content = graph.to_string();
+    info!(
+        "indexed doc: {}, added: {}, kept: {}, removed: {}",
+        document_key,
+        report.added(),
+        report.kept(),
+        report.removed()
+    );
+    log_quantization_report(&report);
+    log_embedding_stats(&report);
 
-    let mut output_key = PathBuf::from(document_key);
-    output_key.push(GRAPH_FILENAME);
+    for related_key in related_keys {
+        if let Some(other) = related_graph(client, bucket_name, related_key).await {
+            indexer.link_document(&mut graph, &other);
+        }
+    }
+
+    // Files of the new version are uploaded before it is switched to, so
+    // readers never see a graph with the ANN index of another.
+    let version = common::generate_id();
+    let folder = version_folder(document_key, &version);
+    if let Some(ann_index) = graph.ann_index() {
+        upload_ann_index(client, bucket_name, &folder, ann_index).await?;
+        info!("uploaded ann index: {}", document_key);
+    }
 
+    let graph_file_key = format!("{}/{}", folder, GRAPH_BINARY_FILENAME);
     s3_helper::upload_object_with_content(
         &client,
         &bucket_name,
-        output_key.to_str().unwrap(),
-        ByteStream::from(output_content.as_bytes().to_vec()),
+        &graph_file_key,
+        ByteStream::from(graph.to_bytes()?),
     )
     .await?;
-    info!("uploaded indexed file: {}", file_key);
-    info!("indexed document: {}", document_key);
+    let fingerprint = DocumentFingerprint::from_graph(document_key, &graph);
+    upload_fingerprint(client, bucket_name, &folder, &fingerprint).await?;
+    switch_graph_version(client, bucket_name, document_key, &version).await?;
+    info!("uploaded indexed file: {}", document_key);
 
-    if !keep_file {
-        s3_helper::delete_object(&client, &bucket_name, &file_key).await?;
+    Ok(report)
+}
+
+// Indexer of `model`, quantizing embeddings as set by
+// `APP_EMBEDDING_QUANTIZATION`.
+fn new_indexer(model: EmbeddingModel) -> Result<Indexer> {
+    let mut indexer = Indexer::new(model).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    if let Ok(quantization) = common::vars::get_app_embedding_quantization() {
+        indexer.set_quantization(Some(Quantization::from_str(&quantization)?));
     }
+    Ok(indexer)
+}
+
+// Current graph of the document `document_key` with its ANN index, to link
+// another document to. Related graphs are loaded one at a time to bound
+// memory, with their ANN index to find the closest chunks without scanning
+// them. Graphs that fail to load are skipped.
+async fn related_graph(client: &Client, bucket_name: &str, document_key: &str) -> Option<Graph> {
+    let other = match graph_folder(&client, &bucket_name, document_key).await {
+        Ok(folder) => download_graph(&client, &bucket_name, &folder)
+            .await
+            .map(|other| other.map(|other| (folder, other))),
+        Err(err) => Err(err),
+    };
+    match other {
+        Ok(Some((folder, mut other))) => {
+            let ann_index = load_ann_index_from_s3(&client, &bucket_name, &folder).await;
+            other.set_ann_index(ann_index);
+            Some(other)
+        }
+        Ok(None) => None,
+        Err(err) => {
+            warn!("skip linking to {}: {}", document_key, err);
+            None
+        }
+    }
+}
+
+// Stores the ANN index of a graph next to it in `folder`.
+async fn upload_ann_index(
+    client: &Client,
+    bucket_name: &str,
+    folder: &str,
+    ann_index: &HnswIndex,
+) -> Result<()> {
+    let ann_file_key = format!("{}/{}", folder, ANN_INDEX_FILENAME);
+    let ann_content = serde_json::to_string(ann_index)?;
+    s3_helper::upload_object_with_content(
+        &client,
+        &bucket_name,
+        &ann_file_key,
+        ByteStream::from(ann_content.as_bytes().to_vec()),
+    )
+    .await?;
     Ok(())
 }
 
+// Stores `fingerprint` next to the graph in `folder`, so duplicates are found
+// without downloading the graph.
+async fn upload_fingerprint(
+    client: &Client,
+    bucket_name: &str,
+    folder: &str,
+    fingerprint: &DocumentFingerprint,
+) -> Result<()> {
+    let fingerprint_file_key = format!("{}/{}", folder, FINGERPRINT_FILENAME);
+    s3_helper::upload_object_with_content(
+        &client,
+        &bucket_name,
+        &fingerprint_file_key,
+        ByteStream::from(serde_json::to_vec(fingerprint)?),
+    )
+    .await?;
+    Ok(())
+}
+
+// Fingerprint stored next to the graph in `folder`, `None` for documents
+// indexed before fingerprints were stored.
+async fn download_fingerprint(
+    client: &Client,
+    bucket: &str,
+    document_key: &str,
+    folder: &str,
+) -> Result<Option<DocumentFingerprint>> {
+    let fingerprint_file_key = format!("{}/{}", folder, FINGERPRINT_FILENAME);
+    let Ok(output) = s3_helper::download_object(&client, &bucket, &fingerprint_file_key).await
+    else {
+        return Ok(None);
+    };
+    let data = output.body.collect().await.map(|data| data.into_bytes())?;
+    let mut fingerprint: DocumentFingerprint = serde_json::from_slice(&data)?;
+    fingerprint.set_key(document_key);
+    Ok(Some(fingerprint))
+}
+
+fn log_quantization_report(report: &IndexingReport) {
+    if let Some(quantization) = report.quantization() {
+        info!(
+            "quantization: {:?}, recall@{}: {:.3}, size: {} -> {} bytes",
+            quantization.quantization(),
+            quantization.k(),
+            quantization.recall(),
+            quantization.full_precision_bytes(),
+            quantization.quantized_bytes()
+        );
+    }
+}
+
+// Warns about inputs cut short by the max sequence length of the model.
+fn log_embedding_stats(report: &IndexingReport) {
+    let Some(stats) = report.embedding() else {
+        return;
+    };
+    info!(
+        "embedded inputs: {}, overflowing: {}, windows: {}",
+        stats.inputs(),
+        stats.overflowing(),
+        stats.windows()
+    );
+    if *stats.truncated_tokens() > 0 {
+        warn!(
+            "truncated {} tokens of inputs longer than the embedding windows",
+            stats.truncated_tokens()
+        );
+    }
+}
+
+/// Loads the model named by `APP_EMBEDDING_MODEL`, or the local MiniLM L6
+/// model when it is not set.
 pub async fn get_embedding_model(resources_path: &str) -> Result<EmbeddingModel> {
-    let model_id = ModelId::AllMiniLML6V2.to_string();
-    let model_name = model_id.split("::").last().unwrap_or("");
+    let model_id = match common::vars::get_app_embedding_model() {
+        Ok(model_id) => ModelId::from_str(&model_id)?,
+        Err(_) => ModelId::AllMiniLML6V2,
+    };
+    load_embedding_model(resources_path, model_id).await
+}
+
+/// Registry holding the model of `get_embedding_model`, to resolve the query
+/// model of searched graphs from their index model.
+pub async fn get_embedding_registry(resources_path: &str) -> Result<EmbeddingRegistry> {
+    let mut registry = EmbeddingRegistry::new();
+    registry.register(Arc::new(get_embedding_model(resources_path).await?));
+    Ok(registry)
+}
+
+/// Loads `model_id`, from `resources_path` for local models.
+pub async fn load_embedding_model(
+    resources_path: &str,
+    model_id: ModelId,
+) -> Result<EmbeddingModel> {
+    if model_id.is_openai() {
+        return EmbeddingModel::load_model(&model_id.to_string(), None);
+    }
+
     let mut path = PathBuf::from(resources_path);
     path.push("models");
-    path.push(model_name);
+    path.push(model_id.model_name());
     let model_path = path.to_str().unwrap();
-    let embedding_model = MiniLMEmbeddingModel::from_file(ModelId::AllMiniLML6V2, model_path)
-        .expect("fail to load model.");
-    let model = EmbeddingModel::MiniLMEmbeddingModel(embedding_model);
+    let model = match model_id {
+        ModelId::Onnx(_) => TransformerEmbeddingModel::from_file(model_id, model_path)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            .into(),
+        _ => MiniLMEmbeddingModel::from_file(model_id, model_path)
+            .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            .into(),
+    };
 
     Ok(model)
 }
 
+/// Loads the cross-encoder named by `APP_RERANKER_MODEL`, or `None` when it is
+/// not set.
+pub async fn get_reranker_model(resources_path: &str) -> Result<Option<CrossEncoderModel>> {
+    let Ok(model_name) = common::vars::get_app_reranker_model() else {
+        return Ok(None);
+    };
+    load_reranker_model(resources_path, &model_name)
+        .await
+        .map(Some)
+}
+
+/// Loads the cross-encoder `model_name` from `resources_path`.
+pub async fn load_reranker_model(
+    resources_path: &str,
+    model_name: &str,
+) -> Result<CrossEncoderModel> {
+    let mut path = PathBuf::from(resources_path);
+    path.push("models");
+    path.push(model_name);
+    let model_path = path.to_str().unwrap();
+    CrossEncoderModel::from_file(model_name, model_path)
+}
+
 pub async fn search_graph(
-    graphs: Vec<Graph>,
+    graphs: &[Graph],
     query: &str,
     model: &EmbeddingModel,
-) -> Result<Vec<(f32, String)>> {
+    reranker: Option<&CrossEncoderModel>,
+    options: &SearchOptions,
+) -> Result<Vec<Context>> {
     info!("search nodes");
+    let results = retrieve_contexts(graphs, query, model, reranker, options).await?;
+    info!("search result: {}", results.len());
+    Ok(results)
+}
+
+// Contexts of the sub-chunks of the nodes ranked in `graphs` for `query`,
+// sorted by score: fused with their BM25 scores in hybrid mode, with near
+// duplicates collapsed, then reranked and diversified as set in `options`.
+// Rerankers and diversification compare the chunk texts, `raw_data`.
+async fn retrieve_contexts(
+    graphs: &[Graph],
+    query: &str,
+    model: &EmbeddingModel,
+    reranker: Option<&CrossEncoderModel>,
+    options: &SearchOptions,
+) -> Result<Vec<Context>> {
+    warn_missing_reranker(reranker, options);
     let query_embedding = model
-        .run(query)
+        .run_query(query)
+        .await
         .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-    let chunker = OverlappedChunker::with_size(100);
+    let graphs = compatible_graphs(graphs, model)?;
+    let graphs = filter_graphs(graphs, options);
+    let graphs = route_graphs(graphs, &query_embedding, options);
 
-    let mut results: Vec<(f32, String)> = vec![];
-    for graph in graphs {
-        let query_embedding: Vec<f32> = query_embedding.clone();
-        let nodes = graph.get_all_nodes();
+    let mut results: Vec<Context> = vec![];
+    let mut lexical_scores: Vec<f32> = vec![];
+    let mut chunk_embeddings: HashMap<String, Vec<f32>> = HashMap::new();
+    let mut fingerprints: HashMap<String, u64> = HashMap::new();
+    let ranked = rank_graphs(&graphs, query, &query_embedding, options);
+    for (graph, document_chunks) in graphs.iter().zip(ranked) {
         let title = graph.title();
+        let reference = graph.reference();
 
-        let mut document_chunks = nodes
-            .par_iter()
-            .map(|node| {
-                let similarity =
-                    math::cosine_similarity(query_embedding.to_vec(), node.embeddings().to_vec());
-                (similarity, node.to_owned())
-            })
-            .collect::<Vec<(f32, Node)>>();
-        document_chunks.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        info!("search graph: {}", title);
+
+        let chunks = score_sub_chunks(&graph, &document_chunks, &query_embedding);
+        if options.hybrid().is_some() {
+            lexical_scores.extend(
+                chunks
+                    .iter()
+                    .map(|(_, chunk)| graph.lexical_score(query, chunk.data())),
+            );
+        }
+        for (score, chunk) in chunks {
+            let raw_data = chunk.data().to_owned();
+            let data = format!("From document {}:\n {}", title, raw_data);
+            let fingerprint = chunk
+                .simhash()
+                .unwrap_or_else(|| fingerprint::simhash(&raw_data));
+            fingerprints.insert(raw_data.clone(), fingerprint);
+            let embedding = graph.embedding(chunk);
+            if !embedding.is_empty() {
+                chunk_embeddings.insert(raw_data.clone(), embedding.into_owned());
+            }
+            results.push(
+                Context::builder()
+                    .score(score)
+                    .raw_data(raw_data)
+                    .data(data)
+                    .reference(reference.to_owned())
+                    .provenance(Provenance::of(graph, chunk))
+                    .build(),
+            );
+        }
+    }
+
+    if let Some(hybrid) = options.hybrid() {
+        let vector_scores = results.iter().map(|x| *x.score()).collect::<Vec<f32>>();
+        let scores = fuse_scores(&vector_scores, &lexical_scores, hybrid);
+        for (context, score) in results.iter_mut().zip(scores) {
+            context.set_score(score);
+        }
+    }
+
+    results.sort_by(|a, b| b.score().total_cmp(a.score()));
+    if *options.collapse_duplicates() {
+        results = collapse_duplicates(results, &fingerprints);
+    }
+    if let (Some(rerank), Some(reranker)) = (options.rerank(), reranker) {
+        results.truncate(*rerank.top_n());
+        let texts = results
+            .iter()
+            .map(|x| x.raw_data().as_str())
+            .collect::<Vec<&str>>();
+        let scores = reranker.score(query, &texts)?;
+        for (context, score) in results.iter_mut().zip(scores) {
+            context.set_score(score);
+        }
+        results.sort_by(|a, b| b.score().total_cmp(a.score()));
+    }
+    if let Some(diversity) = options.diversity() {
+        results = diversify(
+            results,
+            |context| context.raw_data(),
+            |context| *context.score(),
+            &chunk_embeddings,
+            diversity,
+        );
+    }
+    Ok(results)
+}
+
+// Drops the graphs whose embeddings cannot be compared with query embeddings
+// of `model`. Fails when no graph is left.
+fn compatible_graphs<'g>(graphs: &'g [Graph], model: &EmbeddingModel) -> Result<Vec<&'g Graph>> {
+    let mut compatible = vec![];
+    let mut error = None;
+    for graph in graphs {
+        match model.check_compatible(graph) {
+            Ok(()) => compatible.push(graph),
+            Err(err) => {
+                warn!("skip graph: {}", err);
+                error = Some(err);
+            }
+        }
+    }
+    match (compatible.is_empty(), error) {
+        (true, Some(err)) => Err(err.into()),
+        _ => Ok(compatible),
+    }
+}
+
+// Drops the graphs whose document does not match the search filter.
+fn filter_graphs<'g>(graphs: Vec<&'g Graph>, options: &SearchOptions) -> Vec<&'g Graph> {
+    let Some(filter) = options.filter() else {
+        return graphs;
+    };
+
+    let document_count = graphs.len();
+    let graphs = graphs
+        .into_iter()
+        .filter(|graph| filter.matches_graph(graph))
+        .collect::<Vec<&Graph>>();
+    info!("filtered {} of {} documents", graphs.len(), document_count);
+    graphs
+}
+
+// First phase of a collection search: keeps the `top_documents` graphs closest
+// to the query, and every graph without document embedding.
+fn route_graphs<'g>(
+    graphs: Vec<&'g Graph>,
+    query_embedding: &[f32],
+    options: &SearchOptions,
+) -> Vec<&'g Graph> {
+    let Some(top_documents) = *options.top_documents() else {
+        return graphs;
+    };
+    if graphs.len() <= top_documents {
+        return graphs;
+    }
 
-        let new_data: Vec<String> = document_chunks
-            .par_iter()
-            .take(10)
+    let document_count = graphs.len();
+    let mut routed: Vec<(f32, &Graph)> = vec![];
+    let mut unrouted: Vec<&Graph> = vec![];
+    for graph in graphs {
+        match graph.document_score(query_embedding) {
+            Some(score) => routed.push((score, graph)),
+            None => unrouted.push(graph),
+        }
+    }
+    routed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    routed.truncate(top_documents);
+    info!(
+        "routed query to {} of {} documents",
+        routed.len() + unrouted.len(),
+        document_count
+    );
+
+    routed
+        .into_iter()
+        .map(|(_, graph)| graph)
+        .chain(unrouted)
+        .collect()
+}
+
+// Ranked nodes of every graph, followed by the nodes of other graphs their
+// cross-document edges lead to.
+fn rank_graphs<'g>(
+    graphs: &[&'g Graph],
+    query: &str,
+    query_embedding: &[f32],
+    options: &SearchOptions,
+) -> Vec<Vec<(f32, &'g Node)>> {
+    let mut ranked = graphs
+        .iter()
+        .map(|graph| rank_nodes(graph, query, query_embedding, options))
+        .collect::<Vec<Vec<(f32, &Node)>>>();
+    if let Some(expansion) = options.expansion() {
+        if expansion.kinds().contains(&EdgeKind::CrossDocument) {
+            expand_across_documents(graphs, &mut ranked, expansion);
+        }
+    }
+    ranked
+}
+
+// Top nodes of `graph`, fused with its BM25 ranking in hybrid mode, expanded
+// along its edges, then to their parent sections.
+fn rank_nodes<'g>(
+    graph: &'g Graph,
+    query: &str,
+    query_embedding: &[f32],
+    options: &SearchOptions,
+) -> Vec<(f32, &'g Node)> {
+    let top_k = *options.top_k();
+    let filter = options.filter().as_ref();
+    let vector_nodes = graph.search_nodes(query_embedding, top_k, options.strategy(), filter);
+    let nodes = match options.hybrid() {
+        Some(hybrid) => fuse_nodes(graph, query, vector_nodes, hybrid, top_k, filter),
+        None => vector_nodes,
+    };
+    let nodes = match options.expansion() {
+        Some(expansion) => graph.expand_along_edges(nodes, expansion),
+        None => nodes,
+    };
+    match options.expand_sections() {
+        Some(min_siblings) => graph.expand_to_sections(nodes, *min_siblings),
+        None => nodes,
+    }
+}
+
+// Adds to the ranked nodes of each graph the nodes linked by cross-document
+// edges to the best nodes of the other graphs. Edges are stored in the graph
+// indexed last, so they are followed both ways. Only searched graphs are
+// reached.
+fn expand_across_documents<'g>(
+    graphs: &[&'g Graph],
+    ranked: &mut [Vec<(f32, &'g Node)>],
+    expansion: &ExpansionOptions,
+) {
+    let graph_indices = graphs
+        .iter()
+        .enumerate()
+        .filter_map(|(index, graph)| {
+            graph
+                .reference()
+                .as_deref()
+                .map(|reference| (reference, index))
+        })
+        .collect::<HashMap<&str, usize>>();
+    // (graph index, node) pairs linked by each cross-document edge, both ways.
+    let mut links: HashMap<(usize, &str), Vec<(usize, &Node, f32)>> = HashMap::new();
+    for (index, graph) in graphs.iter().enumerate() {
+        for node in graph.node_map().values() {
+            for edge in graph.edges(node) {
+                let Some(&target_index) = edge
+                    .document()
+                    .as_deref()
+                    .and_then(|document| graph_indices.get(document))
+                else {
+                    continue;
+                };
+                let Some(target) = graphs[target_index].node_map().get(edge.target()) else {
+                    continue;
+                };
+                let weight = *edge.weight();
+                let source = (index, node.id().as_str());
+                links
+                    .entry(source)
+                    .or_default()
+                    .push((target_index, target, weight));
+                let reached = (target_index, target.id().as_str());
+                links
+                    .entry(reached)
+                    .or_default()
+                    .push((index, node, weight));
+            }
+        }
+    }
+
+    let mut additions = vec![];
+    for (index, nodes) in ranked.iter().enumerate() {
+        for (score, node) in nodes.iter().take(*expansion.hits()) {
+            let Some(linked) = links.get_mut(&(index, node.id().as_str())) else {
+                continue;
+            };
+            linked.sort_by(|a, b| b.2.total_cmp(&a.2));
+            for (target_index, target, weight) in linked.iter().take(*expansion.edges_per_hit()) {
+                additions.push((*target_index, score * weight, *target));
+            }
+        }
+    }
+    for (index, score, node) in additions {
+        if !ranked[index]
+            .iter()
+            .any(|(_, other)| other.id() == node.id())
+        {
+            ranked[index].push((score, node));
+        }
+    }
+}
+
+fn fuse_nodes<'g>(
+    graph: &'g Graph,
+    query: &str,
+    vector_nodes: Vec<(f32, &'g Node)>,
+    hybrid: &HybridOptions,
+    top_k: usize,
+    filter: Option<&SearchFilter>,
+) -> Vec<(f32, &'g Node)> {
+    let lexical_nodes = graph.search_nodes_lexical(query, top_k, filter);
+
+    let mut nodes: HashMap<&str, &Node> = HashMap::new();
+    let mut rankings = vec![];
+    for (weight, ranked_nodes) in [
+        (*hybrid.vector_weight(), vector_nodes),
+        (*hybrid.lexical_weight(), lexical_nodes),
+    ] {
+        let ranking = ranked_nodes
+            .into_iter()
             .map(|(_, node)| {
-                node.data()
-                    .split("\n")
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>()
-            })
-            .flatten()
-            .collect::<Vec<String>>();
-
-        let chunks = chunker.chunks(&new_data);
-        let result = chunks
-            .par_iter()
-            .map(|chunk| {
-                let embedding_result = model
-                    .run(&chunk)
-                    .map_err(|e| anyhow::anyhow!(e.to_string()))
-                    .unwrap();
-                let similarity =
-                    math::cosine_similarity(query_embedding.to_vec(), embedding_result.to_vec());
-                let text = format!("From document {}:\n {}", title, chunk.to_owned());
-                (similarity, text)
+                nodes.insert(node.id(), node);
+                node.id().as_str()
             })
-            .collect::<Vec<(f32, String)>>();
-        results.extend(result);
+            .collect::<Vec<&str>>();
+        rankings.push((weight, ranking));
     }
 
-    results.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-    info!("search result: {}", results.len());
-    Ok(results)
+    let mut result = math::reciprocal_rank_fusion(&rankings, *hybrid.rank_constant())
+        .into_iter()
+        .map(|(id, score)| (score, nodes[id]))
+        .collect::<Vec<(f32, &Node)>>();
+    result.sort_by(|a, b| b.0.total_cmp(&a.0));
+    result.truncate(top_k);
+    result
+}
+
+// Reciprocal rank fusion of two score lists over the same items. Items
+// without any lexical match take no part in the lexical ranking.
+fn fuse_scores(vector_scores: &[f32], lexical_scores: &[f32], hybrid: &HybridOptions) -> Vec<f32> {
+    let mut vector_ranking = (0..vector_scores.len()).collect::<Vec<usize>>();
+    vector_ranking.sort_by(|&a, &b| vector_scores[b].total_cmp(&vector_scores[a]));
+    let mut lexical_ranking = (0..lexical_scores.len())
+        .filter(|&index| lexical_scores[index] > 0.0)
+        .collect::<Vec<usize>>();
+    lexical_ranking.sort_by(|&a, &b| lexical_scores[b].total_cmp(&lexical_scores[a]));
+
+    let scores = math::reciprocal_rank_fusion(
+        &[
+            (*hybrid.vector_weight(), vector_ranking),
+            (*hybrid.lexical_weight(), lexical_ranking),
+        ],
+        *hybrid.rank_constant(),
+    );
+    (0..vector_scores.len())
+        .map(|index| scores.get(&index).copied().unwrap_or(0.0))
+        .collect()
+}
+
+// Drops results whose lines mostly repeat a better ranked result, then orders
+// the best of the rest by maximal marginal relevance. `results` must be sorted
+// by score and `embeddings` keyed by `text`. Results without embeddings take
+// no part in the redundancy term.
+fn diversify<T>(
+    results: Vec<T>,
+    text: impl Fn(&T) -> &str,
+    score: impl Fn(&T) -> f32,
+    embeddings: &HashMap<String, Vec<f32>>,
+    diversity: &DiversityOptions,
+) -> Vec<T> {
+    let lines = results
+        .iter()
+        .map(|result| {
+            text(result)
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .map(|line| line.to_string())
+                .collect::<HashSet<String>>()
+        })
+        .collect::<Vec<HashSet<String>>>();
+    let mut kept_lines: Vec<&HashSet<String>> = vec![];
+    let mut kept = vec![];
+    for (result, lines) in results.into_iter().zip(&lines) {
+        let redundant = kept_lines.iter().any(|other| {
+            let shared = lines.intersection(other).count();
+            let overlap = shared as f32 / lines.len().min(other.len()).max(1) as f32;
+            overlap >= *diversity.max_overlap()
+        });
+        if !redundant {
+            kept_lines.push(lines);
+            kept.push(result);
+        }
+    }
+
+    // Scores may be fused or reranked, so relevance is rescaled to [0, 1] to
+    // be comparable with cosine similarities.
+    let scores = kept.iter().map(&score).collect::<Vec<f32>>();
+    let min = scores.iter().copied().fold(f32::MAX, f32::min);
+    let max = scores.iter().copied().fold(f32::MIN, f32::max);
+    let relevance = scores
+        .iter()
+        .map(|score| (score - min) / (max - min).max(f32::EPSILON))
+        .collect::<Vec<f32>>();
+    let vectors = kept
+        .iter()
+        .map(|result| {
+            embeddings
+                .get(text(result))
+                .map_or(&[][..], |e| e.as_slice())
+        })
+        .collect::<Vec<&[f32]>>();
+    let order = math::maximal_marginal_relevance(
+        &relevance,
+        &vectors,
+        *diversity.lambda(),
+        *diversity.top_n(),
+    );
+
+    let mut kept = kept.into_iter().map(Some).collect::<Vec<Option<T>>>();
+    let mut result = order
+        .into_iter()
+        .filter_map(|index| kept[index].take())
+        .collect::<Vec<T>>();
+    result.extend(kept.into_iter().flatten());
+    result
 }
 
+fn warn_missing_reranker(reranker: Option<&CrossEncoderModel>, options: &SearchOptions) {
+    if options.rerank().is_some() && reranker.is_none() {
+        warn!("rerank requested without a reranker model, keeping first stage scores");
+    }
+}
+
+// Sub-chunks of the ranked nodes with their similarity to the query. Nodes of
+// graphs indexed without sub-chunks are scored as a whole, keeping their rank
+// score when they have no embeddings.
+fn score_sub_chunks<'g>(
+    graph: &'g Graph,
+    nodes: &[(f32, &'g Node)],
+    query_embedding: &[f32],
+) -> Vec<(f32, &'g Node)> {
+    nodes
+        .par_iter()
+        .flat_map(|(score, node)| {
+            let sub_chunks = graph.sub_chunks(node);
+            if sub_chunks.is_empty() {
+                let embedding = graph.embedding(node);
+                let score = match embedding.is_empty() {
+                    true => *score,
+                    false => math::cosine_similarity_slice(query_embedding, &embedding),
+                };
+                return vec![(score, *node)];
+            }
+            sub_chunks
+                .into_iter()
+                .map(|sub_chunk| {
+                    let similarity =
+                        math::cosine_similarity_slice(query_embedding, &graph.embedding(sub_chunk));
+                    (similarity, sub_chunk)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Merges every context whose text is a near duplicate of a better ranked
+// context from another document into that context, which then lists both
+// references. `results` must be sorted by score.
+fn collapse_duplicates(results: Vec<Context>, fingerprints: &HashMap<String, u64>) -> Vec<Context> {
+    let context_count = results.len();
+    let mut collapsed: Vec<Context> = vec![];
+    for context in results {
+        let fingerprint = fingerprints.get(context.raw_data());
+        let duplicate = match (fingerprint, context.reference()) {
+            (Some(fingerprint), Some(reference)) => collapsed.iter_mut().find(|kept| {
+                kept.reference().as_ref() != Some(reference)
+                    && !kept.references().contains(reference)
+                    && fingerprints
+                        .get(kept.raw_data())
+                        .is_some_and(|other| fingerprint::is_near_duplicate(*fingerprint, *other))
+            }),
+            _ => None,
+        };
+        match duplicate {
+            Some(kept) => kept.add_reference(context.reference().clone().unwrap()),
+            None => collapsed.push(context),
+        }
+    }
+    if collapsed.len() < context_count {
+        info!(
+            "collapsed {} duplicate contexts",
+            context_count - collapsed.len()
+        );
+    }
+    collapsed
+}
+
+/// Graphs of `document_keys` with their ANN index. Fails when a document has
+/// no graph.
 pub async fn load_graphs_from_s3(
     client: &Client,
     bucket: &str,
@@ -189,98 +1265,573 @@ pub async fn load_graphs_from_s3(
 ) -> Result<Vec<Graph>> {
     let mut graphs = vec![];
     for document_key in document_keys {
-        let graph_file_key = format!("{}/{}", document_key, GRAPH_FILENAME);
-        info!("load graph: {:?}", graph_file_key);
-        match s3_helper::download_object(&client, &bucket, &graph_file_key).await {
-            Ok(output) => {
-                let data = output.body.collect().await.map(|data| data.into_bytes())?;
-                let obj = serde_json::from_slice(&data)?;
-                let graph = Graph::from(obj);
+        info!("load graph: {:?}", document_key);
+        let folder = graph_folder(&client, &bucket, document_key).await?;
+        match download_graph(&client, &bucket, &folder).await? {
+            Some(mut graph) => {
+                graph.set_ann_index(load_ann_index_from_s3(&client, &bucket, &folder).await);
+                graph.set_document_key(Some(document_key.to_string()));
                 graphs.push(graph);
             }
-            Err(_) => panic!("Fail to read {}", graph_file_key),
+            None => anyhow::bail!("no graph for document: {}", document_key),
         };
     }
     Ok(graphs)
 }
 
+/// Duplicate and near duplicate pairs among the documents of `document_keys`.
+/// Only the fingerprints stored next to the graphs are downloaded. Graphs of
+/// documents indexed before are loaded one at a time for their fingerprint.
+pub async fn find_duplicates_in_s3(
+    client: &Client,
+    bucket: &str,
+    document_keys: Vec<&str>,
+) -> Result<Vec<DuplicateDocuments>> {
+    let mut fingerprints = vec![];
+    for document_key in document_keys {
+        let folder = graph_folder(&client, &bucket, document_key).await?;
+        if let Some(fingerprint) =
+            download_fingerprint(client, bucket, document_key, &folder).await?
+        {
+            fingerprints.push(fingerprint);
+            continue;
+        }
+        match download_graph(&client, &bucket, &folder).await? {
+            Some(graph) => fingerprints.push(DocumentFingerprint::from_graph(document_key, &graph)),
+            None => warn!("no graph to fingerprint: {}", document_key),
+        }
+    }
+    let duplicates = fingerprint::find_duplicates(&fingerprints);
+    info!(
+        "found {} duplicate pairs in {} documents",
+        duplicates.len(),
+        fingerprints.len()
+    );
+    Ok(duplicates)
+}
+
+/// Writes the binary graph of a document indexed by an older version from its
+/// JSON graph, as a new graph version with its ANN index. Returns `false` when
+/// there is nothing to convert.
+pub async fn convert_graph_in_s3(
+    client: &Client,
+    bucket: &str,
+    document_key: &str,
+) -> Result<bool> {
+    if graph_folder(&client, &bucket, document_key).await? != document_key {
+        return Ok(false);
+    }
+    let binary_file_key = format!("{}/{}", document_key, GRAPH_BINARY_FILENAME);
+    if s3_helper::download_object(&client, &bucket, &binary_file_key)
+        .await
+        .is_ok()
+    {
+        return Ok(false);
+    }
+    let graph_file_key = format!("{}/{}", document_key, GRAPH_FILENAME);
+    let Ok(output) = s3_helper::download_object(&client, &bucket, &graph_file_key).await else {
+        return Ok(false);
+    };
+    let data = output.body.collect().await.map(|data| data.into_bytes())?;
+    let graph = Graph::from_slice(&data)?;
+
+    let version = common::generate_id();
+    let folder = version_folder(document_key, &version);
+    let ann_file_key = format!("{}/{}", document_key, ANN_INDEX_FILENAME);
+    if let Ok(output) = s3_helper::download_object(&client, &bucket, &ann_file_key).await {
+        s3_helper::upload_object_with_content(
+            &client,
+            &bucket,
+            &format!("{}/{}", folder, ANN_INDEX_FILENAME),
+            output.body,
+        )
+        .await?;
+    }
+    s3_helper::upload_object_with_content(
+        &client,
+        &bucket,
+        &format!("{}/{}", folder, GRAPH_BINARY_FILENAME),
+        ByteStream::from(graph.to_bytes()?),
+    )
+    .await?;
+    let fingerprint = DocumentFingerprint::from_graph(document_key, &graph);
+    upload_fingerprint(client, bucket, &folder, &fingerprint).await?;
+    switch_graph_version(client, bucket, document_key, &version).await?;
+    info!("converted graph: {}", document_key);
+
+    Ok(true)
+}
+
+/// Writes the graph of a document, with its ANN index and parsed content, to
+/// `folder/document_key`, laid out like the document bucket.
+pub fn save_graph_to_folder(
+    folder: &Path,
+    document_key: &str,
+    graph: &Graph,
+    document: &Document,
+) -> Result<()> {
+    let document_folder = folder.join(document_key);
+    std::fs::create_dir_all(&document_folder)?;
+    std::fs::write(
+        document_folder.join(DATA_FILENAME),
+        serde_json::to_string(document)?,
+    )?;
+    let ann_path = document_folder.join(ANN_INDEX_FILENAME);
+    match graph.ann_index() {
+        Some(ann_index) => std::fs::write(&ann_path, serde_json::to_string(ann_index)?)?,
+        None if ann_path.exists() => std::fs::remove_file(&ann_path)?,
+        None => {}
+    }
+    std::fs::write(
+        document_folder.join(GRAPH_BINARY_FILENAME),
+        graph.to_bytes()?,
+    )?;
+    Ok(())
+}
+
+/// Graph saved by `save_graph_to_folder`, with its ANN index, or `None` when
+/// the document was not indexed.
+pub fn load_graph_from_folder(folder: &Path, document_key: &str) -> Result<Option<Graph>> {
+    let document_folder = folder.join(document_key);
+    let Ok(data) = std::fs::read(document_folder.join(GRAPH_BINARY_FILENAME)) else {
+        return Ok(None);
+    };
+    let mut graph = Graph::from_slice(&data)?;
+    let ann_index = std::fs::read(document_folder.join(ANN_INDEX_FILENAME))
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok());
+    graph.set_ann_index(ann_index);
+    graph.set_document_key(Some(document_key.to_string()));
+    Ok(Some(graph))
+}
+
+/// Every graph saved in `folder` by `save_graph_to_folder`, sorted by
+/// document key.
+pub fn load_graphs_from_folder(folder: &Path) -> Result<Vec<Graph>> {
+    let mut document_keys = std::fs::read_dir(folder)?
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().to_string()))
+        .collect::<std::io::Result<Vec<String>>>()?;
+    document_keys.sort();
+
+    let mut graphs = vec![];
+    for document_key in document_keys {
+        if let Some(graph) = load_graph_from_folder(folder, &document_key)? {
+            graphs.push(graph);
+        }
+    }
+    Ok(graphs)
+}
+
+// Folder of the current graph files of a document: the version named by its
+// `graph_version` object, or the document folder itself when it was indexed
+// before graphs were versioned.
+async fn graph_folder(client: &Client, bucket: &str, document_key: &str) -> Result<String> {
+    let version_file_key = format!("{}/{}", document_key, GRAPH_VERSION_FILENAME);
+    let Some(output) = download_optional_object(client, bucket, &version_file_key).await? else {
+        return Ok(document_key.to_string());
+    };
+    let data = output.body.collect().await.map(|data| data.into_bytes())?;
+    let version = String::from_utf8(data.to_vec())?;
+    Ok(version_folder(document_key, version.trim()))
+}
+
+fn version_folder(document_key: &str, version: &str) -> String {
+    format!("{}/{}/{}", document_key, GRAPH_VERSIONS_FOLDER, version)
+}
+
+// Makes `version` the current graph of a document once all its files are
+// uploaded, with a single write. Readers load either the previous version or
+// the new one, never a mix of both. The previous version is kept for readers
+// that loaded it before the switch, and its files are only deleted when the
+// next version replaces this one.
+async fn switch_graph_version(
+    client: &Client,
+    bucket: &str,
+    document_key: &str,
+    version: &str,
+) -> Result<()> {
+    let previous = graph_folder(client, bucket, document_key).await?;
+    let previous_file_key = format!("{}/{}", document_key, GRAPH_PREVIOUS_VERSION_FILENAME);
+    let oldest = match download_optional_object(client, bucket, &previous_file_key).await? {
+        Some(output) => {
+            let data = output.body.collect().await.map(|data| data.into_bytes())?;
+            Some(String::from_utf8(data.to_vec())?)
+        }
+        None => None,
+    };
+
+    let version_file_key = format!("{}/{}", document_key, GRAPH_VERSION_FILENAME);
+    s3_helper::upload_object_with_content(
+        client,
+        bucket,
+        &version_file_key,
+        ByteStream::from(version.as_bytes().to_vec()),
+    )
+    .await?;
+    s3_helper::upload_object_with_content(
+        client,
+        bucket,
+        &previous_file_key,
+        ByteStream::from(previous.as_bytes().to_vec()),
+    )
+    .await?;
+
+    if let Some(oldest) = oldest.filter(|oldest| *oldest != previous) {
+        delete_graph_files(client, bucket, document_key, &oldest).await;
+    }
+    Ok(())
+}
+
+// Deletes the graph files in `folder`, a version folder or the document
+// folder of a graph indexed before graphs were versioned. Failures are
+// logged, the files are then left behind.
+async fn delete_graph_files(client: &Client, bucket: &str, document_key: &str, folder: &str) {
+    let mut filenames = vec![
+        GRAPH_BINARY_FILENAME,
+        ANN_INDEX_FILENAME,
+        FINGERPRINT_FILENAME,
+    ];
+    if folder == document_key {
+        filenames.push(GRAPH_FILENAME);
+    }
+    for filename in filenames {
+        let file_key = format!("{}/{}", folder, filename);
+        if let Err(err) = s3_helper::delete_object(client, bucket, &file_key).await {
+            warn!(
+                "failed to delete previous graph file: {}: {}",
+                file_key, err
+            );
+        }
+    }
+}
+
+// Binary graph in `folder`, or the JSON graph of a document indexed by an
+// older version. `None` when the document has no graph.
+async fn download_graph(client: &Client, bucket: &str, folder: &str) -> Result<Option<Graph>> {
+    for filename in [GRAPH_BINARY_FILENAME, GRAPH_FILENAME] {
+        let graph_file_key = format!("{}/{}", folder, filename);
+        let Some(output) = download_optional_object(client, bucket, &graph_file_key).await? else {
+            continue;
+        };
+        let data = output.body.collect().await.map(|data| data.into_bytes())?;
+        return Ok(Some(Graph::from_slice(&data)?));
+    }
+    Ok(None)
+}
+
+// Binary graph in `folder` downloaded to a temporary file, opened without
+// loading it. `None` when the document has no graph or a JSON one.
+async fn download_stored_graph(
+    client: &Client,
+    bucket: &str,
+    folder: &str,
+) -> Result<Option<(TempPath, StoredGraph)>> {
+    let graph_file_key = format!("{}/{}", folder, GRAPH_BINARY_FILENAME);
+    let Some(output) = download_optional_object(client, bucket, &graph_file_key).await? else {
+        return Ok(None);
+    };
+    let file = download_to_file(output.body, "bin").await?;
+    let graph = StoredGraph::open(file.path())?;
+    Ok(Some((file, graph)))
+}
+
+// Object `key` of `bucket`, `None` when it does not exist. Other errors are
+// returned.
+async fn download_optional_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Option<GetObjectOutput>> {
+    match s3_helper::download_object(client, bucket, key).await {
+        Ok(output) => Ok(Some(output)),
+        Err(err)
+            if err
+                .as_service_error()
+                .is_some_and(GetObjectError::is_no_such_key) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Writes `body` to a temporary file with `extension` as it is received.
+async fn download_to_file(mut body: ByteStream, extension: &str) -> Result<TempPath> {
+    let file = TempPath::new(extension);
+    let mut writer = BufWriter::new(File::create(file.path())?);
+    while let Some(bytes) = body.next().await {
+        writer.write_all(&bytes?)?;
+    }
+    writer.flush()?;
+    Ok(file)
+}
+
+async fn load_ann_index_from_s3(client: &Client, bucket: &str, folder: &str) -> Option<HnswIndex> {
+    let ann_index_file_key = format!("{}/{}", folder, ANN_INDEX_FILENAME);
+    let output = match s3_helper::download_object(&client, &bucket, &ann_index_file_key).await {
+        Ok(output) => output,
+        Err(_) => {
+            info!("no ann index: {:?}, using exact search", ann_index_file_key);
+            return None;
+        }
+    };
+    let data = output.body.collect().await.ok()?.into_bytes();
+    serde_json::from_slice(&data).ok()
+}
+
+/// Contexts are packed into `budget`, the last one being cut at a sentence
+/// boundary when it does not fit whole.
 pub async fn search_context(
-    graphs: Vec<Graph>,
+    graphs: &[Graph],
     query: &str,
     model: &EmbeddingModel,
-    max_tokens: usize,
+    reranker: Option<&CrossEncoderModel>,
+    budget: &TokenBudget,
+    options: &SearchOptions,
 ) -> Result<Vec<Context>> {
     info!("search context");
-    let query_embedding = model
-        .run(query)
-        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-    let chunker = OverlappedChunker::with_size(100);
+    let results = retrieve_contexts(graphs, query, model, reranker, options).await?;
+    let texts = results
+        .iter()
+        .map(|context| context.data().as_str())
+        .collect::<Vec<&str>>();
+    let packed = budget
+        .pack(&texts, true)
+        .iter()
+        .map(|text| text.chars().count())
+        .collect::<Vec<usize>>();
+    let mut contexts = vec![];
+    for (mut context, len) in results.into_iter().zip(packed) {
+        if len < context.data().chars().count() {
+            context.truncate(len);
+        }
+        if !context.raw_data().trim().is_empty() {
+            contexts.push(context);
+        }
+    }
+    Ok(contexts)
+}
 
-    let mut results: Vec<Context> = vec![];
-    for graph in graphs {
-        let query_embedding: Vec<f32> = query_embedding.clone();
-        let nodes = graph.get_all_nodes();
-        let title = graph.title();
-        let reference = graph.reference();
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Borrow, sync::Arc};
 
-        info!("search graph: {}", title);
+    use document::document::{DocumentMeta, Heading, Node as DocumentNode, Text};
 
-        let mut document_chunks = nodes
-            .par_iter()
-            .map(|node| {
-                let similarity =
-                    math::cosine_similarity(query_embedding.to_vec(), node.embeddings().to_vec());
-                (similarity, node.to_owned())
-            })
-            .collect::<Vec<(f32, Node)>>();
-        document_chunks.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    use super::*;
+    use crate::{fixtures::HashProvider, EmbeddingProvider};
 
-        let new_data: Vec<String> = document_chunks
-            .par_iter()
-            .take(10)
-            .map(|(_, node)| {
-                node.data()
-                    .split("\n")
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>()
-            })
-            .flatten()
-            .collect::<Vec<String>>();
-
-        let chunks = chunker.chunks(&new_data);
-        let result = chunks
-            .par_iter()
-            .map(|chunk| {
-                let embedding_result = model
-                    .run(&chunk)
-                    .map_err(|e| anyhow::anyhow!(e.to_string()))
-                    .unwrap();
-                let score =
-                    math::cosine_similarity(query_embedding.to_vec(), embedding_result.to_vec());
-                let raw_data = chunk.to_owned();
-                let data = format!("From document {}:\n {}", title, raw_data);
-                Context::builder()
-                    .score(score)
-                    .raw_data(raw_data)
-                    .data(data)
-                    .reference(reference.to_owned())
-                    .build()
+    fn document(id: &str, embeddings: Option<Vec<f32>>) -> Graph {
+        Graph::builder()
+            .id(id.to_string())
+            .title(id.to_string())
+            .node_map(HashMap::new())
+            .index_model(None)
+            .embeddings(embeddings)
+            .build()
+    }
+
+    fn ids<G: Borrow<Graph>>(graphs: &[G]) -> Vec<&str> {
+        graphs
+            .iter()
+            .map(|graph| graph.borrow().id().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn route_graphs_keeps_closest_documents() {
+        let graphs = [
+            document("far", Some(vec![-1.0, 0.0])),
+            document("close", Some(vec![1.0, 0.1])),
+            document("unrouted", None),
+            document("closest", Some(vec![1.0, 0.0])),
+        ];
+        let options = SearchOptions::builder().top_documents(Some(2)).build();
+
+        let routed = route_graphs(graphs.iter().collect(), &[1.0, 0.0], &options);
+        assert_eq!(ids(&routed), ["closest", "close", "unrouted"]);
+    }
+
+    #[test]
+    fn route_graphs_keeps_all_documents_without_limit() {
+        let graphs = [
+            document("a", Some(vec![-1.0, 0.0])),
+            document("b", Some(vec![1.0, 0.0])),
+        ];
+        let options = SearchOptions::builder().top_documents(None).build();
+        assert_eq!(
+            ids(&route_graphs(
+                graphs.iter().collect(),
+                &[1.0, 0.0],
+                &options
+            )),
+            ["a", "b"]
+        );
+
+        let graphs = [document("a", Some(vec![-1.0, 0.0]))];
+        let options = SearchOptions::builder().top_documents(Some(1)).build();
+        let routed = route_graphs(graphs.iter().collect(), &[1.0, 0.0], &options);
+        assert_eq!(ids(&routed), ["a"]);
+    }
+    #[test]
+    fn compatible_graphs_skips_other_models() {
+        let provider: Arc<dyn EmbeddingProvider> = Arc::new(HashProvider::new("fake::a", 2));
+        let model = EmbeddingModel::from(provider);
+        let graph = |id: &str, index_model: &str| {
+            Graph::builder()
+                .id(id.to_string())
+                .title(id.to_string())
+                .node_map(HashMap::new())
+                .index_model(Some(index_model.to_string()))
+                .build()
+        };
+
+        let graphs = vec![graph("a", "fake::a"), graph("b", "fake::b")];
+        assert_eq!(ids(&compatible_graphs(&graphs, &model).unwrap()), ["a"]);
+        assert!(compatible_graphs(&graphs[1..], &model).is_err());
+        assert!(compatible_graphs(&[], &model).unwrap().is_empty());
+    }
+    #[tokio::test]
+    async fn diversify_contexts_of_a_quantized_graph() {
+        let provider: Arc<dyn EmbeddingProvider> = Arc::new(HashProvider::new("fake::a", 8));
+        let mut indexer = Indexer::new(EmbeddingModel::from(provider.clone())).unwrap();
+        indexer.set_quantization(Some(Quantization::Int8));
+        let meta = IndexingMeta::builder()
+            .id("pumps".to_string())
+            .title("pumps".to_string())
+            .external_link(String::new())
+            .build();
+        let texts = vec!["Check the seals.", "Replace the seals.", "Drain the pump."];
+        let graph = indexer.index(texts, meta).await.unwrap();
+        let loaded = Graph::from_slice(&graph.to_bytes().unwrap()).unwrap();
+        assert!(loaded
+            .node_map()
+            .values()
+            .all(|node| node.embeddings().is_empty()));
+
+        let options = SearchOptions::builder()
+            .diversity(Some(DiversityOptions::default()))
+            .build();
+        let model = EmbeddingModel::from(provider);
+        let results = retrieve_contexts(&[loaded], "seals", &model, None, &options)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|context| context.score().is_finite()));
+    }
+
+    fn text(value: &str) -> DocumentNode {
+        DocumentNode::Text(
+            Text::builder()
+                .value(value.to_string())
+                .position(None)
+                .build(),
+        )
+    }
+
+    fn heading(depth: u8, title: &str) -> DocumentNode {
+        DocumentNode::Heading(
+            Heading::builder()
+                .children(vec![text(title)])
+                .position(None)
+                .depth(depth)
+                .build(),
+        )
+    }
+
+    // Manual with a preface, a section and a subsection.
+    fn manual() -> Document {
+        let meta = DocumentMeta::builder()
+            .title("manual".to_string())
+            .language(None)
+            .author(None)
+            .creator(None)
+            .producer(None)
+            .subject(None)
+            .description(None)
+            .keywords(None)
+            .creation_date(None)
+            .modification_date(None)
+            .build();
+        Document::builder()
+            .meta(meta)
+            .nodes(vec![
+                text("Read this first.\n"),
+                heading(1, "Pumps"),
+                text("Check the pump seals monthly.\n"),
+                heading(2, "Seals"),
+                text("Replace worn seals.\n"),
+            ])
+            .build()
+    }
+
+    #[test]
+    fn indexing_sections_follow_headings() {
+        let sections = indexing_sections(&manual(), CHUNK_SIZE);
+        let titles = sections
+            .iter()
+            .map(|section| section.title().as_deref())
+            .collect::<Vec<Option<&str>>>();
+        assert_eq!(titles, [None, Some("Pumps"), Some("Seals")]);
+        assert_eq!(*sections[2].depth(), 2);
+        assert_eq!(sections[2].heading_path(), &["Pumps", "Seals"]);
+        assert!(sections[1].text().contains("Check the pump seals monthly."));
+        for section in &sections {
+            assert!(!section.chunks().is_empty());
+        }
+    }
+
+    #[test]
+    fn spool_document_writes_data_and_sections() {
+        let document = manual();
+        let (data, spool) = (TempPath::new("json"), TempPath::new("jsonl"));
+        spool_document(data.path(), spool.path(), |push| {
+            for node in document.nodes() {
+                push(std::slice::from_ref(node))?;
+            }
+            Ok(document.meta().clone())
+        })
+        .unwrap();
+
+        let mut nodes = vec![];
+        DocumentReader::new(File::open(data.path()).unwrap())
+            .for_each_node(|node| {
+                nodes.push(node);
+                Ok(())
             })
-            .collect::<Vec<Context>>();
-        results.extend(result);
+            .unwrap();
+        assert_eq!(nodes, *document.nodes());
+        let titles = |sections: Vec<Section>| {
+            sections
+                .into_iter()
+                .map(|section| (section.title().clone(), section.lines().clone()))
+                .collect::<Vec<(Option<String>, Vec<String>)>>()
+        };
+        let spooled = SectionSpool::read(spool.path())
+            .unwrap()
+            .collect::<Result<Vec<Section>>>()
+            .unwrap();
+        assert_eq!(titles(spooled), titles(document.sections()));
     }
 
-    results.sort_by(|a, b| b.score().partial_cmp(&a.score()).unwrap());
-    let mut contexts = vec![];
-    let mut current_tokens = 0;
+    #[test]
+    fn graphs_round_trip_through_a_folder() {
+        let folder = std::env::temp_dir().join(format!("local-search-{}", std::process::id()));
+        let graph = crate::fixtures::random_graph(4, 8, 42);
+        save_graph_to_folder(&folder, "manual", &graph, &manual()).unwrap();
+        std::fs::create_dir_all(folder.join("empty")).unwrap();
 
-    for context in results {
-        current_tokens = current_tokens + context.data().len() / 4;
+        let graphs = load_graphs_from_folder(&folder).unwrap();
+        assert!(load_graph_from_folder(&folder, "empty").unwrap().is_none());
+        std::fs::remove_dir_all(&folder).unwrap();
 
-        if current_tokens > max_tokens {
-            break;
+        assert_eq!(ids(&graphs), ["graph"]);
+        for node in graph.node_map().values() {
+            assert_eq!(
+                graphs[0].node_map()[node.id()].embeddings(),
+                node.embeddings()
+            );
         }
-        contexts.push(context);
+        assert!(graphs[0].ann_index().is_none());
     }
-    Ok(contexts)
 }
@@ -7,8 +7,9 @@ use composer::{
 use database as db;
 use db::{put_task, CallbackTask, Task};
 use indexer::{
-    utils::{get_embedding_model, load_graphs_from_s3, search_graph},
-    EmbeddingModel,
+    rerank::{CrossEncoderModel, RerankOptions},
+    utils::{get_embedding_registry, get_reranker_model, load_graphs_from_s3, search_graph},
+    EmbeddingModel, EmbeddingRegistry, SearchOptions,
 };
 use lambda_http::{
     http::StatusCode, run, service_fn, Error, IntoResponse, Request, RequestPayloadExt, Response,
@@ -20,7 +21,8 @@ use tracing::log::info;
 struct Context {
     s3_client: aws_sdk_s3::Client,
     dynamodb_client: aws_sdk_dynamodb::Client,
-    model: EmbeddingModel,
+    registry: EmbeddingRegistry,
+    reranker: Option<CrossEncoderModel>,
     composer: Composer,
 }
 
@@ -38,12 +40,14 @@ async fn main() -> Result<(), Error> {
     let config = aws_config::load_defaults(BehaviorVersion::v2023_11_09()).await;
     let s3_client = aws_sdk_s3::Client::new(&config);
     let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
-    let model = get_embedding_model(&resources_path).await?;
+    let registry = get_embedding_registry(&resources_path).await?;
+    let reranker = get_reranker_model(&resources_path).await?;
     let composer = get_composer(1800).await?;
     let context = Context {
         s3_client,
         dynamodb_client,
-        model,
+        registry,
+        reranker,
         composer,
     };
     let context_ref = &context;
@@ -67,7 +71,8 @@ async fn process_request(
     Context {
         s3_client,
         dynamodb_client,
-        model,
+        registry,
+        reranker,
         composer,
     }: &Context,
 ) -> Result<impl IntoResponse, Error> {
@@ -101,11 +106,16 @@ async fn process_request(
                         .collect();
                     let graphs =
                         load_graphs_from_s3(&s3_client, &bucket_name, document_keys).await?;
-                    let nodes = search_graph(graphs, &query, model).await?;
-                    let (message, context) =
+                    let options = SearchOptions::builder()
+                        .rerank(reranker.as_ref().map(|_| RerankOptions::default()))
+                        .build();
+                    let model = EmbeddingModel::from(registry.resolve_query(&graphs)?);
+                    let nodes =
+                        search_graph(&graphs, &query, &model, reranker.as_ref(), &options).await?;
+                    let (message, context, sources) =
                         compose_message_with_graph(composer, nodes, &query, 2048).await?;
 
-                    json!({ "message": message, "context": context})
+                    json!({ "message": message, "context": context, "sources": sources })
                 }
             };
 
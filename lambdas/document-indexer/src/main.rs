@@ -1,13 +1,16 @@
 use aws_config::BehaviorVersion;
 use aws_lambda_events::dynamodb::Event;
 use database::{
-    delete_task, from_item, get_document, put_task, update_document_index_state, CallbackTask,
-    Task, TaskKind,
+    delete_task, from_item, get_document, get_documents_by_group_id, put_task,
+    update_document_index_state, CallbackTask, Task, TaskKind,
 };
-use indexer::IndexingMeta;
+use indexer::{graph::DocumentMetadata, IndexingMeta};
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use serde_json::json;
 
+// Most recent documents of the group linked to an indexed document.
+const LINKED_DOCUMENTS: usize = 20;
+
 struct Context {
     s3_client: aws_sdk_s3::Client,
     dynamodb_client: aws_sdk_dynamodb::Client,
@@ -49,11 +52,37 @@ async fn process_request(event: LambdaEvent<Event>, context: &Context) -> Result
                     let document_id = task.document_id().to_owned();
                     let file_key = task.file_key().to_owned();
                     let external_link = task.external_link().clone().unwrap_or("".to_string());
+                    let document = get_document(&context.dynamodb_client, &document_id).await?;
+                    let file_type = task
+                        .filename()
+                        .rsplit_once(".")
+                        .map(|(_, extension)| extension.to_lowercase());
+                    let metadata = DocumentMetadata::builder()
+                        .creator(Some(task.creator_id().to_string()))
+                        .creation_time(Some(*document.creation_time()))
+                        .file_type(file_type)
+                        .tags(document.tags().clone())
+                        .build();
                     let meta = IndexingMeta::builder()
                         .id(common::generate_id())
                         .title(task.filename().to_string())
                         .external_link(external_link)
+                        .metadata(metadata)
                         .build();
+                    let mut related_documents =
+                        get_documents_by_group_id(&context.dynamodb_client, document.group_id())
+                            .await?
+                            .into_iter()
+                            .filter(|related| {
+                                related.id() != &document_id && related.index_state() == "ready"
+                            })
+                            .collect::<Vec<_>>();
+                    related_documents.sort_by(|a, b| b.creation_time().cmp(a.creation_time()));
+                    let related_keys = related_documents
+                        .iter()
+                        .take(LINKED_DOCUMENTS)
+                        .map(|related| related.id().to_string())
+                        .collect::<Vec<String>>();
 
                     indexer::utils::build_index(
                         &context.s3_client,
@@ -61,6 +90,7 @@ async fn process_request(event: LambdaEvent<Event>, context: &Context) -> Result
                         &document_id,
                         &file_key,
                         true,
+                        &related_keys,
                     )
                     .await
                     .map_err(|e| anyhow::anyhow!(e.to_string()))?;
@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use aws_config::BehaviorVersion;
+use aws_lambda_events::dynamodb::Event;
+use database::{
+    delete_task, from_item, get_collection, get_documents_by_group_id, put_task,
+    update_document_index_state, CallbackTask, ReindexScope, ReindexTask, Task, TaskKind,
+};
+use indexer::{
+    utils::{load_embedding_model, rebuild_index},
+    ModelId,
+};
+use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use serde_json::json;
+use tracing::{info, warn};
+
+struct Context {
+    s3_client: aws_sdk_s3::Client,
+    dynamodb_client: aws_sdk_dynamodb::Client,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        // disable printing the name of the module in every log line.
+        .with_target(false)
+        // disabling time is handy because CloudWatch will add the ingestion time.
+        .without_time()
+        .init();
+
+    //Get config from environment.
+    let config = aws_config::load_defaults(BehaviorVersion::v2023_11_09()).await;
+    let s3_client = aws_sdk_s3::Client::new(&config);
+    let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
+    let context = Context {
+        s3_client,
+        dynamodb_client,
+    };
+    let context_ref = &context;
+    run(service_fn(move |event: LambdaEvent<Event>| async move {
+        Ok::<(), Error>(process_request(event, context_ref).await?)
+    }))
+    .await?;
+    Ok(())
+}
+
+async fn process_request(event: LambdaEvent<Event>, context: &Context) -> Result<(), Error> {
+    if let Some(record) = event.payload.records.last() {
+        if record.event_name == "INSERT" {
+            let task: Task = from_item(record.change.new_image.clone())?;
+            let task_id = task.id().to_string();
+            match task.kind() {
+                TaskKind::ReindexTask(task) => {
+                    match task.scope() {
+                        ReindexScope::Document(document_id) => {
+                            reindex_document(context, task, document_id).await?
+                        }
+                        ReindexScope::Collection(collection_id) => {
+                            let collection =
+                                get_collection(&context.dynamodb_client, collection_id).await?;
+                            split_task(context, task, collection.document_ids().clone()).await?
+                        }
+                        ReindexScope::Group(group_id) => {
+                            let document_ids =
+                                get_documents_by_group_id(&context.dynamodb_client, group_id)
+                                    .await?
+                                    .iter()
+                                    .map(|document| document.id().to_string())
+                                    .collect();
+                            split_task(context, task, document_ids).await?
+                        }
+                    };
+                    delete_task(&context.dynamodb_client, &task_id).await?;
+                }
+
+                _ => {}
+            };
+        }
+    }
+
+    Ok(())
+}
+
+// Queues one document task per document, so every document is re-indexed in
+// its own invocation.
+async fn split_task(
+    context: &Context,
+    task: &ReindexTask,
+    document_ids: Vec<String>,
+) -> Result<(), Error> {
+    let total = document_ids.len();
+    for (index, document_id) in document_ids.into_iter().enumerate() {
+        let document_task = ReindexTask::builder()
+            .model_id(task.model_id().to_string())
+            .scope(ReindexScope::Document(document_id))
+            .creator_id(task.creator_id().to_string())
+            .callback_url(task.callback_url().clone())
+            .position(Some(index + 1))
+            .total(Some(total))
+            .build();
+        let task = Task::builder().kind(document_task.into()).build();
+        put_task(&context.dynamodb_client, task).await?;
+    }
+    info!("queued {} documents for re-indexing", total);
+
+    Ok(())
+}
+
+// Rebuilds the graph of a document. The document keeps its current graph
+// and stays searchable when re-indexing fails.
+async fn reindex_document(
+    context: &Context,
+    task: &ReindexTask,
+    document_id: &str,
+) -> Result<(), Error> {
+    let resources_path = common::vars::get_app_resources_path()?;
+    let model_id = ModelId::from_str(task.model_id())?;
+
+    update_document_index_state(&context.dynamodb_client, document_id, "reindexing").await?;
+    let result = match load_embedding_model(&resources_path, model_id).await {
+        Ok(model) => rebuild_index(&context.s3_client, document_id, model).await,
+        Err(err) => Err(err),
+    };
+    update_document_index_state(&context.dynamodb_client, document_id, "ready").await?;
+
+    let progress = match (task.position(), task.total()) {
+        (Some(position), Some(total)) => format!("{}/{}", position, total),
+        _ => "1/1".to_string(),
+    };
+    let data = match result {
+        Ok(report) => {
+            info!("re-indexed document: {} ({})", document_id, progress);
+            json!({
+                "document_id": document_id,
+                "model_id": task.model_id(),
+                "progress": progress,
+                "status": "done",
+                "added": report.added(),
+                "kept": report.kept(),
+                "removed": report.removed()
+            })
+        }
+        Err(err) => {
+            warn!(
+                "failed to re-index document: {} ({}): {}",
+                document_id, progress, err
+            );
+            json!({
+                "document_id": document_id,
+                "model_id": task.model_id(),
+                "progress": progress,
+                "status": "failed",
+                "error": err.to_string()
+            })
+        }
+    };
+
+    if let Some(callback_url) = task.callback_url() {
+        let callback_task = CallbackTask::builder()
+            .data(data)
+            .callback_url(callback_url.to_string())
+            .caller_id(task.creator_id().to_string())
+            .build();
+        let task = Task::builder().kind(callback_task.into()).build();
+        put_task(&context.dynamodb_client, task).await?;
+    }
+
+    Ok(())
+}
@@ -2,6 +2,7 @@ use common::extract_sub_from_jwt;
 use database::{
     check_group_member, get_collections_by_group_id, get_documents_by_group_id, get_group,
 };
+use indexer::utils::find_duplicates_in_s3;
 use lambda_http::{Request, RequestExt};
 use route_recognizer::Router;
 
@@ -11,6 +12,7 @@ enum GetRoutes {
     GetGroup,
     GetGroupDocuments,
     GetGroupCollections,
+    GetGroupDuplicates,
 }
 
 pub(crate) async fn process_get_request(
@@ -24,6 +26,7 @@ pub(crate) async fn process_get_request(
     router.add("/groups/:id", GetRoutes::GetGroup);
     router.add("/groups/:id/documents", GetRoutes::GetGroupDocuments);
     router.add("/groups/:id/collections", GetRoutes::GetGroupCollections);
+    router.add("/groups/:id/duplicates", GetRoutes::GetGroupDuplicates);
     let routing = router.recognize(&request.raw_http_path());
 
     match routing {
@@ -73,6 +76,28 @@ pub(crate) async fn process_get_request(
                     None => Err(anyhow::anyhow!("missing group id")),
                 }
             }
+            GetRoutes::GetGroupDuplicates => {
+                let id = routing.params().find("id");
+                match id {
+                    Some(id) => {
+                        check_group_member(&context.dynamodb_client, id, &user_id).await?;
+                        let documents =
+                            get_documents_by_group_id(&context.dynamodb_client, id).await?;
+                        let document_keys = documents
+                            .iter()
+                            .filter(|document| document.index_state() == "ready")
+                            .map(|document| document.id().as_str())
+                            .collect();
+                        let bucket_name = common::vars::get_app_document_bucket()?;
+                        let result =
+                            find_duplicates_in_s3(&context.s3_client, &bucket_name, document_keys)
+                                .await?;
+
+                        Ok(serde_json::to_value(result)?)
+                    }
+                    None => Err(anyhow::anyhow!("missing group id")),
+                }
+            }
         },
         Err(_) => Err(anyhow::anyhow!("Not found")),
     }
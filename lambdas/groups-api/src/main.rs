@@ -16,6 +16,7 @@ use put_handler::process_put_request;
 
 struct Context {
     pub dynamodb_client: aws_sdk_dynamodb::Client,
+    pub s3_client: aws_sdk_s3::Client,
 }
 
 #[tokio::main]
@@ -29,7 +30,11 @@ async fn main() -> Result<(), Error> {
         .init();
     let config = aws_config::load_defaults(BehaviorVersion::v2023_11_09()).await;
     let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
-    let context = Context { dynamodb_client };
+    let s3_client = aws_sdk_s3::Client::new(&config);
+    let context = Context {
+        dynamodb_client,
+        s3_client,
+    };
     let context_ref = &context;
 
     run(service_fn(move |req: Request| async move {
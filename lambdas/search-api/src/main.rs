@@ -1,10 +1,12 @@
 use aws_config::BehaviorVersion;
 use common::extract_sub_from_jwt;
+use composer::utils::context_budget;
 use database as db;
 use db::{get_documents, put_task, CallbackTask, Task};
 use indexer::{
-    utils::{get_embedding_model, load_graphs_from_s3, search_context},
-    EmbeddingModel,
+    rerank::CrossEncoderModel,
+    utils::{get_embedding_registry, get_reranker_model, load_graphs_from_s3, search_context},
+    EmbeddingModel, EmbeddingRegistry, SearchFilter, SearchOptions,
 };
 use lambda_http::{
     http::StatusCode, run, service_fn, Error, IntoResponse, Request, RequestPayloadExt, Response,
@@ -13,10 +15,16 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::info;
 
+// Encoding used to count context tokens when the request names no chat model.
+const DEFAULT_CHAT_MODEL: &str = "gpt-3.5-turbo";
+// Tokens left for the answer when the request does not set them.
+const DEFAULT_COMPLETION_TOKENS: usize = 512;
+
 struct Context {
     s3_client: aws_sdk_s3::Client,
     dynamodb_client: aws_sdk_dynamodb::Client,
-    model: EmbeddingModel,
+    registry: EmbeddingRegistry,
+    reranker: Option<CrossEncoderModel>,
 }
 
 #[tokio::main]
@@ -33,11 +41,13 @@ async fn main() -> Result<(), Error> {
     let config = aws_config::load_defaults(BehaviorVersion::v2023_11_09()).await;
     let s3_client = aws_sdk_s3::Client::new(&config);
     let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
-    let model = get_embedding_model(&resources_path).await?;
+    let registry = get_embedding_registry(&resources_path).await?;
+    let reranker = get_reranker_model(&resources_path).await?;
     let context = Context {
         s3_client,
         dynamodb_client,
-        model,
+        registry,
+        reranker,
     };
     let context_ref = &context;
     run(service_fn(move |req: Request| async move {
@@ -52,6 +62,10 @@ pub struct Payload {
     query: String,
     document_ids: Vec<String>,
     max_tokens: Option<usize>,
+    chat_model: Option<String>,
+    completion_tokens: Option<usize>,
+    options: Option<SearchOptions>,
+    filter: Option<SearchFilter>,
     callback_url: Option<String>,
 }
 
@@ -60,7 +74,8 @@ async fn process_request(
     Context {
         s3_client,
         dynamodb_client,
-        model,
+        registry,
+        reranker,
     }: &Context,
 ) -> Result<impl IntoResponse, Error> {
     let token = request.headers().get("Authorization").unwrap().to_str()?;
@@ -73,6 +88,15 @@ async fn process_request(
             let query = payload.query;
             let document_ids = payload.document_ids;
             let max_tokens = payload.max_tokens.unwrap_or(1024);
+            let chat_model = payload.chat_model.as_deref().unwrap_or(DEFAULT_CHAT_MODEL);
+            let completion_tokens = payload
+                .completion_tokens
+                .unwrap_or(DEFAULT_COMPLETION_TOKENS);
+            let budget = context_budget(chat_model, &query, completion_tokens, max_tokens)?;
+            let mut options = payload.options.unwrap_or_default();
+            if payload.filter.is_some() {
+                options.set_filter(payload.filter);
+            }
             info!("new request: {:?} document: {:?}", query, document_ids);
             if document_ids.is_empty() || query.is_empty() {
                 Ok(json!({}))
@@ -85,7 +109,16 @@ async fn process_request(
                     .map(|x| x.id().as_str())
                     .collect::<Vec<&str>>();
                 let graphs = load_graphs_from_s3(&s3_client, &bucket_name, document_keys).await?;
-                let contexts = search_context(graphs, &query, model, max_tokens).await?;
+                let model = EmbeddingModel::from(registry.resolve_query(&graphs)?);
+                let contexts = search_context(
+                    &graphs,
+                    &query,
+                    &model,
+                    reranker.as_ref(),
+                    &budget,
+                    &options,
+                )
+                .await?;
 
                 if let Some(callback_url) = payload.callback_url {
                     let callback_task = CallbackTask::builder()
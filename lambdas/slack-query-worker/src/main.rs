@@ -15,8 +15,11 @@ use database::{
     TaskKind,
 };
 use indexer::{
-    utils::{get_embedding_model, load_graphs_from_s3, search_graph},
+    rerank::{CrossEncoderModel, RerankOptions},
+    utils::{get_embedding_registry, get_reranker_model, load_graphs_from_s3, search_graph},
     EmbeddingModel,
+    EmbeddingRegistry,
+    SearchOptions,
 };
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
 use tracing::info;
@@ -24,7 +27,8 @@ use tracing::info;
 struct Context {
     s3_client: aws_sdk_s3::Client,
     dynamodb_client: aws_sdk_dynamodb::Client,
-    model: EmbeddingModel,
+    registry: EmbeddingRegistry,
+    reranker: Option<CrossEncoderModel>,
     composer: Composer,
 }
 
@@ -43,12 +47,14 @@ async fn main() -> Result<(), Error> {
     let config = aws_config::load_from_env().await;
     let s3_client = aws_sdk_s3::Client::new(&config);
     let dynamodb_client = aws_sdk_dynamodb::Client::new(&config);
-    let model = get_embedding_model(&resources_path).await?;
+    let registry = get_embedding_registry(&resources_path).await?;
+    let reranker = get_reranker_model(&resources_path).await?;
     let composer = get_composer(2000).await?;
     let context = Context {
         s3_client,
         dynamodb_client,
-        model,
+        registry,
+        reranker,
         composer,
     };
     let context_ref = &context;
@@ -83,8 +89,19 @@ async fn process_request(event: LambdaEvent<Event>, context: &Context) -> Result
                     let graphs =
                         load_graphs_from_s3(&context.s3_client, &bucket_name, document_keys)
                             .await?;
-                    let nodes = search_graph(graphs, &task.text(), &context.model).await?;
-                    let (message, _) =
+                    let model = EmbeddingModel::from(context.registry.resolve_query(&graphs)?);
+                    let options = SearchOptions::builder()
+                        .rerank(context.reranker.as_ref().map(|_| RerankOptions::default()))
+                        .build();
+                    let nodes = search_graph(
+                        &graphs,
+                        &task.text(),
+                        &model,
+                        context.reranker.as_ref(),
+                        &options,
+                    )
+                    .await?;
+                    let (message, _, _) =
                         compose_message_with_graph(&context.composer, nodes, &task.text(), 1500)
                             .await?;
                     let message_task = SlackMessageTask::builder()